@@ -1,11 +1,63 @@
+use crate::attachments::{Upload, read_multipart};
+use crate::audit::{AuditAction, AuditActor, AuditEntry, AuditFilter, AuditSource};
+use crate::auth::{ApiKey, AuthenticatedKey, Scope};
+use crate::backups::Backup;
+use crate::cache::{etag_matches, etag_matches_strong};
 use crate::common::*;
+use crate::error::{ApiError, FieldError};
+use crate::webhooks::{WEBHOOK_EVENTS, Webhook, WebhookDelivery};
 use axum::{
-    extract::{Path, State},
-    http::StatusCode,
-    response::Json,
+    Extension,
+    extract::{FromRequest, Multipart, Path, Query, Request, State, rejection::QueryRejection},
+    http::{HeaderMap, StatusCode, header},
+    response::{IntoResponse, Json, Response},
 };
 use log::info;
+use rss::{Category, Channel, Enclosure, Item, Source};
 use serde::Deserialize;
+use sha2::{Digest, Sha256};
+
+// Path parameters, routes without a feed segment operate on the default feed
+#[derive(Deserialize)]
+pub struct FeedPath {
+    feed: Option<String>,
+}
+
+impl FeedPath {
+    fn feed_id(&self) -> String {
+        self.feed
+            .clone()
+            .unwrap_or_else(|| DEFAULT_FEED.to_string())
+    }
+}
+
+#[derive(Deserialize)]
+pub struct ItemPath {
+    feed: Option<String>,
+    id: String,
+}
+
+impl ItemPath {
+    fn feed_id(&self) -> String {
+        self.feed
+            .clone()
+            .unwrap_or_else(|| DEFAULT_FEED.to_string())
+    }
+}
+
+#[derive(Deserialize)]
+pub struct BackupPath {
+    feed: Option<String>,
+    id: String,
+}
+
+impl BackupPath {
+    fn feed_id(&self) -> String {
+        self.feed
+            .clone()
+            .unwrap_or_else(|| DEFAULT_FEED.to_string())
+    }
+}
 
 // API data structures
 #[derive(Deserialize)]
@@ -13,6 +65,194 @@ pub struct ApiNewItem {
     title: String,
     description: Option<String>,
     link: Option<String>,
+    author: Option<String>,
+    comments: Option<String>,
+    #[serde(default)]
+    categories: Vec<ApiCategory>,
+    enclosure: Option<ApiEnclosure>,
+    source: Option<ApiSource>,
+    // A timestamp or "never", items without one follow the feed's retention
+    expires_at: Option<String>,
+    // Items with a future publish time are left out of the feed until then
+    publish_at: Option<String>,
+}
+
+#[derive(Deserialize, serde::Serialize)]
+pub struct ApiCategory {
+    name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    domain: Option<String>,
+}
+
+#[derive(Deserialize, serde::Serialize)]
+pub struct ApiEnclosure {
+    url: String,
+    // Size in bytes
+    #[serde(default)]
+    length: u64,
+    mime_type: Option<String>,
+}
+
+#[derive(Deserialize, serde::Serialize)]
+pub struct ApiSource {
+    url: String,
+    title: Option<String>,
+}
+
+impl ApiNewItem {
+    fn fields(&self) -> ItemFields {
+        ItemFields {
+            title: self.title.clone(),
+            description: self.description.clone(),
+            link: self.link.clone(),
+            author: self.author.clone(),
+            comments: self.comments.clone(),
+            categories: self
+                .categories
+                .iter()
+                .map(|category| Category {
+                    name: category.name.trim().to_string(),
+                    domain: category.domain.clone().filter(|s| !s.trim().is_empty()),
+                })
+                .collect(),
+            enclosure: self.enclosure.as_ref().map(|enclosure| Enclosure {
+                url: enclosure.url.clone(),
+                length: enclosure.length.to_string(),
+                mime_type: enclosure
+                    .mime_type
+                    .clone()
+                    .filter(|s| !s.trim().is_empty())
+                    .unwrap_or_else(|| "application/octet-stream".to_string()),
+            }),
+            source: self.source.as_ref().map(|source| Source {
+                url: source.url.clone(),
+                title: source.title.clone().filter(|s| !s.trim().is_empty()),
+            }),
+        }
+    }
+
+    fn expiry(&self) -> Result<Option<ItemExpiry>, String> {
+        self.expires_at
+            .as_deref()
+            .filter(|s| !s.trim().is_empty())
+            .map(ItemExpiry::parse)
+            .transpose()
+    }
+
+    fn publish_at(&self) -> Result<Option<chrono::DateTime<chrono::Utc>>, String> {
+        self.publish_at
+            .as_deref()
+            .filter(|s| !s.trim().is_empty())
+            .map(parse_datetime)
+            .transpose()
+    }
+
+    // Checks every field, returning the parsed expiry and publish times
+    fn validate(&self) -> Result<ItemTimes, Vec<FieldError>> {
+        let mut errors = Vec::new();
+        if self.title.trim().is_empty() {
+            errors.push(FieldError::new("title", "Title is required"));
+        }
+        let expiry = self
+            .expiry()
+            .map_err(|e| errors.push(FieldError::new("expires_at", &e)))
+            .ok()
+            .flatten();
+        let publish_at = self
+            .publish_at()
+            .map_err(|e| errors.push(FieldError::new("publish_at", &e)))
+            .ok()
+            .flatten();
+        if errors.is_empty() {
+            Ok((expiry, publish_at))
+        } else {
+            Err(errors)
+        }
+    }
+
+    // Checks the payload and turns it into a create or update operation
+    fn operation(&self, id: Option<String>) -> Result<ItemOperation, Vec<FieldError>> {
+        let (expiry, publish_at) = self.validate()?;
+
+        let Some(id) = id else {
+            let mut item = create_item_from_fields(self.fields());
+            set_item_expiry(&mut item, expiry);
+            // Times that have already passed publish immediately
+            if let Some(publish_at) = publish_at.filter(|p| *p > chrono::Utc::now()) {
+                schedule_item(&mut item, publish_at);
+            }
+            return Ok(ItemOperation::Create(Box::new(item)));
+        };
+        Ok(ItemOperation::Update {
+            id,
+            fields: Box::new(self.fields()),
+            expiry,
+            publish_at,
+        })
+    }
+}
+
+// Largest number of operations accepted in one bulk request
+const MAX_BULK_OPERATIONS: usize = 1000;
+
+#[derive(Deserialize)]
+pub struct ApiBulkRequest {
+    operations: Vec<ApiBulkOperation>,
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "op", rename_all = "lowercase")]
+pub enum ApiBulkOperation {
+    Create { item: ApiNewItem },
+    Update { id: String, item: ApiNewItem },
+    Delete { id: String },
+}
+
+impl ApiBulkOperation {
+    fn name(&self) -> &'static str {
+        match self {
+            ApiBulkOperation::Create { .. } => "create",
+            ApiBulkOperation::Update { .. } => "update",
+            ApiBulkOperation::Delete { .. } => "delete",
+        }
+    }
+
+    fn id(&self) -> Option<String> {
+        match self {
+            ApiBulkOperation::Create { .. } => None,
+            ApiBulkOperation::Update { id, .. } | ApiBulkOperation::Delete { id } => {
+                Some(id.clone())
+            }
+        }
+    }
+
+    fn operation(&self) -> Result<ItemOperation, Vec<FieldError>> {
+        match self {
+            ApiBulkOperation::Create { item } => item.operation(None),
+            ApiBulkOperation::Update { id, item } => item.operation(Some(id.clone())),
+            ApiBulkOperation::Delete { id } => Ok(ItemOperation::Delete { id: id.clone() }),
+        }
+    }
+}
+
+// Outcome of one operation in a bulk request
+#[derive(serde::Serialize)]
+pub struct ApiBulkResult {
+    op: &'static str,
+    id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    item: Option<ApiItem>,
+}
+
+#[derive(Deserialize)]
+pub struct ApiNewFeed {
+    id: String,
+    title: String,
+    link: String,
+    description: String,
+    max_item_age_seconds: Option<u64>,
+    max_items: Option<usize>,
+    max_served_items: Option<usize>,
 }
 
 #[derive(serde::Serialize)]
@@ -22,22 +262,296 @@ pub struct ApiResponse<T> {
     message: String,
 }
 
+impl<T> ApiResponse<T> {
+    fn ok(data: T, message: &str) -> Json<Self> {
+        Json(ApiResponse {
+            success: true,
+            data: Some(data),
+            message: message.to_string(),
+        })
+    }
+}
+
+// Parsed expiry and publish time of a new or edited item
+type ItemTimes = (Option<ItemExpiry>, Option<chrono::DateTime<chrono::Utc>>);
+
+type ApiResult<T> = Result<Json<ApiResponse<T>>, ApiError>;
+
+// JSON body extractor that reports malformed bodies as an `ApiError`
+pub struct ApiJson<T>(pub T);
+
+impl<S: Send + Sync, T: serde::de::DeserializeOwned> FromRequest<S> for ApiJson<T> {
+    type Rejection = ApiError;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        Json::<T>::from_request(req, state)
+            .await
+            .map(|Json(value)| ApiJson(value))
+            .map_err(|e| ApiError::BadRequest(e.body_text()))
+    }
+}
+
+// Envelope for a page of results, `total` counts every match before `offset` and `limit` apply
+#[derive(serde::Serialize)]
+pub struct ApiPage<T> {
+    success: bool,
+    data: Option<Vec<T>>,
+    message: String,
+    total: usize,
+    offset: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    limit: Option<usize>,
+}
+
+pub type ApiItemPage = ApiPage<ApiItem>;
+
+#[derive(Deserialize)]
+pub struct ApiItemsQuery {
+    limit: Option<usize>,
+    #[serde(default)]
+    offset: usize,
+    q: Option<String>,
+    category: Option<String>,
+    since: Option<String>,
+    until: Option<String>,
+}
+
+impl ApiItemsQuery {
+    fn filter(&self) -> Result<ItemFilter, ApiError> {
+        let non_empty =
+            |value: &Option<String>| value.clone().filter(|value| !value.trim().is_empty());
+        let parse = |name: &str, value: &Option<String>| {
+            non_empty(value)
+                .map(|value| parse_datetime(&value).map_err(|e| ApiError::invalid(name, &e)))
+                .transpose()
+        };
+        Ok(ItemFilter {
+            query: non_empty(&self.q),
+            category: non_empty(&self.category),
+            since: parse("since", &self.since)?,
+            until: parse("until", &self.until)?,
+        })
+    }
+}
+
+const DEFAULT_AUDIT_LIMIT: usize = 100;
+const MAX_AUDIT_LIMIT: usize = 1000;
+
+#[derive(Deserialize)]
+pub struct ApiAuditQuery {
+    feed: Option<String>,
+    item: Option<String>,
+    action: Option<AuditAction>,
+    source: Option<AuditSource>,
+    actor: Option<String>,
+    limit: Option<usize>,
+    #[serde(default)]
+    offset: usize,
+}
+
+impl ApiAuditQuery {
+    fn filter(&self) -> AuditFilter {
+        let non_empty =
+            |value: &Option<String>| value.clone().filter(|value| !value.trim().is_empty());
+        AuditFilter {
+            feed_id: non_empty(&self.feed),
+            item_id: non_empty(&self.item),
+            action: self.action,
+            source: self.source,
+            actor: non_empty(&self.actor),
+        }
+    }
+}
+
+#[derive(serde::Serialize)]
+pub struct ApiAuditEntry {
+    timestamp: String,
+    action: AuditAction,
+    feed: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    item: Option<String>,
+    actor: Option<String>,
+    source: AuditSource,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    before: Option<ApiItem>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    after: Option<ApiItem>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    backup: Option<String>,
+}
+
+impl From<&AuditEntry> for ApiAuditEntry {
+    fn from(entry: &AuditEntry) -> Self {
+        ApiAuditEntry {
+            timestamp: entry.timestamp.clone(),
+            action: entry.action,
+            feed: entry.feed_id.clone(),
+            item: entry.item_id.clone(),
+            actor: entry.actor.clone(),
+            source: entry.source,
+            before: entry.before.as_ref().map(ApiItem::from),
+            after: entry.after.as_ref().map(ApiItem::from),
+            backup: entry.backup_id.clone(),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct ApiNewKey {
+    name: String,
+    scope: Scope,
+}
+
+#[derive(serde::Serialize)]
+pub struct ApiKeyInfo {
+    id: String,
+    name: String,
+    scope: Scope,
+    created: String,
+    // Only returned once, when the key is created
+    #[serde(skip_serializing_if = "Option::is_none")]
+    token: Option<String>,
+}
+
+impl From<&ApiKey> for ApiKeyInfo {
+    fn from(key: &ApiKey) -> Self {
+        ApiKeyInfo {
+            id: key.id.clone(),
+            name: key.name.clone(),
+            scope: key.scope,
+            created: key.created.clone(),
+            token: None,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct ApiNewWebhook {
+    url: String,
+    secret: Option<String>,
+    #[serde(default)]
+    events: Vec<String>,
+    feed: Option<String>,
+}
+
+impl ApiNewWebhook {
+    fn validate(&self) -> Result<(), Vec<FieldError>> {
+        let mut errors = Vec::new();
+        match reqwest::Url::parse(self.url.trim()) {
+            Ok(url) if matches!(url.scheme(), "http" | "https") => {}
+            _ => errors.push(FieldError::new("url", "URL must be an http or https URL")),
+        }
+        if self.secret.as_ref().is_some_and(|s| s.trim().is_empty()) {
+            errors.push(FieldError::new("secret", "Secret can't be empty"));
+        }
+        for (index, event) in self.events.iter().enumerate() {
+            if !WEBHOOK_EVENTS.contains(&event.as_str()) {
+                errors.push(FieldError::new(
+                    &format!("events[{}]", index),
+                    &format!("Unknown event '{}'", event),
+                ));
+            }
+        }
+        if self
+            .feed
+            .as_ref()
+            .is_some_and(|feed| !is_valid_feed_id(feed))
+        {
+            errors.push(FieldError::new("feed", "Feed ID is not valid"));
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+#[derive(serde::Serialize)]
+pub struct ApiWebhook {
+    id: String,
+    url: String,
+    events: Vec<String>,
+    feed: Option<String>,
+    created: String,
+    // Only returned once, when the webhook is created
+    #[serde(skip_serializing_if = "Option::is_none")]
+    secret: Option<String>,
+}
+
+impl From<&Webhook> for ApiWebhook {
+    fn from(webhook: &Webhook) -> Self {
+        ApiWebhook {
+            id: webhook.id.clone(),
+            url: webhook.url.clone(),
+            events: webhook.events.clone(),
+            feed: webhook.feed.clone(),
+            created: webhook.created.clone(),
+            secret: None,
+        }
+    }
+}
+
+#[derive(serde::Serialize)]
+pub struct ApiBackup {
+    id: String,
+    created: String,
+    size: u64,
+}
+
+impl From<&Backup> for ApiBackup {
+    fn from(backup: &Backup) -> Self {
+        ApiBackup {
+            id: backup.id.clone(),
+            created: backup.created.to_rfc3339(),
+            size: backup.size,
+        }
+    }
+}
+
 #[derive(serde::Serialize)]
 pub struct ApiItem {
     id: String,
     title: String,
     description: Option<String>,
     link: Option<String>,
+    author: Option<String>,
+    comments: Option<String>,
+    categories: Vec<ApiCategory>,
+    enclosure: Option<ApiEnclosure>,
+    source: Option<ApiSource>,
     pub_date: Option<String>,
+    updated: Option<String>,
+    expires_at: Option<String>,
+    scheduled: bool,
+    // Only set for items in the trash
+    #[serde(skip_serializing_if = "Option::is_none")]
+    deleted_at: Option<String>,
 }
 
-// API route handlers
-pub async fn api_get_items(State(state): State<AppState>) -> Json<ApiResponse<Vec<ApiItem>>> {
-    let channel = state.channel.lock().unwrap();
-    let items: Vec<ApiItem> = channel
-        .items()
-        .iter()
-        .map(|item| ApiItem {
+#[derive(serde::Serialize)]
+pub struct ApiFeed {
+    id: String,
+    title: String,
+    link: String,
+    description: String,
+    max_item_age_seconds: Option<u64>,
+    max_items: Option<usize>,
+    max_served_items: Option<usize>,
+    item_count: usize,
+}
+
+// A restored feed, with the enclosure URLs whose uploaded files have been removed since
+#[derive(serde::Serialize)]
+pub struct ApiRestoredFeed {
+    #[serde(flatten)]
+    feed: ApiFeed,
+    missing_attachments: Vec<String>,
+}
+
+impl From<&Item> for ApiItem {
+    fn from(item: &Item) -> Self {
+        ApiItem {
             id: item
                 .guid()
                 .map(|g| g.value().to_string())
@@ -45,137 +559,801 @@ pub async fn api_get_items(State(state): State<AppState>) -> Json<ApiResponse<Ve
             title: item.title().unwrap_or("Untitled").to_string(),
             description: item.description().map(|s| s.to_string()),
             link: item.link().map(|s| s.to_string()),
+            author: item.author().map(|s| s.to_string()),
+            comments: item.comments().map(|s| s.to_string()),
+            categories: item
+                .categories()
+                .iter()
+                .map(|category| ApiCategory {
+                    name: category.name().to_string(),
+                    domain: category.domain().map(|s| s.to_string()),
+                })
+                .collect(),
+            enclosure: item.enclosure().map(|enclosure| ApiEnclosure {
+                url: enclosure.url().to_string(),
+                length: enclosure.length().parse().unwrap_or_default(),
+                mime_type: Some(enclosure.mime_type().to_string()),
+            }),
+            source: item.source().map(|source| ApiSource {
+                url: source.url().to_string(),
+                title: source.title().map(|s| s.to_string()),
+            }),
             pub_date: item.pub_date().map(|s| s.to_string()),
-        })
+            updated: item_updated(item).map(|d| d.to_rfc2822()),
+            expires_at: item_expiry(item).map(|e| e.to_string()),
+            scheduled: is_item_scheduled(item),
+            deleted_at: item_deleted_at(item).map(|d| d.to_rfc2822()),
+        }
+    }
+}
+
+impl ApiFeed {
+    fn new(id: &str, channel: &Channel) -> Self {
+        ApiFeed {
+            id: id.to_string(),
+            title: channel.title().to_string(),
+            link: channel.link().to_string(),
+            description: channel.description().to_string(),
+            max_item_age_seconds: feed_max_item_age(channel),
+            max_items: feed_max_items(channel),
+            max_served_items: feed_max_served_items(channel),
+            item_count: channel
+                .items()
+                .iter()
+                .filter(|item| !is_item_deleted(item))
+                .count(),
+        }
+    }
+}
+
+impl From<ApiNewFeed> for FeedSettings {
+    fn from(payload: ApiNewFeed) -> Self {
+        FeedSettings {
+            id: payload.id.trim().to_string(),
+            title: payload.title,
+            link: payload.link,
+            description: payload.description,
+            max_item_age_seconds: payload.max_item_age_seconds,
+            max_items: payload.max_items,
+            max_served_items: payload.max_served_items,
+        }
+    }
+}
+
+fn validate_new_feed(payload: &ApiNewFeed) -> Result<(), ApiError> {
+    let mut errors = Vec::new();
+    if !is_valid_feed_id(payload.id.trim()) {
+        errors.push(FieldError::new(
+            "id",
+            "Feed id must be 1-64 lowercase letters, digits, dashes or underscores",
+        ));
+    }
+    if payload.title.trim().is_empty() {
+        errors.push(FieldError::new("title", "Title is required"));
+    }
+    if payload.link.trim().is_empty() {
+        errors.push(FieldError::new("link", "Link is required"));
+    }
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(ApiError::Validation(errors))
+    }
+}
+
+fn feed_not_found() -> ApiError {
+    ApiError::NotFound("Feed not found".to_string())
+}
+
+fn default_feed_kept() -> ApiError {
+    ApiError::Conflict(format!(
+        "The default feed '{}' can't be renamed or deleted",
+        DEFAULT_FEED
+    ))
+}
+
+fn item_not_found() -> ApiError {
+    ApiError::NotFound("Item not found".to_string())
+}
+
+// API route handlers
+pub async fn api_get_feeds(State(state): State<AppState>) -> ApiResult<Vec<ApiFeed>> {
+    let feeds = state.feeds.lock().unwrap();
+    let api_feeds: Vec<ApiFeed> = feeds
+        .iter()
+        .map(|(id, channel)| ApiFeed::new(id, channel))
+        .collect();
+
+    Ok(ApiResponse::ok(api_feeds, "Feeds retrieved successfully"))
+}
+
+pub async fn api_get_feed(
+    State(state): State<AppState>,
+    Path(feed_id): Path<String>,
+) -> ApiResult<ApiFeed> {
+    let feeds = state.feeds.lock().unwrap();
+    let channel = feeds.get(&feed_id).ok_or_else(feed_not_found)?;
+    Ok(ApiResponse::ok(
+        ApiFeed::new(&feed_id, channel),
+        "Feed retrieved successfully",
+    ))
+}
+
+pub async fn api_add_feed(
+    State(state): State<AppState>,
+    ApiJson(payload): ApiJson<ApiNewFeed>,
+) -> ApiResult<ApiFeed> {
+    validate_new_feed(&payload)?;
+
+    let settings = FeedSettings::from(payload);
+    let feed_id = settings.id.clone();
+    let channel = add_feed(axum::extract::State(state), settings)?.ok_or_else(|| {
+        ApiError::Conflict(format!("A feed with the id '{}' already exists", feed_id))
+    })?;
+    Ok(ApiResponse::ok(
+        ApiFeed::new(&feed_id, &channel),
+        "Feed added successfully",
+    ))
+}
+
+pub async fn api_edit_feed(
+    State(state): State<AppState>,
+    Path(feed_id): Path<String>,
+    ApiJson(payload): ApiJson<ApiNewFeed>,
+) -> ApiResult<ApiFeed> {
+    validate_new_feed(&payload)?;
+    if !state.feeds.lock().unwrap().contains_key(&feed_id) {
+        return Err(feed_not_found());
+    }
+
+    if feed_id == DEFAULT_FEED && payload.id != feed_id {
+        return Err(default_feed_kept());
+    }
+
+    let settings = FeedSettings::from(payload);
+    let new_feed_id = settings.id.clone();
+    // The feed exists, so a failed edit means the new id is taken
+    let channel = edit_feed(
+        axum::extract::State(state),
+        axum::extract::Path(feed_id),
+        settings,
+    )?
+    .ok_or_else(|| {
+        ApiError::Conflict(format!(
+            "A feed with the id '{}' already exists",
+            new_feed_id
+        ))
+    })?;
+    info!("Feed edited successfully: {}", new_feed_id);
+    Ok(ApiResponse::ok(
+        ApiFeed::new(&new_feed_id, &channel),
+        "Feed updated successfully",
+    ))
+}
+
+pub async fn api_delete_feed(
+    State(state): State<AppState>,
+    Path(feed_id): Path<String>,
+) -> ApiResult<()> {
+    if feed_id == DEFAULT_FEED {
+        return Err(default_feed_kept());
+    }
+    delete_feed(
+        axum::extract::State(state),
+        axum::extract::Path(feed_id.clone()),
+    )?
+    .ok_or_else(feed_not_found)?;
+    Ok(ApiResponse::ok((), "Feed deleted successfully"))
+}
+
+pub async fn api_get_items(
+    State(state): State<AppState>,
+    Path(path): Path<FeedPath>,
+    query: Result<Query<ApiItemsQuery>, QueryRejection>,
+) -> Result<Json<ApiItemPage>, ApiError> {
+    let Query(query) = query.map_err(|e| ApiError::BadRequest(e.body_text()))?;
+    let filter = query.filter()?;
+
+    let feeds = state.feeds.lock().unwrap();
+    let channel = feeds.get(&path.feed_id()).ok_or_else(feed_not_found)?;
+    let matching: Vec<&Item> = channel
+        .items()
+        .iter()
+        .filter(|item| !is_item_deleted(item) && filter.matches(item))
+        .collect();
+    let items: Vec<ApiItem> = matching
+        .iter()
+        .skip(query.offset)
+        .take(query.limit.unwrap_or(usize::MAX))
+        .map(|item| ApiItem::from(*item))
         .collect();
 
-    Json(ApiResponse {
+    Ok(Json(ApiItemPage {
         success: true,
         data: Some(items),
         message: "Items retrieved successfully".to_string(),
-    })
+        total: matching.len(),
+        offset: query.offset,
+        limit: query.limit,
+    }))
+}
+
+// Strong ETag over the item's serialized content, so any change to the item changes it
+fn api_item_etag(item: &ApiItem) -> String {
+    let json = serde_json::to_vec(item).expect("Failed to serialize item");
+    format!("\"{:x}\"", Sha256::digest(&json))
+}
+
+pub async fn api_get_item(
+    State(state): State<AppState>,
+    Path(path): Path<ItemPath>,
+    headers: HeaderMap,
+) -> Result<Response, ApiError> {
+    let item = {
+        let feeds = state.feeds.lock().unwrap();
+        let channel = feeds.get(&path.feed_id()).ok_or_else(feed_not_found)?;
+        channel
+            .items()
+            .iter()
+            .find(|item| is_live_item(item, &path.id))
+            .map(ApiItem::from)
+            .ok_or_else(item_not_found)?
+    };
+
+    let etag = api_item_etag(&item);
+    if etag_matches(&headers, header::IF_NONE_MATCH, &etag) {
+        return Ok((StatusCode::NOT_MODIFIED, [(header::ETAG, etag)]).into_response());
+    }
+
+    Ok((
+        [(header::ETAG, etag)],
+        ApiResponse::ok(item, "Item retrieved successfully"),
+    )
+        .into_response())
+}
+
+// New item body, either JSON or multipart form data with the item as JSON in an `item`
+// part and the attachment in a `file` part
+pub struct ApiNewItemRequest {
+    item: ApiNewItem,
+    upload: Option<Upload>,
+}
+
+impl<S: Send + Sync> FromRequest<S> for ApiNewItemRequest {
+    type Rejection = ApiError;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let is_multipart = req
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|value| value.starts_with("multipart/form-data"));
+        if !is_multipart {
+            let ApiJson(item) = ApiJson::<ApiNewItem>::from_request(req, state).await?;
+            return Ok(Self { item, upload: None });
+        }
+
+        let multipart = Multipart::from_request(req, state)
+            .await
+            .map_err(|e| ApiError::BadRequest(e.body_text()))?;
+        let (fields, upload) = read_multipart(multipart)
+            .await
+            .map_err(ApiError::BadRequest)?;
+        let item = fields
+            .iter()
+            .find(|(name, _)| name == "item")
+            .map(|(_, value)| serde_json::from_str::<ApiNewItem>(value))
+            .ok_or_else(|| ApiError::BadRequest("Missing item part".to_string()))?
+            .map_err(|e| ApiError::BadRequest(format!("Invalid item part: {}", e)))?;
+        Ok(Self { item, upload })
+    }
 }
 
 pub async fn api_add_item(
     State(state): State<AppState>,
-    Json(payload): Json<ApiNewItem>,
-) -> Result<Json<ApiResponse<ApiItem>>, StatusCode> {
-    if payload.title.trim().is_empty() {
-        return Ok(Json(ApiResponse {
-            success: false,
-            data: None,
-            message: "Title is required".to_string(),
-        }));
+    Path(path): Path<FeedPath>,
+    key: Option<Extension<AuthenticatedKey>>,
+    ApiNewItemRequest {
+        item: payload,
+        upload,
+    }: ApiNewItemRequest,
+) -> ApiResult<ApiItem> {
+    let (expiry, publish_at) = payload.validate().map_err(ApiError::Validation)?;
+    if !state.feeds.lock().unwrap().contains_key(&path.feed_id()) {
+        return Err(feed_not_found());
     }
 
-    let item = create_item(
-        payload.title.clone(),
-        payload.description.clone().filter(|s| !s.trim().is_empty()),
-        payload.link.clone().filter(|s| !s.trim().is_empty()),
-    );
+    let mut fields = payload.fields();
+    let has_upload = upload.is_some();
+    // An uploaded file becomes the item's enclosure
+    if let Some(upload) = upload {
+        fields.enclosure = Some(state.attachments.save(&upload)?);
+    }
 
-    let api_item = ApiItem {
-        id: item
-            .guid()
-            .map(|g| g.value().to_string())
-            .unwrap_or_default(),
-        title: payload.title,
-        description: payload.description,
-        link: payload.link,
-        pub_date: item.pub_date().map(|s| s.to_string()),
-    };
+    let mut item = create_item_from_fields(fields);
+    set_item_expiry(&mut item, expiry);
+    // Times that have already passed publish immediately
+    if let Some(publish_at) = publish_at.filter(|p| *p > chrono::Utc::now()) {
+        schedule_item(&mut item, publish_at);
+    }
+    let api_item = ApiItem::from(&item);
 
-    add_item(axum::extract::State(state), item.clone());
-    info!("Item added successfully: {}", item.guid().unwrap().value);
+    let added = add_item(
+        axum::extract::State(state.clone()),
+        axum::extract::Path(path.feed_id()),
+        item.clone(),
+        &AuditActor::api(key.as_deref()),
+    );
+    if !matches!(added, Ok(Some(_))) {
+        if let Some(enclosure) = item.enclosure().filter(|_| has_upload) {
+            state.attachments.remove(enclosure.url());
+        }
+        added?.ok_or_else(feed_not_found)?;
+    }
+    info!("Item added successfully: {}", api_item.id);
 
-    Ok(Json(ApiResponse {
-        success: true,
-        data: Some(api_item),
-        message: "Item added successfully".to_string(),
-    }))
+    Ok(ApiResponse::ok(api_item, "Item added successfully"))
 }
 
 pub async fn api_delete_item(
     State(state): State<AppState>,
-    Path(item_id): Path<String>,
-) -> Json<ApiResponse<()>> {
-    let return_item_id = delete_item(
+    Path(path): Path<ItemPath>,
+    key: Option<Extension<AuthenticatedKey>>,
+) -> ApiResult<()> {
+    delete_item(
         axum::extract::State(state),
-        axum::extract::Path(item_id.clone()),
-    );
-    info!("Item deleted successfully: {}", item_id.clone());
+        axum::extract::Path((path.feed_id(), path.id.clone())),
+        &AuditActor::api(key.as_deref()),
+    )?
+    .ok_or_else(item_not_found)?;
+    info!("Item moved to the trash: {}", path.id);
 
-    if return_item_id.is_some() {
-        Json(ApiResponse {
-            success: true,
-            data: Some(()),
-            message: "Item deleted successfully".to_string(),
-        })
+    Ok(ApiResponse::ok((), "Item moved to the trash"))
+}
+
+pub async fn api_get_trash(
+    State(state): State<AppState>,
+    Path(path): Path<FeedPath>,
+) -> ApiResult<Vec<ApiItem>> {
+    let feeds = state.feeds.lock().unwrap();
+    let channel = feeds.get(&path.feed_id()).ok_or_else(feed_not_found)?;
+    let items = channel
+        .items()
+        .iter()
+        .filter(|item| is_item_deleted(item))
+        .map(ApiItem::from)
+        .collect();
+
+    Ok(ApiResponse::ok(
+        items,
+        "Deleted items retrieved successfully",
+    ))
+}
+
+pub async fn api_restore_item(
+    State(state): State<AppState>,
+    Path(path): Path<ItemPath>,
+    key: Option<Extension<AuthenticatedKey>>,
+) -> ApiResult<ApiItem> {
+    let item = restore_item(
+        &state,
+        &path.feed_id(),
+        &path.id,
+        &AuditActor::api(key.as_deref()),
+    )?
+    .ok_or_else(|| ApiError::NotFound("Item not found in the trash".to_string()))?;
+
+    Ok(ApiResponse::ok(
+        ApiItem::from(&item),
+        "Item restored successfully",
+    ))
+}
+
+pub async fn api_edit_item(
+    State(state): State<AppState>,
+    Path(path): Path<ItemPath>,
+    key: Option<Extension<AuthenticatedKey>>,
+    ApiJson(payload): ApiJson<ApiNewItem>,
+) -> ApiResult<ApiItem> {
+    let (expiry, publish_at) = payload.validate().map_err(ApiError::Validation)?;
+
+    let item = edit_item(
+        axum::extract::State(state),
+        axum::extract::Path((path.feed_id(), path.id)),
+        payload.fields(),
+        expiry,
+        publish_at,
+        &AuditActor::api(key.as_deref()),
+    )?
+    .ok_or_else(item_not_found)?;
+
+    let api_item = ApiItem::from(&item);
+    info!("Item edited successfully: {}", api_item.id);
+    Ok(ApiResponse::ok(api_item, "Item updated successfully"))
+}
+
+// Applies a JSON merge patch (RFC 7396), null removes a member and objects merge recursively
+fn merge_patch(target: &mut serde_json::Value, patch: &serde_json::Value) {
+    let serde_json::Value::Object(patch) = patch else {
+        *target = patch.clone();
+        return;
+    };
+    if !target.is_object() {
+        *target = serde_json::Value::Object(serde_json::Map::new());
+    }
+    let target = target.as_object_mut().expect("Patch target is an object");
+    for (key, value) in patch {
+        if value.is_null() {
+            target.remove(key);
+        } else {
+            merge_patch(
+                target.entry(key.clone()).or_insert(serde_json::Value::Null),
+                value,
+            );
+        }
+    }
+}
+
+// The editable part of an item in the shape of `ApiNewItem`, for patches to apply to
+fn api_item_document(item: &Item) -> serde_json::Value {
+    let mut document = serde_json::to_value(ApiItem::from(item)).expect("Failed to serialize item");
+    if let Some(document) = document.as_object_mut() {
+        for key in ["id", "pub_date", "updated", "scheduled", "deleted_at"] {
+            document.remove(key);
+        }
+        // Keep a scheduled item's publish time unless the patch changes it
+        if is_item_scheduled(item)
+            && let Some(pub_date) = item.pub_date()
+        {
+            document.insert("publish_at".to_string(), pub_date.into());
+        }
+    }
+    document
+}
+
+pub async fn api_patch_item(
+    State(state): State<AppState>,
+    Path(path): Path<ItemPath>,
+    headers: HeaderMap,
+    key: Option<Extension<AuthenticatedKey>>,
+    ApiJson(patch): ApiJson<serde_json::Value>,
+) -> Result<Response, ApiError> {
+    let feed_id = path.feed_id();
+    // The lock is held from the If-Match check until the edit is saved
+    let mut feeds = state.feeds.lock().unwrap();
+    let channel = feeds.get(&feed_id).ok_or_else(feed_not_found)?;
+    let item = channel
+        .items()
+        .iter()
+        .find(|item| is_live_item(item, &path.id))
+        .ok_or_else(item_not_found)?;
+
+    if headers.contains_key(header::IF_MATCH)
+        && !etag_matches_strong(
+            &headers,
+            header::IF_MATCH,
+            &api_item_etag(&ApiItem::from(item)),
+        )
+    {
+        return Err(ApiError::PreconditionFailed(
+            "Item has changed since it was fetched".to_string(),
+        ));
+    }
+
+    // Like any other member, a null publish_at removes the schedule, publishing the item now
+    let unschedule =
+        is_item_scheduled(item) && patch.get("publish_at").is_some_and(|v| v.is_null());
+    let mut document = api_item_document(item);
+    merge_patch(&mut document, &patch);
+    // A removed title fails validation like an empty one, rather than as a malformed patch
+    if let Some(document) = document.as_object_mut() {
+        document.entry("title").or_insert_with(|| "".into());
+    }
+    let payload: ApiNewItem = serde_json::from_value(document)
+        .map_err(|e| ApiError::BadRequest(format!("Invalid patch: {}", e)))?;
+    let (expiry, publish_at) = payload.validate().map_err(ApiError::Validation)?;
+    let publish_at = if unschedule {
+        // A passed publish time publishes a scheduled item within the same edit
+        Some(chrono::Utc::now())
     } else {
-        Json(ApiResponse {
-            success: false,
-            data: None,
-            message: "Item not found".to_string(),
+        publish_at
+    };
+
+    let item = edit_locked_item(
+        &state,
+        &mut feeds,
+        &feed_id,
+        &path.id,
+        payload.fields(),
+        expiry,
+        publish_at,
+        &AuditActor::api(key.as_deref()),
+    )?
+    .ok_or_else(item_not_found)?;
+    let api_item = ApiItem::from(&item);
+    info!("Item patched successfully: {}", api_item.id);
+
+    Ok((
+        [(header::ETAG, api_item_etag(&api_item))],
+        ApiResponse::ok(api_item, "Item updated successfully"),
+    )
+        .into_response())
+}
+
+pub async fn api_bulk_items(
+    State(state): State<AppState>,
+    Path(path): Path<FeedPath>,
+    key: Option<Extension<AuthenticatedKey>>,
+    ApiJson(payload): ApiJson<ApiBulkRequest>,
+) -> ApiResult<Vec<ApiBulkResult>> {
+    if payload.operations.len() > MAX_BULK_OPERATIONS {
+        return Err(ApiError::invalid(
+            "operations",
+            &format!(
+                "At most {} operations are allowed per request",
+                MAX_BULK_OPERATIONS
+            ),
+        ));
+    }
+
+    // Nothing is applied unless every operation is valid and succeeds, field errors are
+    // reported against the operation they belong to
+    let mut errors = Vec::new();
+    let mut operations = Vec::new();
+    for (index, operation) in payload.operations.iter().enumerate() {
+        match operation.operation() {
+            Ok(operation) => operations.push(operation),
+            Err(fields) => errors.extend(fields.into_iter().map(|error| {
+                FieldError::new(
+                    &format!("operations[{}].item.{}", index, error.field),
+                    &error.message,
+                )
+            })),
+        }
+    }
+    if !errors.is_empty() {
+        return Err(ApiError::Validation(errors));
+    }
+
+    let outcomes = apply_item_operations(
+        &state,
+        &path.feed_id(),
+        operations,
+        &AuditActor::api(key.as_deref()),
+    )?
+    .ok_or_else(feed_not_found)?;
+    let errors: Vec<FieldError> = outcomes
+        .iter()
+        .enumerate()
+        .filter_map(|(index, outcome)| {
+            outcome
+                .as_ref()
+                .err()
+                .map(|message| FieldError::new(&format!("operations[{}].id", index), message))
         })
+        .collect();
+    if !errors.is_empty() {
+        return Err(ApiError::Validation(errors));
     }
+
+    let results: Vec<ApiBulkResult> = payload
+        .operations
+        .iter()
+        .zip(outcomes)
+        .map(|(operation, outcome)| {
+            let item = outcome.ok().flatten().map(|item| ApiItem::from(&item));
+            ApiBulkResult {
+                op: operation.name(),
+                id: item.as_ref().map(|item| item.id.clone()).or(operation.id()),
+                item,
+            }
+        })
+        .collect();
+    info!(
+        "Bulk request applied {} operations to '{}'",
+        results.len(),
+        path.feed_id()
+    );
+
+    Ok(ApiResponse::ok(results, "All operations applied"))
 }
 
-pub async fn api_edit_item(
+pub async fn api_get_keys(State(state): State<AppState>) -> ApiResult<Vec<ApiKeyInfo>> {
+    let api_keys = state.api_keys.lock().unwrap();
+    let keys: Vec<ApiKeyInfo> = api_keys.keys().iter().map(ApiKeyInfo::from).collect();
+
+    Ok(ApiResponse::ok(keys, "API keys retrieved successfully"))
+}
+
+pub async fn api_add_key(
     State(state): State<AppState>,
-    Path(item_id): Path<String>,
-    Json(payload): Json<ApiNewItem>,
-) -> Result<Json<ApiResponse<ApiItem>>, StatusCode> {
-    if payload.title.trim().is_empty() {
-        return Ok(Json(ApiResponse {
-            success: false,
-            data: None,
-            message: "Title is required".to_string(),
-        }));
+    ApiJson(payload): ApiJson<ApiNewKey>,
+) -> ApiResult<ApiKeyInfo> {
+    if payload.name.trim().is_empty() {
+        return Err(ApiError::invalid("name", "Name is required"));
+    }
+
+    let mut api_keys = state.api_keys.lock().unwrap();
+    // The first key enables authentication, so it must be able to manage the others
+    if !api_keys.is_enabled() && payload.scope != Scope::Admin {
+        return Err(ApiError::Conflict(
+            "The first API key must have the admin scope".to_string(),
+        ));
     }
 
-    let mut found_item: Option<ApiItem> = None;
+    let (key, token) = api_keys
+        .create(payload.name.trim().to_string(), payload.scope)
+        .map_err(|e| ApiError::Internal(format!("Failed to save API keys: {}", e)))?;
+    info!("API key created: {} ({})", key.name, key.id);
+
+    let mut key_info = ApiKeyInfo::from(&key);
+    key_info.token = Some(token);
+    Ok(ApiResponse::ok(
+        key_info,
+        "API key created, store the token now as it will not be shown again",
+    ))
+}
 
+pub async fn api_delete_key(
+    State(state): State<AppState>,
+    Path(key_id): Path<String>,
+) -> ApiResult<()> {
+    let mut api_keys = state.api_keys.lock().unwrap();
+    if api_keys
+        .keys()
+        .iter()
+        .any(|key| key.id == key_id && key.from_env)
     {
-        let updated_item = edit_item(
-            axum::extract::State(state),
-            axum::extract::Path(item_id),
-            payload.title.clone(),
-            payload.description.clone(),
-            payload.link.clone(),
-        );
-        if updated_item.is_some() {
-            // Store the API representation for response
-            found_item = Some(ApiItem {
-                id: updated_item
-                    .as_ref()
-                    .unwrap()
-                    .guid()
-                    .map(|g| g.value().to_string())
-                    .unwrap_or_default(),
-                title: payload.title.clone(),
-                description: payload.description.clone(),
-                link: payload.link.clone(),
-                pub_date: updated_item
-                    .as_ref()
-                    .unwrap()
-                    .pub_date()
-                    .map(|s| s.to_string()),
-            });
-        }
-
-        if let Some(api_item) = found_item {
-            info!("Item edited successfully: {}", api_item.id);
-            Ok(Json(ApiResponse {
-                success: true,
-                data: Some(api_item),
-                message: "Item updated successfully".to_string(),
-            }))
-        } else {
-            Ok(Json(ApiResponse {
-                success: false,
-                data: None,
-                message: "Item not found".to_string(),
-            }))
-        }
+        return Err(ApiError::Conflict(
+            "API keys configured through the environment can't be deleted".to_string(),
+        ));
     }
+    let key = api_keys
+        .delete(&key_id)
+        .map_err(|e| ApiError::Internal(format!("Failed to save API keys: {}", e)))?
+        .ok_or_else(|| ApiError::NotFound("API key not found".to_string()))?;
+    info!("API key deleted: {} ({})", key.name, key.id);
+
+    Ok(ApiResponse::ok((), "API key deleted successfully"))
+}
+
+pub async fn api_get_webhooks(State(state): State<AppState>) -> ApiResult<Vec<ApiWebhook>> {
+    let webhooks = state.webhooks.webhooks();
+
+    Ok(ApiResponse::ok(
+        webhooks.iter().map(ApiWebhook::from).collect(),
+        "Webhooks retrieved successfully",
+    ))
+}
+
+pub async fn api_add_webhook(
+    State(state): State<AppState>,
+    ApiJson(payload): ApiJson<ApiNewWebhook>,
+) -> ApiResult<ApiWebhook> {
+    payload.validate().map_err(ApiError::Validation)?;
+
+    let webhook = state
+        .webhooks
+        .create(
+            payload.url.trim().to_string(),
+            payload.secret,
+            payload.events,
+            payload.feed,
+        )
+        .map_err(|e| ApiError::Internal(format!("Failed to save webhooks: {}", e)))?;
+
+    let mut webhook_info = ApiWebhook::from(&webhook);
+    webhook_info.secret = Some(webhook.secret);
+    Ok(ApiResponse::ok(
+        webhook_info,
+        "Webhook created, store the secret now as it will not be shown again",
+    ))
+}
+
+pub async fn api_delete_webhook(
+    State(state): State<AppState>,
+    Path(webhook_id): Path<String>,
+) -> ApiResult<()> {
+    state
+        .webhooks
+        .delete(&webhook_id)
+        .map_err(|e| ApiError::Internal(format!("Failed to save webhooks: {}", e)))?
+        .ok_or_else(|| ApiError::NotFound("Webhook not found".to_string()))?;
+
+    Ok(ApiResponse::ok((), "Webhook deleted successfully"))
+}
+
+pub async fn api_get_webhook_deliveries(
+    State(state): State<AppState>,
+) -> ApiResult<Vec<WebhookDelivery>> {
+    Ok(ApiResponse::ok(
+        state.webhooks.deliveries(),
+        "Webhook deliveries retrieved successfully",
+    ))
+}
+
+pub async fn api_get_audit(
+    State(state): State<AppState>,
+    query: Result<Query<ApiAuditQuery>, QueryRejection>,
+) -> Result<Json<ApiPage<ApiAuditEntry>>, ApiError> {
+    let Query(query) = query.map_err(|e| ApiError::BadRequest(e.body_text()))?;
+    let limit = query
+        .limit
+        .unwrap_or(DEFAULT_AUDIT_LIMIT)
+        .min(MAX_AUDIT_LIMIT);
+    let (entries, total) = state
+        .audit
+        .query(&query.filter(), query.offset, limit)
+        .map_err(|e| ApiError::Internal(format!("Failed to read the audit log: {}", e)))?;
+
+    Ok(Json(ApiPage {
+        success: true,
+        data: Some(entries.iter().map(ApiAuditEntry::from).collect()),
+        message: "Audit entries retrieved successfully".to_string(),
+        total,
+        offset: query.offset,
+        limit: Some(limit),
+    }))
+}
+
+#[derive(Deserialize)]
+pub struct ApiStreamQuery {
+    feed: Option<String>,
+}
+
+// Streams item changes as Server-Sent Events, to one feed with `?feed=` or to every feed
+pub async fn api_stream(
+    State(state): State<AppState>,
+    Query(query): Query<ApiStreamQuery>,
+) -> Result<impl IntoResponse, ApiError> {
+    let feed_id = query.feed.filter(|feed| !feed.trim().is_empty());
+    if let Some(feed_id) = &feed_id
+        && !state.feeds.lock().unwrap().contains_key(feed_id)
+    {
+        return Err(feed_not_found());
+    }
+
+    Ok(state.events.sse(feed_id))
+}
+
+pub async fn api_get_backups(
+    State(state): State<AppState>,
+    Path(path): Path<FeedPath>,
+) -> ApiResult<Vec<ApiBackup>> {
+    let backups = state
+        .backups
+        .list(&path.feed_id())
+        .map_err(|e| ApiError::Internal(format!("Failed to list backups: {}", e)))?;
+
+    Ok(ApiResponse::ok(
+        backups.iter().map(ApiBackup::from).collect(),
+        "Backups retrieved successfully",
+    ))
+}
+
+pub async fn api_restore_backup(
+    State(state): State<AppState>,
+    Path(path): Path<BackupPath>,
+    key: Option<Extension<AuthenticatedKey>>,
+) -> ApiResult<ApiRestoredFeed> {
+    let feed_id = path.feed_id();
+    let channel = restore_feed(&state, &feed_id, &path.id, &AuditActor::api(key.as_deref()))?
+        .ok_or_else(|| ApiError::NotFound("Backup not found".to_string()))?;
+    let missing_attachments = state.attachments.missing(channel.items());
+    let message = if missing_attachments.is_empty() {
+        "Feed restored from backup".to_string()
+    } else {
+        format!(
+            "Feed restored from backup, {} attachment files are missing",
+            missing_attachments.len()
+        )
+    };
+
+    Ok(ApiResponse::ok(
+        ApiRestoredFeed {
+            feed: ApiFeed::new(&feed_id, &channel),
+            missing_attachments,
+        },
+        &message,
+    ))
 }
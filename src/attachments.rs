@@ -0,0 +1,259 @@
+use crate::common::FEED_DIRECTORY;
+use crate::error::ApiError;
+use axum::{
+    body::Bytes,
+    extract::multipart::Multipart,
+    http::{HeaderMap, header},
+};
+use log::{info, warn};
+use rss::{Channel, Enclosure, Item};
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use uuid::Uuid;
+
+// Route uploaded attachments are served from
+pub const ATTACHMENTS_ROUTE: &str = "/attachments";
+
+const DEFAULT_MAX_ATTACHMENT_BYTES: u64 = 10 * 1024 * 1024;
+const DEFAULT_ALLOWED_ATTACHMENT_TYPES: &str = "image/*,audio/*,video/*,application/pdf";
+
+// A file received as part of a multipart request
+pub struct Upload {
+    pub file_name: String,
+    pub content_type: Option<String>,
+    pub data: Bytes,
+}
+
+// Uploaded files are stored flat under the attachments directory as `{uuid}.{ext}`
+pub struct AttachmentStore {
+    directory: PathBuf,
+    max_bytes: u64,
+    allowed_types: Vec<String>,
+}
+
+impl AttachmentStore {
+    pub fn new(directory: PathBuf, max_bytes: u64, allowed_types: Vec<String>) -> Self {
+        Self {
+            directory,
+            max_bytes,
+            allowed_types,
+        }
+    }
+
+    // Reads ATTACHMENTS_DIRECTORY, MAX_ATTACHMENT_BYTES and ALLOWED_ATTACHMENT_TYPES
+    pub fn load() -> Self {
+        let directory = env::var("ATTACHMENTS_DIRECTORY")
+            .unwrap_or_else(|_| format!("{}/attachments", FEED_DIRECTORY));
+        let max_bytes = match env::var("MAX_ATTACHMENT_BYTES") {
+            Ok(val) => val.parse::<u64>().unwrap_or_else(|_| {
+                warn!(
+                    "Invalid MAX_ATTACHMENT_BYTES value: '{}', using the default",
+                    val
+                );
+                DEFAULT_MAX_ATTACHMENT_BYTES
+            }),
+            Err(_) => DEFAULT_MAX_ATTACHMENT_BYTES,
+        };
+        let allowed_types = env::var("ALLOWED_ATTACHMENT_TYPES")
+            .unwrap_or_else(|_| DEFAULT_ALLOWED_ATTACHMENT_TYPES.to_string())
+            .split(',')
+            .map(|t| t.trim().to_ascii_lowercase())
+            .filter(|t| !t.is_empty())
+            .collect();
+        Self::new(PathBuf::from(directory), max_bytes, allowed_types)
+    }
+
+    pub fn max_bytes(&self) -> u64 {
+        self.max_bytes
+    }
+
+    // Allowed types are exact MIME types or wildcards like `image/*`
+    pub fn is_allowed(&self, mime_type: &str) -> bool {
+        let mime_type = mime_type.to_ascii_lowercase();
+        self.allowed_types
+            .iter()
+            .any(|allowed| match allowed.strip_suffix("/*") {
+                Some(prefix) => mime_type
+                    .split_once('/')
+                    .is_some_and(|(top, _)| top == prefix),
+                None => *allowed == mime_type || allowed == "*",
+            })
+    }
+
+    // Validates and stores an upload, returning the enclosure pointing at it. The URL is saved
+    // root-relative and made absolute when the feed is rendered, see `render_feed`
+    pub fn save(&self, upload: &Upload) -> Result<Enclosure, ApiError> {
+        if upload.data.len() as u64 > self.max_bytes {
+            return Err(ApiError::invalid(
+                "file",
+                &format!(
+                    "Attachment exceeds the maximum size of {} bytes",
+                    self.max_bytes
+                ),
+            ));
+        }
+
+        // The extension decides the type the file is later served with, so it wins over
+        // whatever the client claimed
+        let guessed = mime_guess::from_path(&upload.file_name).first_raw();
+        let mime_type = guessed
+            .map(str::to_string)
+            .or_else(|| upload.content_type.clone().filter(|t| !t.is_empty()))
+            .unwrap_or_else(|| "application/octet-stream".to_string());
+        if !self.is_allowed(&mime_type) {
+            return Err(ApiError::invalid(
+                "file",
+                &format!("Attachment type '{}' is not allowed", mime_type),
+            ));
+        }
+        let extension = match guessed {
+            Some(_) => PathBuf::from(&upload.file_name)
+                .extension()
+                .map(|e| e.to_string_lossy().to_ascii_lowercase()),
+            None => mime_guess::get_mime_extensions_str(&mime_type)
+                .and_then(|extensions| extensions.first())
+                .map(|e| e.to_string()),
+        }
+        .unwrap_or_else(|| "bin".to_string());
+
+        let file_name = format!("{}.{}", Uuid::new_v4(), extension);
+        fs::create_dir_all(&self.directory)
+            .and_then(|_| fs::write(self.directory.join(&file_name), &upload.data))
+            .map_err(|e| ApiError::Internal(format!("Failed to store attachment: {}", e)))?;
+        info!(
+            "Attachment stored: {} ({} bytes, {})",
+            file_name,
+            upload.data.len(),
+            mime_type
+        );
+
+        Ok(Enclosure {
+            url: format!("{}/{}", ATTACHMENTS_ROUTE, file_name),
+            length: upload.data.len().to_string(),
+            mime_type,
+        })
+    }
+
+    // Location of a stored attachment, None for names that could escape the directory
+    pub fn path(&self, file_name: &str) -> Option<PathBuf> {
+        let is_valid = file_name.split_once('.').is_some_and(|(id, extension)| {
+            Uuid::parse_str(id).is_ok()
+                && !extension.is_empty()
+                && extension.chars().all(|c| c.is_ascii_alphanumeric())
+        });
+        is_valid.then(|| self.directory.join(file_name))
+    }
+
+    // Deletes the file behind an enclosure URL if it points at a stored attachment
+    pub fn remove(&self, url: &str) {
+        let Some(path) = attachment_file_name(url).and_then(|name| self.path(name)) else {
+            return;
+        };
+        match fs::remove_file(&path) {
+            Ok(()) => info!("Attachment removed: {}", path.display()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => warn!("Failed to remove attachment {}: {}", path.display(), e),
+        }
+    }
+
+    // Enclosure URLs of the items that point at uploads whose file no longer exists, as after
+    // restoring a backup taken before an attachment was removed
+    pub fn missing(&self, items: &[Item]) -> Vec<String> {
+        items
+            .iter()
+            .filter_map(|item| item.enclosure().map(|e| e.url()))
+            .filter(|url| {
+                attachment_file_name(url)
+                    .is_some_and(|name| self.path(name).is_none_or(|path| !path.is_file()))
+            })
+            .map(str::to_string)
+            .collect()
+    }
+
+    // Deletes attachments of removed items that no remaining item in any feed refers to
+    pub fn remove_orphaned(&self, feeds: &BTreeMap<String, Channel>, removed: &[Item]) {
+        for url in removed
+            .iter()
+            .filter_map(|item| item.enclosure().map(|e| e.url()))
+        {
+            let Some(file_name) = attachment_file_name(url) else {
+                continue;
+            };
+            let is_referenced = feeds
+                .values()
+                .flat_map(|channel| channel.items())
+                .filter_map(|item| item.enclosure())
+                .any(|enclosure| attachment_file_name(enclosure.url()) == Some(file_name));
+            if !is_referenced {
+                self.remove(url);
+            }
+        }
+    }
+}
+
+// Name of the stored file an enclosure URL points at, if it is one of ours
+pub fn attachment_file_name(url: &str) -> Option<&str> {
+    let (_, file_name) = url.rsplit_once(&format!("{}/", ATTACHMENTS_ROUTE))?;
+    (!file_name.is_empty() && !file_name.contains('/')).then_some(file_name)
+}
+
+// Base URL feed links are built from, PUBLIC_URL or the request's own host. The host is lowercased
+// and only http or https are used, so equivalent requests get the same URL
+pub fn public_base_url(headers: &HeaderMap) -> String {
+    if let Ok(url) = env::var("PUBLIC_URL")
+        && !url.trim().is_empty()
+    {
+        return url.trim().trim_end_matches('/').to_string();
+    }
+    let host = headers
+        .get(header::HOST)
+        .and_then(|value| value.to_str().ok())
+        .map(|host| host.trim().trim_end_matches('.').to_ascii_lowercase())
+        .filter(|host| {
+            !host.is_empty()
+                && host
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | ':' | '[' | ']'))
+        })
+        .unwrap_or_else(|| "localhost".to_string());
+    let scheme = match headers
+        .get("X-Forwarded-Proto")
+        .and_then(|value| value.to_str().ok())
+    {
+        Some(proto) if proto.trim().eq_ignore_ascii_case("https") => "https",
+        _ => "http",
+    };
+    format!("{}://{}", scheme, host)
+}
+
+// Splits a multipart body into its text fields and the uploaded `file`, if any
+pub async fn read_multipart(
+    mut multipart: Multipart,
+) -> Result<(Vec<(String, String)>, Option<Upload>), String> {
+    let mut fields = Vec::new();
+    let mut upload = None;
+
+    while let Some(field) = multipart.next_field().await.map_err(|e| e.body_text())? {
+        let name = field.name().unwrap_or_default().to_string();
+        if name == "file" {
+            let file_name = field.file_name().unwrap_or_default().to_string();
+            let content_type = field.content_type().map(str::to_string);
+            let data = field.bytes().await.map_err(|e| e.body_text())?;
+            // Browsers send an empty part when no file was picked
+            if !file_name.is_empty() || !data.is_empty() {
+                upload = Some(Upload {
+                    file_name,
+                    content_type,
+                    data,
+                });
+            }
+        } else {
+            let value = field.text().await.map_err(|e| e.body_text())?;
+            fields.push((name, value));
+        }
+    }
+
+    Ok((fields, upload))
+}
@@ -0,0 +1,270 @@
+use crate::auth::AuthenticatedKey;
+use crate::common::FEED_DIRECTORY;
+use crate::session::Session;
+use log::warn;
+use rss::Item;
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::fmt;
+use std::fs::{self, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AuditAction {
+    Create,
+    Update,
+    Delete,
+    // Removed once it expired, aged out, or fell past the feed's item cap
+    Expire,
+    // Removed by the cleanup task after its time in the trash
+    Purge,
+    // A scheduled item whose publish time came
+    Publish,
+    // An item was taken out of the trash, or the whole feed was replaced by a backup
+    Restore,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AuditSource {
+    Web,
+    Api,
+    Cleanup,
+    Scheduler,
+}
+
+impl fmt::Display for AuditAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            AuditAction::Create => "create",
+            AuditAction::Update => "update",
+            AuditAction::Delete => "delete",
+            AuditAction::Expire => "expire",
+            AuditAction::Purge => "purge",
+            AuditAction::Publish => "publish",
+            AuditAction::Restore => "restore",
+        };
+        f.write_str(name)
+    }
+}
+
+impl fmt::Display for AuditSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            AuditSource::Web => "web",
+            AuditSource::Api => "api",
+            AuditSource::Cleanup => "cleanup",
+            AuditSource::Scheduler => "scheduler",
+        };
+        f.write_str(name)
+    }
+}
+
+// Who made a change, the name is the API key or web user, background tasks have none
+#[derive(Clone, Debug)]
+pub struct AuditActor {
+    pub name: Option<String>,
+    pub source: AuditSource,
+}
+
+impl AuditActor {
+    pub fn new(name: Option<String>, source: AuditSource) -> Self {
+        Self { name, source }
+    }
+
+    // Requests are anonymous while no API keys are configured
+    pub fn api(key: Option<&AuthenticatedKey>) -> Self {
+        Self::new(key.map(|key| key.name.clone()), AuditSource::Api)
+    }
+
+    pub fn web(session: &Session) -> Self {
+        Self::new(Some(session.username.clone()), AuditSource::Web)
+    }
+
+    pub fn system(source: AuditSource) -> Self {
+        Self::new(None, source)
+    }
+}
+
+// One line of the audit log, items are recorded as they were before and after the change
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct AuditEntry {
+    pub timestamp: String,
+    pub action: AuditAction,
+    pub feed_id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub item_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub actor: Option<String>,
+    pub source: AuditSource,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub before: Option<Item>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub after: Option<Item>,
+    // The backup a restore came from
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub backup_id: Option<String>,
+}
+
+impl AuditEntry {
+    // An item change, the item id is taken from whichever side of the change exists
+    pub fn item(
+        action: AuditAction,
+        feed_id: &str,
+        actor: &AuditActor,
+        before: Option<&Item>,
+        after: Option<&Item>,
+    ) -> Self {
+        let item_id = after
+            .or(before)
+            .and_then(|item| item.guid())
+            .map(|guid| guid.value().to_string());
+        Self {
+            timestamp: chrono::Utc::now().to_rfc3339(),
+            action,
+            feed_id: feed_id.to_string(),
+            item_id,
+            actor: actor.name.clone(),
+            source: actor.source,
+            before: before.cloned(),
+            after: after.cloned(),
+            backup_id: None,
+        }
+    }
+
+    pub fn restore(feed_id: &str, actor: &AuditActor, backup_id: &str) -> Self {
+        Self {
+            timestamp: chrono::Utc::now().to_rfc3339(),
+            action: AuditAction::Restore,
+            feed_id: feed_id.to_string(),
+            item_id: None,
+            actor: actor.name.clone(),
+            source: actor.source,
+            before: None,
+            after: None,
+            backup_id: Some(backup_id.to_string()),
+        }
+    }
+
+    // Title of the item the entry is about, for listings
+    pub fn title(&self) -> Option<&str> {
+        self.after
+            .as_ref()
+            .or(self.before.as_ref())
+            .and_then(|item| item.title())
+    }
+}
+
+// Criteria for narrowing down audit entries, unset criteria match everything
+#[derive(Default)]
+pub struct AuditFilter {
+    pub feed_id: Option<String>,
+    pub item_id: Option<String>,
+    pub action: Option<AuditAction>,
+    pub source: Option<AuditSource>,
+    pub actor: Option<String>,
+}
+
+impl AuditFilter {
+    pub fn matches(&self, entry: &AuditEntry) -> bool {
+        self.feed_id.as_ref().is_none_or(|id| *id == entry.feed_id)
+            && self
+                .item_id
+                .as_ref()
+                .is_none_or(|id| entry.item_id.as_ref() == Some(id))
+            && self.action.is_none_or(|action| action == entry.action)
+            && self.source.is_none_or(|source| source == entry.source)
+            && self
+                .actor
+                .as_ref()
+                .is_none_or(|actor| entry.actor.as_ref() == Some(actor))
+    }
+}
+
+// Append-only JSON lines file, entries are never rewritten
+pub struct AuditLog {
+    path: PathBuf,
+    // Keeps lines from concurrent changes from interleaving
+    lock: Mutex<()>,
+}
+
+impl AuditLog {
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            lock: Mutex::new(()),
+        }
+    }
+
+    // Reads AUDIT_LOG_FILE, by default the log is kept next to the feeds
+    pub fn load() -> Self {
+        let path = env::var("AUDIT_LOG_FILE")
+            .unwrap_or_else(|_| format!("{}/audit.jsonl", FEED_DIRECTORY));
+        Self::new(PathBuf::from(path))
+    }
+
+    fn append(&self, entries: &[AuditEntry]) -> std::io::Result<()> {
+        let mut lines = String::new();
+        for entry in entries {
+            lines.push_str(&serde_json::to_string(entry)?);
+            lines.push('\n');
+        }
+
+        let _guard = self.lock.lock().unwrap();
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        file.write_all(lines.as_bytes())?;
+        file.sync_data()
+    }
+
+    // Records changes that have already been saved, a failure is logged but doesn't undo them
+    pub fn record(&self, entries: &[AuditEntry]) {
+        if entries.is_empty() {
+            return;
+        }
+        if let Err(e) = self.append(entries) {
+            warn!("Failed to write {} audit entries: {}", entries.len(), e);
+        }
+    }
+
+    // Matching entries newest first, skipping `offset` and returning at most `limit`, along
+    // with the total number of matches
+    pub fn query(
+        &self,
+        filter: &AuditFilter,
+        offset: usize,
+        limit: usize,
+    ) -> std::io::Result<(Vec<AuditEntry>, usize)> {
+        let file = match fs::File::open(&self.path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok((Vec::new(), 0)),
+            Err(e) => return Err(e),
+        };
+
+        let mut entries = Vec::new();
+        for line in BufReader::new(file).lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            // A torn last line from a crash shouldn't hide the rest of the log
+            match serde_json::from_str::<AuditEntry>(&line) {
+                Ok(entry) if filter.matches(&entry) => entries.push(entry),
+                Ok(_) => {}
+                Err(e) => warn!("Skipping unreadable audit entry: {}", e),
+            }
+        }
+
+        let total = entries.len();
+        let page = entries.into_iter().rev().skip(offset).take(limit).collect();
+        Ok((page, total))
+    }
+}
@@ -0,0 +1,296 @@
+use crate::common::*;
+use crate::error::ApiError;
+use axum::{
+    extract::{Request, State},
+    http::{Method, header},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use log::{debug, info, warn};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{env, fs, io, path::Path, str::FromStr};
+use uuid::Uuid;
+
+// Permission levels, each scope includes the ones below it
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Scope {
+    Read,
+    Write,
+    Admin,
+}
+
+impl FromStr for Scope {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "read" => Ok(Scope::Read),
+            "write" => Ok(Scope::Write),
+            "admin" => Ok(Scope::Admin),
+            other => Err(format!("Unknown scope '{}'", other)),
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ApiKey {
+    pub id: String,
+    pub name: String,
+    pub scope: Scope,
+    pub token_hash: String,
+    pub created: String,
+    // Keys from the API_KEYS environment variable can't be managed through the API
+    #[serde(skip)]
+    pub from_env: bool,
+}
+
+// The key that authenticated the current request, stored in the request extensions
+#[derive(Clone)]
+pub struct AuthenticatedKey {
+    pub id: String,
+    pub name: String,
+    pub scope: Scope,
+}
+
+pub struct ApiKeyStore {
+    keys: Vec<ApiKey>,
+    path: String,
+    enabled: bool,
+    // Hash of API_BOOTSTRAP_TOKEN, which can only create the first key
+    bootstrap_hash: Option<String>,
+}
+
+pub fn hash_token(token: &str) -> String {
+    format!("{:x}", Sha256::digest(token.as_bytes()))
+}
+
+impl ApiKeyStore {
+    // Loads keys from API_KEYS (`name:scope:token` entries separated by commas) and the
+    // managed key file at API_KEYS_FILE. Until either exists the API is read only, and the
+    // first key can only be created with API_BOOTSTRAP_TOKEN
+    pub fn load() -> io::Result<Self> {
+        let path = env::var("API_KEYS_FILE")
+            .unwrap_or_else(|_| format!("{}/api_keys.json", FEED_DIRECTORY));
+        let mut keys = Vec::new();
+        let mut enabled = false;
+
+        if let Ok(value) = env::var("API_KEYS").map(|v| v.trim().to_string())
+            && !value.is_empty()
+        {
+            enabled = true;
+            for entry in value.split(',').filter(|e| !e.trim().is_empty()) {
+                let parts: Vec<&str> = entry.trim().splitn(3, ':').collect();
+                let [name, scope, token] = parts[..] else {
+                    warn!("Ignoring malformed API_KEYS entry, expected name:scope:token");
+                    continue;
+                };
+                match scope.parse::<Scope>() {
+                    Ok(scope) => keys.push(ApiKey {
+                        id: format!("env:{}", name),
+                        name: name.to_string(),
+                        scope,
+                        token_hash: hash_token(token),
+                        created: String::new(),
+                        from_env: true,
+                    }),
+                    Err(e) => warn!("Ignoring API_KEYS entry '{}': {}", name, e),
+                }
+            }
+        }
+
+        if Path::new(&path).exists() {
+            enabled = true;
+            let contents = fs::read_to_string(&path)?;
+            let managed: Vec<ApiKey> = serde_json::from_str(&contents)?;
+            keys.extend(managed);
+        }
+
+        let bootstrap_hash = env::var("API_BOOTSTRAP_TOKEN")
+            .ok()
+            .map(|token| token.trim().to_string())
+            .filter(|token| !token.is_empty())
+            .map(|token| hash_token(&token));
+        if enabled {
+            info!("API authentication enabled with {} keys", keys.len());
+        } else if bootstrap_hash.is_some() {
+            warn!("No API keys configured, create the first one with API_BOOTSTRAP_TOKEN");
+        } else {
+            warn!("No API keys configured, the API is read only until API_KEYS is set");
+        }
+
+        Ok(Self {
+            keys,
+            path,
+            enabled,
+            bootstrap_hash,
+        })
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn keys(&self) -> &[ApiKey] {
+        &self.keys
+    }
+
+    pub fn authenticate(&self, token: &str) -> Option<&ApiKey> {
+        let token_hash = hash_token(token);
+        self.keys.iter().find(|key| key.token_hash == token_hash)
+    }
+
+    // Whether the token is the bootstrap token, which stops working once a key exists
+    pub fn is_bootstrap_token(&self, token: &str) -> bool {
+        !self.enabled && self.bootstrap_hash.as_deref() == Some(hash_token(token).as_str())
+    }
+
+    // Creates a managed key and returns it with its token, which is never stored in plain text
+    pub fn create(&mut self, name: String, scope: Scope) -> io::Result<(ApiKey, String)> {
+        let token = format!("fc_{}{}", Uuid::new_v4().simple(), Uuid::new_v4().simple());
+        let key = ApiKey {
+            id: Uuid::new_v4().to_string(),
+            name,
+            scope,
+            token_hash: hash_token(&token),
+            created: chrono::Utc::now().to_rfc2822(),
+            from_env: false,
+        };
+        let mut keys = self.keys.clone();
+        keys.push(key.clone());
+        self.save(&keys)?;
+        self.keys = keys;
+        self.enabled = true;
+        Ok((key, token))
+    }
+
+    pub fn delete(&mut self, id: &str) -> io::Result<Option<ApiKey>> {
+        let Some(index) = self
+            .keys
+            .iter()
+            .position(|key| key.id == id && !key.from_env)
+        else {
+            return Ok(None);
+        };
+        let mut keys = self.keys.clone();
+        let key = keys.remove(index);
+        self.save(&keys)?;
+        self.keys = keys;
+        Ok(Some(key))
+    }
+
+    // The keys in memory are only replaced once the file has been written
+    fn save(&self, keys: &[ApiKey]) -> io::Result<()> {
+        let managed: Vec<&ApiKey> = keys.iter().filter(|key| !key.from_env).collect();
+        let contents = serde_json::to_string_pretty(&managed)?;
+        RealFileSystem.write_atomic(&self.path, &contents)
+    }
+}
+
+// Scope needed for a request, based on its method and path
+pub fn required_scope(method: &Method, path: &str) -> Scope {
+    let is_read = method == Method::GET || method == Method::HEAD;
+    // The segment after `/api/feeds/{feed}`, matched by segment so a feed can be named like
+    // one of its routes, e.g. `backups` or `items-x`
+    let feed_route = path
+        .strip_prefix("/api/feeds/")
+        .and_then(|rest| rest.split('/').nth(1));
+    // `/api/backups` or `/api/feeds/{feed}/backups`
+    let is_backups = path.starts_with("/api/backups") || feed_route == Some("backups");
+    if path.starts_with("/api/keys")
+        || path.starts_with("/api/webhooks")
+        || path.starts_with("/api/audit")
+        || is_backups
+    {
+        // Managing keys, webhooks and backups and reading the audit log, including listing them
+        Scope::Admin
+    } else if path.starts_with("/api/feeds") && feed_route != Some("items") && !is_read {
+        // Creating, renaming and deleting whole feeds
+        Scope::Admin
+    } else if is_read {
+        Scope::Read
+    } else {
+        Scope::Write
+    }
+}
+
+fn auth_error(error: ApiError) -> Response {
+    let challenge = matches!(error, ApiError::Unauthorized(_));
+    let mut response = error.into_response();
+    if challenge {
+        response
+            .headers_mut()
+            .insert(header::WWW_AUTHENTICATE, "Bearer".parse().unwrap());
+    }
+    response
+}
+
+pub async fn require_api_key(
+    State(state): State<AppState>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let required = required_scope(request.method(), request.uri().path());
+    let authenticated = {
+        let api_keys = state.api_keys.lock().unwrap();
+        let token = request
+            .headers()
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .map(str::trim);
+        if api_keys.is_enabled() {
+            let Some(key) = token.and_then(|token| api_keys.authenticate(token)) else {
+                return auth_error(ApiError::Unauthorized(
+                    "Missing or invalid API key".to_string(),
+                ));
+            };
+            Some(AuthenticatedKey {
+                id: key.id.clone(),
+                name: key.name.clone(),
+                scope: key.scope,
+            })
+        } else if request.method() == Method::POST
+            && request.uri().path() == "/api/keys"
+            && token.is_some_and(|token| api_keys.is_bootstrap_token(token))
+        {
+            Some(AuthenticatedKey {
+                id: "bootstrap".to_string(),
+                name: "bootstrap".to_string(),
+                scope: Scope::Admin,
+            })
+        } else if required == Scope::Read {
+            None
+        } else {
+            return auth_error(ApiError::Unauthorized(
+                "No API keys are configured, set API_KEYS or create the first key with API_BOOTSTRAP_TOKEN"
+                    .to_string(),
+            ));
+        }
+    };
+
+    // Without keys only reads are let through, unauthenticated
+    let Some(authenticated) = authenticated else {
+        return next.run(request).await;
+    };
+
+    if authenticated.scope < required {
+        warn!(
+            "API key '{}' denied access to {} {}",
+            authenticated.name,
+            request.method(),
+            request.uri().path()
+        );
+        return auth_error(ApiError::Forbidden(
+            "API key does not have permission for this action".to_string(),
+        ));
+    }
+
+    debug!(
+        "API request authenticated with key '{}' ({})",
+        authenticated.name, authenticated.id
+    );
+    request.extensions_mut().insert(authenticated);
+    next.run(request).await
+}
@@ -0,0 +1,143 @@
+use crate::common::{FEED_DIRECTORY, FileSystem, RealFileSystem, is_valid_feed_id, read_channel};
+use crate::storage::StorageResult;
+use chrono::{DateTime, NaiveDateTime, Utc};
+use log::{info, warn};
+use rss::Channel;
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+const DEFAULT_BACKUP_RETENTION: usize = 10;
+// Snapshot ids are their UTC creation time, which also makes them sort oldest first
+const BACKUP_ID_FORMAT: &str = "%Y%m%dT%H%M%S%6fZ";
+
+// A stored snapshot of a feed
+pub struct Backup {
+    pub id: String,
+    pub created: DateTime<Utc>,
+    pub size: u64,
+}
+
+// Snapshots are RSS documents stored as `{directory}/{feed_id}/{id}.xml`, whatever the storage
+// backend, keeping only the newest `retention` of each feed
+pub struct BackupStore {
+    directory: PathBuf,
+    retention: usize,
+}
+
+impl BackupStore {
+    pub fn new(directory: PathBuf, retention: usize) -> Self {
+        Self {
+            directory,
+            retention,
+        }
+    }
+
+    // Reads BACKUP_DIRECTORY and BACKUP_RETENTION, a retention of 0 disables backups
+    pub fn load() -> Self {
+        let directory =
+            env::var("BACKUP_DIRECTORY").unwrap_or_else(|_| format!("{}/backups", FEED_DIRECTORY));
+        let retention = match env::var("BACKUP_RETENTION") {
+            Ok(val) => val.parse::<usize>().unwrap_or_else(|_| {
+                warn!(
+                    "Invalid BACKUP_RETENTION value: '{}', using the default",
+                    val
+                );
+                DEFAULT_BACKUP_RETENTION
+            }),
+            Err(_) => DEFAULT_BACKUP_RETENTION,
+        };
+        Self::new(PathBuf::from(directory), retention)
+    }
+
+    pub fn retention(&self) -> usize {
+        self.retention
+    }
+
+    fn feed_directory(&self, feed_id: &str) -> PathBuf {
+        self.directory.join(feed_id)
+    }
+
+    fn path(&self, feed_id: &str, id: &str) -> Option<PathBuf> {
+        // Ids come from requests, only accept the shape snapshot ids are created with
+        if !is_valid_feed_id(feed_id)
+            || id.is_empty()
+            || !id.chars().all(|c| c.is_ascii_alphanumeric())
+        {
+            return None;
+        }
+        Some(self.feed_directory(feed_id).join(format!("{}.xml", id)))
+    }
+
+    // Writes a snapshot of the feed and removes the oldest ones beyond the retention count
+    pub fn snapshot(&self, feed_id: &str, channel: &Channel) -> std::io::Result<Option<Backup>> {
+        if self.retention == 0 {
+            return Ok(None);
+        }
+
+        let created = Utc::now();
+        let id = created.format(BACKUP_ID_FORMAT).to_string();
+        let contents = channel.to_string();
+        fs::create_dir_all(self.feed_directory(feed_id))?;
+        let path = self
+            .path(feed_id, &id)
+            .expect("Generated backup ids are valid");
+        RealFileSystem.write_atomic(&path.to_string_lossy(), &contents)?;
+        info!("Backup of feed '{}' written: {}", feed_id, id);
+
+        for old in self.list(feed_id)?.iter().skip(self.retention) {
+            if let Some(path) = self.path(feed_id, &old.id) {
+                fs::remove_file(path)?;
+                info!("Backup of feed '{}' removed: {}", feed_id, old.id);
+            }
+        }
+
+        Ok(Some(Backup {
+            id,
+            created,
+            size: contents.len() as u64,
+        }))
+    }
+
+    // Snapshots of a feed, newest first
+    pub fn list(&self, feed_id: &str) -> std::io::Result<Vec<Backup>> {
+        if !is_valid_feed_id(feed_id) {
+            return Ok(Vec::new());
+        }
+        let entries = match fs::read_dir(self.feed_directory(feed_id)) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e),
+        };
+
+        let mut backups = Vec::new();
+        for entry in entries {
+            let entry = entry?;
+            let file_name = entry.file_name().to_string_lossy().to_string();
+            let Some(id) = file_name.strip_suffix(".xml") else {
+                continue;
+            };
+            let Ok(created) = NaiveDateTime::parse_from_str(id, BACKUP_ID_FORMAT) else {
+                continue;
+            };
+            backups.push(Backup {
+                id: id.to_string(),
+                created: created.and_utc(),
+                size: entry.metadata()?.len(),
+            });
+        }
+        backups.sort_by(|a, b| b.id.cmp(&a.id));
+        Ok(backups)
+    }
+
+    // The feed as it was in a snapshot, or None if there is no such snapshot
+    pub fn read(&self, feed_id: &str, id: &str) -> StorageResult<Option<Channel>> {
+        let Some(path) = self.path(feed_id, id).filter(|path| path.is_file()) else {
+            return Ok(None);
+        };
+        Ok(Some(read_channel(
+            &RealFileSystem,
+            &path.to_string_lossy(),
+        )?))
+    }
+}
@@ -0,0 +1,246 @@
+use crate::formats::FeedFormat;
+use crate::session::env_flag;
+use axum::body::Bytes;
+use axum::http::{HeaderMap, header};
+use chrono::{DateTime, Utc};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::env;
+use std::io::Write;
+use std::sync::{Arc, Mutex, OnceLock};
+
+// Renders depend on the base URL, which comes from the request's Host header unless PUBLIC_URL
+// is set, so the number kept is capped and the least recently used is dropped past it
+pub const MAX_RENDERS: usize = 64;
+
+// Encodings feeds can be compressed with, in order of preference
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ContentEncoding {
+    Brotli,
+    Gzip,
+    Identity,
+}
+
+impl ContentEncoding {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ContentEncoding::Brotli => "br",
+            ContentEncoding::Gzip => "gzip",
+            ContentEncoding::Identity => "identity",
+        }
+    }
+
+    // Picks the encoding with the highest quality value from an Accept-Encoding header,
+    // preferring brotli when both are equally acceptable
+    pub fn negotiate(accept_encoding: Option<&str>) -> Self {
+        let Some(accept_encoding) = accept_encoding else {
+            return ContentEncoding::Identity;
+        };
+
+        let mut best = (ContentEncoding::Identity, 0.0_f32);
+        for coding in accept_encoding.split(',') {
+            let mut parts = coding.split(';').map(str::trim);
+            let name = parts.next().unwrap_or_default().to_ascii_lowercase();
+            let quality = parts
+                .find_map(|param| param.strip_prefix("q="))
+                .and_then(|q| q.parse::<f32>().ok())
+                .unwrap_or(1.0);
+
+            let encoding = match name.as_str() {
+                "br" => ContentEncoding::Brotli,
+                "gzip" | "x-gzip" => ContentEncoding::Gzip,
+                _ => continue,
+            };
+            let preferred = quality > best.1
+                || (quality == best.1 && quality > 0.0 && encoding == ContentEncoding::Brotli);
+            if preferred {
+                best = (encoding, quality);
+            }
+        }
+        best.0
+    }
+}
+
+// Whether a conditional header such as If-None-Match lists the ETag, or is `*`
+pub fn etag_matches(headers: &HeaderMap, name: header::HeaderName, etag: &str) -> bool {
+    headers
+        .get_all(name)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(|tag| tag.trim())
+        .any(|tag| tag == "*" || tag.trim_start_matches("W/") == etag)
+}
+
+// Strong comparison for If-Match, which guards writes, so weak validators never match
+pub fn etag_matches_strong(headers: &HeaderMap, name: header::HeaderName, etag: &str) -> bool {
+    !etag.starts_with("W/")
+        && headers
+            .get_all(name)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .map(|tag| tag.trim())
+            .any(|tag| tag == "*" || tag == etag)
+}
+
+// Cache-Control sent with feeds, FEED_CACHE_CONTROL or a minute of caching, which shared
+// caches are only allowed when feeds are public. Set it empty to send none
+pub fn feed_cache_control() -> Option<String> {
+    match env::var("FEED_CACHE_CONTROL") {
+        Ok(value) => Some(value.trim().to_string()).filter(|value| !value.is_empty()),
+        Err(_) if env_flag("PUBLIC_FEEDS", true) => Some("public, max-age=60".to_string()),
+        Err(_) => Some("private, max-age=60".to_string()),
+    }
+}
+
+// A rendered feed with its validators, compressed bodies are made the first time they are asked
+// for and kept with it
+pub struct RenderedFeed {
+    pub body: Bytes,
+    pub etag: String,
+    pub last_modified: Option<DateTime<Utc>>,
+    // When one of the rendered items expires, the render is made again from then on
+    stale_at: Option<DateTime<Utc>>,
+    gzip: OnceLock<Bytes>,
+    brotli: OnceLock<Bytes>,
+}
+
+impl RenderedFeed {
+    pub fn new(body: String, last_modified: Option<DateTime<Utc>>) -> Self {
+        let etag = format!("\"{:x}\"", Sha256::digest(body.as_bytes()));
+        Self {
+            body: Bytes::from(body),
+            etag,
+            last_modified,
+            stale_at: None,
+            gzip: OnceLock::new(),
+            brotli: OnceLock::new(),
+        }
+    }
+
+    pub fn stale_at(mut self, stale_at: Option<DateTime<Utc>>) -> Self {
+        self.stale_at = stale_at;
+        self
+    }
+
+    fn is_stale(&self) -> bool {
+        self.stale_at.is_some_and(|stale_at| stale_at <= Utc::now())
+    }
+
+    pub fn encoded(&self, encoding: ContentEncoding) -> Bytes {
+        match encoding {
+            ContentEncoding::Identity => self.body.clone(),
+            ContentEncoding::Gzip => self.gzip.get_or_init(|| gzip(&self.body)).clone(),
+            ContentEncoding::Brotli => self.brotli.get_or_init(|| brotli(&self.body)).clone(),
+        }
+    }
+
+    // Whether the client's copy is still current. If-Modified-Since is only used without an
+    // If-None-Match, and only has a second's precision
+    pub fn is_not_modified(&self, headers: &HeaderMap) -> bool {
+        if headers.contains_key(header::IF_NONE_MATCH) {
+            return etag_matches(headers, header::IF_NONE_MATCH, &self.etag);
+        }
+        let since = headers
+            .get(header::IF_MODIFIED_SINCE)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| DateTime::parse_from_rfc2822(value).ok());
+        match (self.last_modified, since) {
+            (Some(last_modified), Some(since)) => last_modified.timestamp() <= since.timestamp(),
+            _ => false,
+        }
+    }
+}
+
+fn gzip(body: &[u8]) -> Bytes {
+    let mut encoder =
+        flate2::write::GzEncoder::new(Vec::with_capacity(body.len() / 4), Default::default());
+    encoder
+        .write_all(body)
+        .expect("Writing to memory can't fail");
+    Bytes::from(encoder.finish().expect("Writing to memory can't fail"))
+}
+
+fn brotli(body: &[u8]) -> Bytes {
+    let mut compressed = Vec::with_capacity(body.len() / 4);
+    {
+        let mut encoder = brotli::CompressorWriter::new(&mut compressed, 4096, 5, 22);
+        encoder
+            .write_all(body)
+            .expect("Writing to memory can't fail");
+    }
+    Bytes::from(compressed)
+}
+
+// Renders are kept per feed, format and the URL they advertise for themselves, until the feed
+// changes. Mutations drop a feed's renders while holding the feeds lock, so renders made under
+// the same lock are never stale
+#[derive(Default)]
+pub struct FeedCache {
+    renders: Mutex<Renders>,
+}
+
+type RenderKey = (String, FeedFormat, String);
+
+// Each render with the tick it was last used at
+#[derive(Default)]
+struct Renders {
+    entries: HashMap<RenderKey, (Arc<RenderedFeed>, u64)>,
+    tick: u64,
+}
+
+impl FeedCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // `self_url` is built from PUBLIC_URL or the normalized request host, see `public_base_url`
+    pub fn get_or_render(
+        &self,
+        feed_id: &str,
+        format: FeedFormat,
+        self_url: &str,
+        render: impl FnOnce() -> RenderedFeed,
+    ) -> Arc<RenderedFeed> {
+        let key = (feed_id.to_string(), format, self_url.to_string());
+        let mut renders = self.renders.lock().unwrap();
+        renders.tick += 1;
+        let tick = renders.tick;
+        if let Some((rendered, last_used)) = renders.entries.get_mut(&key)
+            && !rendered.is_stale()
+        {
+            *last_used = tick;
+            return Arc::clone(rendered);
+        }
+        if !renders.entries.contains_key(&key)
+            && renders.entries.len() >= MAX_RENDERS
+            && let Some(oldest) = renders
+                .entries
+                .iter()
+                .min_by_key(|(_, (_, last_used))| *last_used)
+                .map(|(key, _)| key.clone())
+        {
+            renders.entries.remove(&oldest);
+        }
+        let rendered = Arc::new(render());
+        renders.entries.insert(key, (Arc::clone(&rendered), tick));
+        rendered
+    }
+
+    pub fn invalidate(&self, feed_id: &str) {
+        self.renders
+            .lock()
+            .unwrap()
+            .entries
+            .retain(|(id, _, _), _| id != feed_id);
+    }
+
+    pub fn len(&self) -> usize {
+        self.renders.lock().unwrap().entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
@@ -1,21 +1,60 @@
+use crate::attachments::AttachmentStore;
+use crate::audit::{AuditAction, AuditActor, AuditEntry, AuditLog, AuditSource};
+use crate::auth::ApiKeyStore;
+use crate::backups::BackupStore;
+use crate::cache::FeedCache;
+use crate::session::{SessionStore, UserStore};
+use crate::storage::{SharedStorage, Storage, StorageError, StorageResult};
+use crate::stream::EventStream;
+use crate::webhooks::WebhookStore;
+use crate::websub::WebSubHub;
 use axum::extract::{Path as AxumPath, State};
-use log::{debug, info, warn};
-use rss::{Channel, ChannelBuilder, Guid, Item, ItemBuilder};
+use log::{debug, error, info, warn};
+use rss::{
+    Category, Channel, ChannelBuilder, Enclosure, Guid, Item, ItemBuilder, Source,
+    extension::{Extension, ExtensionMap},
+};
+use std::collections::BTreeMap;
 use std::sync::{Arc, Mutex};
 use std::{
     env,
-    fs::{File, write},
-    io::{BufReader, Read},
+    fs::{File, read_dir, remove_file, rename},
+    io::{BufReader, Read, Write},
     path::Path,
 };
 use uuid::Uuid;
 
+// Feed storage locations
+pub const FEED_DIRECTORY: &str = "./feed";
+pub const DEFAULT_FEED: &str = "feed";
+
+// Namespace used for Feed Crafter specific extension elements
+pub const FEED_CRAFTER_PREFIX: &str = "feedcrafter";
+pub const FEED_CRAFTER_NAMESPACE: &str = "https://github.com/dylan-park/feed-crafter";
+
 pub trait FileSystem {
     type Reader: Read;
 
-    fn exists(&self, path: &str) -> bool;
     fn open(&self, path: &str) -> Result<Self::Reader, std::io::Error>;
+    // Writes the whole file and flushes it to disk before returning
     fn write(&self, path: &str, contents: &str) -> Result<(), std::io::Error>;
+    fn remove(&self, path: &str) -> Result<(), std::io::Error>;
+    // Replaces `to` in a single step, so readers see either the old or the new file
+    fn rename(&self, from: &str, to: &str) -> Result<(), std::io::Error>;
+    fn list(&self, dir: &str) -> Result<Vec<String>, std::io::Error>;
+
+    // Writes to a temporary file next to the target and renames it into place, so a failed
+    // or interrupted write never leaves a truncated file at `path`
+    fn write_atomic(&self, path: &str, contents: &str) -> Result<(), std::io::Error> {
+        let temp_path = format!("{}.tmp", path);
+        let result = self
+            .write(&temp_path, contents)
+            .and_then(|_| self.rename(&temp_path, path));
+        if result.is_err() {
+            let _ = self.remove(&temp_path);
+        }
+        result
+    }
 }
 
 // Real filesystem implementation
@@ -24,55 +63,434 @@ pub struct RealFileSystem;
 impl FileSystem for RealFileSystem {
     type Reader = File;
 
-    fn exists(&self, path: &str) -> bool {
-        Path::new(path).exists()
-    }
-
     fn open(&self, path: &str) -> Result<Self::Reader, std::io::Error> {
         File::open(path)
     }
 
     fn write(&self, path: &str, contents: &str) -> Result<(), std::io::Error> {
-        write(path, contents)
+        let mut file = File::create(path)?;
+        file.write_all(contents.as_bytes())?;
+        file.sync_all()
+    }
+
+    fn remove(&self, path: &str) -> Result<(), std::io::Error> {
+        remove_file(path)
+    }
+
+    fn rename(&self, from: &str, to: &str) -> Result<(), std::io::Error> {
+        rename(from, to)?;
+        // Persist the directory entry too, directories can't be opened for syncing on Windows
+        if let Some(parent) = Path::new(to).parent()
+            && let Ok(directory) = File::open(parent)
+        {
+            let _ = directory.sync_all();
+        }
+        Ok(())
+    }
+
+    fn list(&self, dir: &str) -> Result<Vec<String>, std::io::Error> {
+        let mut names = Vec::new();
+        for entry in read_dir(dir)? {
+            let entry = entry?;
+            if entry.file_type()?.is_file() {
+                names.push(entry.file_name().to_string_lossy().to_string());
+            }
+        }
+        Ok(names)
     }
 }
 
-// Application state
+// Application state, feeds are keyed by their id
 #[derive(Clone)]
 pub struct AppState {
-    pub channel: Arc<Mutex<Channel>>,
+    pub feeds: Arc<Mutex<BTreeMap<String, Channel>>>,
+    pub storage: SharedStorage,
+    pub api_keys: Arc<Mutex<ApiKeyStore>>,
+    pub users: Arc<Mutex<UserStore>>,
+    pub sessions: Arc<Mutex<SessionStore>>,
+    pub attachments: Arc<AttachmentStore>,
+    pub backups: Arc<BackupStore>,
+    pub audit: Arc<AuditLog>,
+    pub webhooks: Arc<WebhookStore>,
+    pub websub: Arc<WebSubHub>,
+    pub events: Arc<EventStream>,
+    pub feed_cache: Arc<FeedCache>,
+}
+
+// Settings used to create or update a feed
+pub struct FeedSettings {
+    pub id: String,
+    pub title: String,
+    pub link: String,
+    pub description: String,
+    pub max_item_age_seconds: Option<u64>,
+    pub max_items: Option<usize>,
+    pub max_served_items: Option<usize>,
+}
+
+pub fn feed_path(feed_id: &str) -> String {
+    format!("{}/{}.xml", FEED_DIRECTORY, feed_id)
+}
+
+pub fn is_valid_feed_id(feed_id: &str) -> bool {
+    !feed_id.is_empty()
+        && feed_id.len() <= 64
+        && feed_id
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_')
+}
+
+pub fn get_extension_value<'a>(extensions: &'a ExtensionMap, name: &str) -> Option<&'a str> {
+    extensions
+        .get(FEED_CRAFTER_PREFIX)?
+        .get(name)?
+        .first()?
+        .value()
+}
+
+pub fn set_extension_value(extensions: &mut ExtensionMap, name: &str, value: Option<String>) {
+    let map = extensions
+        .entry(FEED_CRAFTER_PREFIX.to_string())
+        .or_default();
+    match value {
+        Some(value) => {
+            let mut extension = Extension::default();
+            extension.set_name(format!("{}:{}", FEED_CRAFTER_PREFIX, name));
+            extension.set_value(Some(value));
+            map.insert(name.to_string(), vec![extension]);
+        }
+        None => {
+            map.remove(name);
+        }
+    }
+    if map.is_empty() {
+        extensions.remove(FEED_CRAFTER_PREFIX);
+    }
+}
+
+pub fn feed_max_item_age(channel: &Channel) -> Option<u64> {
+    get_extension_value(channel.extensions(), "maxItemAgeSeconds").and_then(|s| s.parse().ok())
+}
+
+pub fn set_feed_max_item_age(channel: &mut Channel, max_item_age_seconds: Option<u64>) {
+    set_extension_value(
+        &mut channel.extensions,
+        "maxItemAgeSeconds",
+        max_item_age_seconds.map(|s| s.to_string()),
+    );
+    ensure_feed_crafter_namespace(channel);
+}
+
+pub fn feed_max_items(channel: &Channel) -> Option<usize> {
+    get_extension_value(channel.extensions(), "maxItems").and_then(|s| s.parse().ok())
 }
 
-pub fn initialize_feed<F: FileSystem>(fs: &F) -> Channel
+pub fn set_feed_max_items(channel: &mut Channel, max_items: Option<usize>) {
+    set_extension_value(
+        &mut channel.extensions,
+        "maxItems",
+        max_items.map(|n| n.to_string()),
+    );
+    ensure_feed_crafter_namespace(channel);
+}
+
+pub fn feed_max_served_items(channel: &Channel) -> Option<usize> {
+    get_extension_value(channel.extensions(), "maxServedItems").and_then(|s| s.parse().ok())
+}
+
+pub fn set_feed_max_served_items(channel: &mut Channel, max_served_items: Option<usize>) {
+    set_extension_value(
+        &mut channel.extensions,
+        "maxServedItems",
+        max_served_items.map(|n| n.to_string()),
+    );
+    ensure_feed_crafter_namespace(channel);
+}
+
+// Declares the Feed Crafter namespace so extension elements on the channel or its items are written
+pub fn ensure_feed_crafter_namespace(channel: &mut Channel) {
+    channel.namespaces.insert(
+        FEED_CRAFTER_PREFIX.to_string(),
+        FEED_CRAFTER_NAMESPACE.to_string(),
+    );
+}
+
+// Accepts RFC 3339, RFC 2822, a `YYYY-MM-DDTHH:MM` time in UTC as sent by forms, or a
+// `YYYY-MM-DD` date meaning midnight UTC
+pub fn parse_datetime(value: &str) -> Result<chrono::DateTime<chrono::Utc>, String> {
+    let value = value.trim();
+    chrono::DateTime::parse_from_rfc3339(value)
+        .or_else(|_| chrono::DateTime::parse_from_rfc2822(value))
+        .map(|date| date.with_timezone(&chrono::Utc))
+        .or_else(|_| {
+            chrono::NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%M")
+                .or_else(|_| chrono::NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%M:%S"))
+                .or_else(|_| {
+                    chrono::NaiveDate::parse_from_str(value, "%Y-%m-%d")
+                        .map(|date| date.and_time(chrono::NaiveTime::MIN))
+                })
+                .map(|date| date.and_utc())
+        })
+        .map_err(|_| format!("Invalid time '{}'", value))
+}
+
+// Per-item expiry, which takes precedence over the feed's age based retention
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ItemExpiry {
+    At(chrono::DateTime<chrono::Utc>),
+    Never,
+}
+
+impl ItemExpiry {
+    // Accepts "never" or any time supported by `parse_datetime`
+    pub fn parse(value: &str) -> Result<Self, String> {
+        if value.trim().eq_ignore_ascii_case("never") {
+            return Ok(ItemExpiry::Never);
+        }
+        parse_datetime(value)
+            .map(ItemExpiry::At)
+            .map_err(|_| format!("Invalid expiry time '{}'", value.trim()))
+    }
+
+    pub fn is_expired(&self, now: chrono::DateTime<chrono::Utc>) -> bool {
+        match self {
+            ItemExpiry::At(expires_at) => *expires_at <= now,
+            ItemExpiry::Never => false,
+        }
+    }
+}
+
+impl std::fmt::Display for ItemExpiry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ItemExpiry::At(expires_at) => write!(f, "{}", expires_at.to_rfc2822()),
+            ItemExpiry::Never => write!(f, "never"),
+        }
+    }
+}
+
+pub fn item_expiry(item: &Item) -> Option<ItemExpiry> {
+    get_extension_value(item.extensions(), "expiresAt").and_then(|s| ItemExpiry::parse(s).ok())
+}
+
+pub fn set_item_expiry(item: &mut Item, expiry: Option<ItemExpiry>) {
+    set_extension_value(
+        &mut item.extensions,
+        "expiresAt",
+        expiry.map(|e| e.to_string()),
+    );
+}
+
+// Time the item was last edited, its pub_date is kept as the original publish time
+pub fn item_updated(item: &Item) -> Option<chrono::DateTime<chrono::Utc>> {
+    get_extension_value(item.extensions(), "updated").and_then(|s| parse_datetime(s).ok())
+}
+
+pub fn set_item_updated(item: &mut Item, updated: Option<chrono::DateTime<chrono::Utc>>) {
+    set_extension_value(
+        &mut item.extensions,
+        "updated",
+        updated.map(|d| d.to_rfc2822()),
+    );
+}
+
+// Scheduled items are stored with their future pub_date, but left out of the served feed
+pub fn is_item_scheduled(item: &Item) -> bool {
+    get_extension_value(item.extensions(), "scheduled") == Some("true")
+}
+
+pub fn schedule_item(item: &mut Item, publish_at: chrono::DateTime<chrono::Utc>) {
+    item.set_pub_date(publish_at.to_rfc2822());
+    set_extension_value(&mut item.extensions, "scheduled", Some("true".to_string()));
+}
+
+// Deleted items are kept in the feed's trash until they are restored or purged
+pub fn item_deleted_at(item: &Item) -> Option<chrono::DateTime<chrono::Utc>> {
+    get_extension_value(item.extensions(), "deleted").and_then(|s| parse_datetime(s).ok())
+}
+
+pub fn is_item_deleted(item: &Item) -> bool {
+    get_extension_value(item.extensions(), "deleted").is_some()
+}
+
+pub fn set_item_deleted(item: &mut Item, deleted: Option<chrono::DateTime<chrono::Utc>>) {
+    set_extension_value(
+        &mut item.extensions,
+        "deleted",
+        deleted.map(|d| d.to_rfc2822()),
+    );
+}
+
+// Whether this is the item with the given GUID, and it isn't in the trash
+pub fn is_live_item(item: &Item, item_id: &str) -> bool {
+    !is_item_deleted(item) && item.guid().is_some_and(|g| g.value() == item_id)
+}
+
+// Copy of the channel with only the items that have been published and aren't in the trash.
+// Items past their own expiry time are left out too, before the cleanup task removes them
+pub fn published_channel(channel: &Channel) -> Channel {
+    let now = chrono::Utc::now();
+    let mut published = channel.clone();
+    published.set_items(
+        channel
+            .items()
+            .iter()
+            .filter(|item| {
+                !is_item_scheduled(item)
+                    && !is_item_deleted(item)
+                    && !item_expiry(item).is_some_and(|expiry| expiry.is_expired(now))
+            })
+            .cloned()
+            .collect::<Vec<Item>>(),
+    );
+    published
+}
+
+// The earliest time one of the channel's items expires, when a render of it goes stale
+pub fn next_item_expiry(channel: &Channel) -> Option<chrono::DateTime<chrono::Utc>> {
+    channel
+        .items()
+        .iter()
+        .filter_map(|item| match item_expiry(item) {
+            Some(ItemExpiry::At(expires_at)) => Some(expires_at),
+            _ => None,
+        })
+        .min()
+}
+
+// The published channel as it is served, cut down to the newest items when the feed limits how
+// many are served. Storage and the API keep every item
+pub fn served_channel(channel: &Channel) -> Channel {
+    let mut served = published_channel(channel);
+    let limit = feed_max_served_items(channel)
+        .or_else(default_max_served_items)
+        .filter(|limit| *limit > 0);
+    if let Some(limit) = limit
+        && served.items().len() > limit
+    {
+        let items = served.items()[..limit].to_vec();
+        served.set_items(items);
+    }
+    served
+}
+
+// Takes the oldest items past the feed's item cap out of the channel and returns them. Only
+// published items count towards the cap, scheduled items and the trash are never evicted
+pub fn evict_items(channel: &mut Channel) -> Vec<Item> {
+    let Some(max_items) = feed_max_items(channel)
+        .or_else(default_max_items)
+        .filter(|max_items| *max_items > 0)
+    else {
+        return Vec::new();
+    };
+    let mut counted = 0;
+    // Items are kept newest first, so the ones past the cap are the oldest
+    let (items, evicted): (Vec<Item>, Vec<Item>) =
+        channel.items().iter().cloned().partition(|item| {
+            if is_item_deleted(item) || is_item_scheduled(item) {
+                return true;
+            }
+            counted += 1;
+            counted <= max_items
+        });
+    if !evicted.is_empty() {
+        channel.set_items(items);
+    }
+    evicted
+}
+
+// Audit entries for items evicted by a feed's item cap
+fn eviction_entries(feed_id: &str, evicted: &[Item]) -> Vec<AuditEntry> {
+    let actor = AuditActor::system(AuditSource::Cleanup);
+    evicted
+        .iter()
+        .map(|item| {
+            info!(
+                "Evicting item from '{}' past its item cap: '{}'",
+                feed_id,
+                item.title().unwrap_or("Untitled")
+            );
+            AuditEntry::item(AuditAction::Expire, feed_id, &actor, Some(item), None)
+        })
+        .collect()
+}
+
+// Loads every feed, or creates the default feed when there are none. A feed that can't be
+// read is quarantined rather than stopping startup, and is left out of the loaded feeds
+pub fn load_feeds(storage: &dyn Storage) -> StorageResult<BTreeMap<String, Channel>> {
+    let mut feeds = BTreeMap::new();
+    let feed_ids = storage.list_feeds()?;
+
+    for feed_id in feed_ids {
+        match storage.load_feed(&feed_id) {
+            Ok(channel) => {
+                info!("Feed '{}' successfully loaded", feed_id);
+                feeds.insert(feed_id, channel);
+            }
+            Err(e) => {
+                let name = quarantine_name(&feed_id, chrono::Utc::now());
+                error!("Feed '{}' could not be loaded: {}", feed_id, e);
+                // Never start without moving it aside, or saving the feed again would overwrite it
+                storage.quarantine_feed(&feed_id, &name)?;
+                error!(
+                    "Feed '{}' was moved to '{}' and must be restored by hand, its items are not being served",
+                    feed_id, name
+                );
+            }
+        }
+    }
+
+    if feeds.is_empty() {
+        info!("No feeds found, creating based on environment variables");
+        let channel = create_feed()?;
+        storage.save_feed(DEFAULT_FEED, &channel)?;
+        info!("Feed successfully created");
+        feeds.insert(DEFAULT_FEED.to_string(), channel);
+    }
+    Ok(feeds)
+}
+
+// Name a broken feed is kept under, the dot makes it an invalid feed id
+pub fn quarantine_name(feed_id: &str, now: chrono::DateTime<chrono::Utc>) -> String {
+    format!("{}.corrupt-{}", feed_id, now.format("%Y%m%d%H%M%S"))
+}
+
+pub fn read_channel<F: FileSystem>(fs: &F, path: &str) -> StorageResult<Channel>
 where
     F::Reader: Read,
 {
-    if fs.exists("./feed/feed.xml") {
-        info!("Feed found on disk, reading...");
-        let file = fs.open("./feed/feed.xml").expect("Error opening feed.xml");
-        let reader = BufReader::new(file);
-        let channel = Channel::read_from(reader).expect("Error reading feed into Channel");
-        info!("Feed successfully read from disk");
-        channel
-    } else {
-        info!("No feed found on disk, creating based on environment variables");
-        let channel = create_feed();
-        write_channel(&channel, None, fs);
-        info!("Feed successfully created and written to disk");
-        channel
-    }
+    let file = fs.open(path)?;
+    Ok(Channel::read_from(BufReader::new(file))?)
+}
+
+pub fn create_feed() -> StorageResult<Channel> {
+    let setting = |name: &str, description: &str| {
+        env::var(name).map_err(|_| {
+            StorageError::Config(format!(
+                "Expected a channel {} in the environment",
+                description
+            ))
+        })
+    };
+    Ok(ChannelBuilder::default()
+        .title(setting("CHANNEL_TITLE", "title")?)
+        .link(setting("CHANNEL_LINK", "link")?)
+        .description(setting("CHANNEL_DESCRIPTION", "description")?)
+        .last_build_date(chrono::Utc::now().to_rfc2822())
+        .build())
 }
 
-pub fn create_feed() -> Channel {
-    ChannelBuilder::default()
-        .title(env::var("CHANNEL_TITLE").expect("Expected a channel title in the environment"))
-        .link(env::var("CHANNEL_LINK").expect("Expected a channel link in the environment"))
-        .description(
-            env::var("CHANNEL_DESCRIPTION")
-                .expect("Expected a channel description in the environment"),
-        )
+pub fn create_feed_from_settings(settings: &FeedSettings) -> Channel {
+    let mut channel = ChannelBuilder::default()
+        .title(settings.title.clone())
+        .link(settings.link.clone())
+        .description(settings.description.clone())
         .last_build_date(chrono::Utc::now().to_rfc2822())
-        .build()
+        .build();
+    set_feed_max_item_age(&mut channel, settings.max_item_age_seconds);
+    set_feed_max_items(&mut channel, settings.max_items);
+    set_feed_max_served_items(&mut channel, settings.max_served_items);
+    channel
 }
 
 pub fn create_item(title: String, description: Option<String>, link: Option<String>) -> Item {
@@ -102,172 +520,873 @@ pub fn create_item(title: String, description: Option<String>, link: Option<Stri
     item
 }
 
-pub fn write_channel<F: FileSystem>(channel: &Channel, path: Option<&str>, fs: &F) {
+// Criteria for narrowing down a feed's items, unset criteria match everything
+#[derive(Default)]
+pub struct ItemFilter {
+    // Case insensitive text searched for in the title and description
+    pub query: Option<String>,
+    pub category: Option<String>,
+    pub since: Option<chrono::DateTime<chrono::Utc>>,
+    pub until: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+impl ItemFilter {
+    pub fn matches(&self, item: &Item) -> bool {
+        if let Some(query) = &self.query {
+            let query = query.to_lowercase();
+            let contains =
+                |text: Option<&str>| text.is_some_and(|text| text.to_lowercase().contains(&query));
+            if !contains(item.title()) && !contains(item.description()) {
+                return false;
+            }
+        }
+
+        if let Some(category) = &self.category
+            && !item
+                .categories()
+                .iter()
+                .any(|c| c.name().eq_ignore_ascii_case(category))
+        {
+            return false;
+        }
+
+        // Date ranges are inclusive, items without a publish date never match one
+        if self.since.is_some() || self.until.is_some() {
+            let Some(pub_date) = item.pub_date().and_then(|d| parse_datetime(d).ok()) else {
+                return false;
+            };
+            if self.since.is_some_and(|since| pub_date < since)
+                || self.until.is_some_and(|until| pub_date > until)
+            {
+                return false;
+            }
+        }
+
+        true
+    }
+}
+
+// Editable content of an item, shared by the API and the web forms
+#[derive(Clone, Default)]
+pub struct ItemFields {
+    pub title: String,
+    pub description: Option<String>,
+    pub link: Option<String>,
+    pub author: Option<String>,
+    pub comments: Option<String>,
+    pub categories: Vec<Category>,
+    pub enclosure: Option<Enclosure>,
+    pub source: Option<Source>,
+}
+
+// Replaces the item's content, empty values clear the field
+pub fn set_item_fields(item: &mut Item, fields: ItemFields) {
+    let non_empty = |value: Option<String>| value.filter(|s| !s.trim().is_empty());
+    item.set_title(fields.title);
+    item.set_description(non_empty(fields.description));
+    item.set_link(non_empty(fields.link));
+    item.set_author(non_empty(fields.author));
+    item.set_comments(non_empty(fields.comments));
+    item.set_categories(
+        fields
+            .categories
+            .into_iter()
+            .filter(|category| !category.name().trim().is_empty())
+            .collect::<Vec<Category>>(),
+    );
+    item.set_enclosure(
+        fields
+            .enclosure
+            .filter(|enclosure| !enclosure.url().trim().is_empty()),
+    );
+    item.set_source(
+        fields
+            .source
+            .filter(|source| !source.url().trim().is_empty()),
+    );
+}
+
+pub fn create_item_from_fields(fields: ItemFields) -> Item {
+    let mut item = create_item(
+        fields.title.clone(),
+        fields.description.clone().filter(|s| !s.trim().is_empty()),
+        fields.link.clone().filter(|s| !s.trim().is_empty()),
+    );
+    set_item_fields(&mut item, fields);
+    item
+}
+
+pub fn write_channel<F: FileSystem>(
+    channel: &Channel,
+    path: Option<&str>,
+    fs: &F,
+) -> StorageResult<()> {
     let rss_content = channel.to_string();
     let file_path = path.unwrap_or("./feed/feed.xml");
-    fs.write(file_path, &rss_content)
-        .expect("Failed to write RSS feed to file");
+    fs.write_atomic(file_path, &rss_content)?;
     info!("Feed written successfully");
+    Ok(())
 }
 
-pub fn add_item(State(state): State<AppState>, item: Item) {
-    let mut channel = state.channel.lock().unwrap();
+// Snapshots a feed after a change has been saved, drops its cached renders and pushes it to
+// its WebSub subscribers, a failed backup doesn't undo the change
+fn feed_saved(state: &AppState, feed_id: &str, channel: &Channel) {
+    if let Err(e) = state.backups.snapshot(feed_id, channel) {
+        warn!("Failed to back up feed '{}': {}", feed_id, e);
+    }
+    state.feed_cache.invalidate(feed_id);
+    state.websub.publish(feed_id, channel);
+}
+
+// Writes saved changes to the audit log, queues their webhook notifications and sends them
+// to stream clients
+fn record_changes(state: &AppState, entries: &[AuditEntry]) {
+    state.audit.record(entries);
+    state.webhooks.notify(entries);
+    state.events.publish(entries);
+}
+
+// The mutations below return Ok(None) when the feed or item doesn't exist, and only change
+// the in-memory feeds once storage has accepted the change
+pub fn add_feed(
+    State(state): State<AppState>,
+    settings: FeedSettings,
+) -> StorageResult<Option<Channel>> {
+    let mut feeds = state.feeds.lock().unwrap();
+    if !is_valid_feed_id(&settings.id) || feeds.contains_key(&settings.id) {
+        return Ok(None);
+    }
+
+    let channel = create_feed_from_settings(&settings);
+    state.storage.save_feed(&settings.id, &channel)?;
+    feed_saved(&state, &settings.id, &channel);
+    feeds.insert(settings.id.clone(), channel.clone());
+    info!("Feed created: {}", settings.id);
+    Ok(Some(channel))
+}
+
+// Returns Ok(None) when the new id is invalid or taken. The default feed keeps its id,
+// since /feed.xml and the /api/items aliases always serve it
+pub fn edit_feed(
+    State(state): State<AppState>,
+    AxumPath(feed_id): AxumPath<String>,
+    settings: FeedSettings,
+) -> StorageResult<Option<Channel>> {
+    let mut feeds = state.feeds.lock().unwrap();
+    if !is_valid_feed_id(&settings.id)
+        || (settings.id != feed_id && feeds.contains_key(&settings.id))
+        || (settings.id != feed_id && feed_id == DEFAULT_FEED)
+    {
+        return Ok(None);
+    }
+
+    let Some(mut channel) = feeds.get(&feed_id).cloned() else {
+        return Ok(None);
+    };
+    channel.set_title(settings.title);
+    channel.set_link(settings.link);
+    channel.set_description(settings.description);
+    set_feed_max_item_age(&mut channel, settings.max_item_age_seconds);
+    set_feed_max_items(&mut channel, settings.max_items);
+    set_feed_max_served_items(&mut channel, settings.max_served_items);
+    channel.set_last_build_date(chrono::Utc::now().to_rfc2822());
+
+    if settings.id != feed_id {
+        state.storage.rename_feed(&feed_id, &settings.id)?;
+        info!("Feed renamed: {} -> {}", feed_id, settings.id);
+    }
+    if let Err(e) = state.storage.update_feed(&settings.id, &channel) {
+        // Undo the rename so storage keeps matching the feeds in memory
+        if settings.id != feed_id
+            && let Err(rename_error) = state.storage.rename_feed(&settings.id, &feed_id)
+        {
+            error!(
+                "Failed to rename feed '{}' back to '{}': {}",
+                settings.id, feed_id, rename_error
+            );
+        }
+        return Err(e);
+    }
+    feed_saved(&state, &settings.id, &channel);
+    if settings.id != feed_id
+        && let Err(e) = state.websub.rename_feed(&feed_id, &settings.id)
+    {
+        error!(
+            "Failed to move WebSub subscriptions of '{}' to '{}': {}",
+            feed_id, settings.id, e
+        );
+    }
+    feeds.remove(&feed_id);
+    feeds.insert(settings.id, channel.clone());
+    Ok(Some(channel))
+}
+
+// Returns Ok(None) for unknown feeds and for the default feed, which can't be deleted
+pub fn delete_feed(
+    State(state): State<AppState>,
+    AxumPath(feed_id): AxumPath<String>,
+) -> StorageResult<Option<Channel>> {
+    let mut feeds = state.feeds.lock().unwrap();
+    if !feeds.contains_key(&feed_id) || feed_id == DEFAULT_FEED {
+        return Ok(None);
+    }
+    state.storage.delete_feed(&feed_id)?;
+    let Some(channel) = feeds.remove(&feed_id) else {
+        return Ok(None);
+    };
+    state.feed_cache.invalidate(&feed_id);
+    if let Err(e) = state.websub.remove_feed(&feed_id) {
+        error!(
+            "Failed to drop WebSub subscriptions of '{}': {}",
+            feed_id, e
+        );
+    }
+    state.attachments.remove_orphaned(&feeds, channel.items());
+    info!("Feed deleted: {}", feed_id);
+    Ok(Some(channel))
+}
+
+// Replaces a feed with one of its snapshots, recreating it if it has been deleted since.
+// Returns Ok(None) when there is no such snapshot
+pub fn restore_feed(
+    state: &AppState,
+    feed_id: &str,
+    backup_id: &str,
+    actor: &AuditActor,
+) -> StorageResult<Option<Channel>> {
+    let Some(mut channel) = state.backups.read(feed_id, backup_id)? else {
+        return Ok(None);
+    };
+    // The restore is a change to the feed, so readers caching it by date see it too
+    channel.set_last_build_date(chrono::Utc::now().to_rfc2822());
+
+    let mut feeds = state.feeds.lock().unwrap();
+    state.storage.save_feed(feed_id, &channel)?;
+    feeds.insert(feed_id.to_string(), channel.clone());
+    state.feed_cache.invalidate(feed_id);
+    state.websub.publish(feed_id, &channel);
+    record_changes(state, &[AuditEntry::restore(feed_id, actor, backup_id)]);
+    info!("Feed '{}' restored from backup {}", feed_id, backup_id);
+    // Attachment files are not part of backups, they may have been removed since
+    for url in state.attachments.missing(channel.items()) {
+        warn!(
+            "Restored feed '{}' refers to a missing attachment: {}",
+            feed_id, url
+        );
+    }
+    Ok(Some(channel))
+}
+
+pub fn add_item(
+    State(state): State<AppState>,
+    AxumPath(feed_id): AxumPath<String>,
+    item: Item,
+    actor: &AuditActor,
+) -> StorageResult<Option<Guid>> {
+    let mut feeds = state.feeds.lock().unwrap();
+    let Some(mut channel) = feeds.get(&feed_id).cloned() else {
+        return Ok(None);
+    };
+    if item.extensions().contains_key(FEED_CRAFTER_PREFIX) {
+        ensure_feed_crafter_namespace(&mut channel);
+    }
     let mut items = channel.items().to_vec();
-    items.insert(0, item);
+    items.insert(0, item.clone());
     channel.set_items(items);
+    let evicted = evict_items(&mut channel);
     channel.set_last_build_date(chrono::Utc::now().to_rfc2822());
 
-    // Save to file
-    write_channel(&channel, None, &RealFileSystem);
+    // Save to storage, the whole feed when items were evicted to make room
+    if evicted.is_empty() {
+        state.storage.insert_item(&feed_id, &channel, &item)?;
+    } else {
+        state.storage.save_feed(&feed_id, &channel)?;
+    }
+    feed_saved(&state, &feed_id, &channel);
+    let mut entries = vec![AuditEntry::item(
+        AuditAction::Create,
+        &feed_id,
+        actor,
+        None,
+        Some(&item),
+    )];
+    entries.extend(eviction_entries(&feed_id, &evicted));
+    record_changes(&state, &entries);
+    feeds.insert(feed_id, channel);
+    state.attachments.remove_orphaned(&feeds, &evicted);
+    Ok(item.guid().cloned())
 }
 
+// Moves an item to the trash, it stays stored but is left out of the feed
 pub fn delete_item(
     State(state): State<AppState>,
-    AxumPath(item_id): AxumPath<String>,
-) -> Option<Guid> {
-    let mut return_item_id: Option<Guid> = None;
+    AxumPath((feed_id, item_id)): AxumPath<(String, String)>,
+    actor: &AuditActor,
+) -> StorageResult<Option<Guid>> {
+    let mut feeds = state.feeds.lock().unwrap();
+    let Some(mut channel) = feeds.get(&feed_id).cloned() else {
+        return Ok(None);
+    };
+    let now = chrono::Utc::now();
+    let Some((previous_item, deleted_item)) = trash_channel_item(&mut channel, &item_id, now)
+    else {
+        return Ok(None);
+    };
 
-    {
-        let mut channel = state.channel.lock().unwrap();
+    channel.set_last_build_date(now.to_rfc2822());
+    state
+        .storage
+        .update_item(&feed_id, &channel, &deleted_item)?;
+    feed_saved(&state, &feed_id, &channel);
+    record_changes(
+        &state,
+        &[AuditEntry::item(
+            AuditAction::Delete,
+            &feed_id,
+            actor,
+            Some(&previous_item),
+            None,
+        )],
+    );
+    feeds.insert(feed_id, channel);
+    Ok(deleted_item.guid().cloned())
+}
 
-        let items: Vec<Item> = channel
-            .items()
-            .iter()
-            .filter(|item| {
-                let matches = item.guid().map(|g| g.value() == item_id).unwrap_or(false);
-                if matches {
-                    return_item_id = item.guid().cloned();
-                }
-                !matches
-            })
-            .cloned()
-            .collect();
+// Takes an item back out of the trash
+pub fn restore_item(
+    state: &AppState,
+    feed_id: &str,
+    item_id: &str,
+    actor: &AuditActor,
+) -> StorageResult<Option<Item>> {
+    let mut feeds = state.feeds.lock().unwrap();
+    let Some(mut channel) = feeds.get(feed_id).cloned() else {
+        return Ok(None);
+    };
+    let mut items = channel.items().to_vec();
+    let Some(item) = items
+        .iter_mut()
+        .find(|item| is_item_deleted(item) && item.guid().is_some_and(|g| g.value() == item_id))
+    else {
+        return Ok(None);
+    };
+    set_item_deleted(item, None);
+    let restored_item = item.clone();
 
-        if return_item_id.is_some() {
-            channel.set_items(items);
-            channel.set_last_build_date(chrono::Utc::now().to_rfc2822());
-            write_channel(&channel, None, &RealFileSystem);
-        }
-    }
-    return_item_id
+    channel.set_items(items);
+    channel.set_last_build_date(chrono::Utc::now().to_rfc2822());
+    state
+        .storage
+        .update_item(feed_id, &channel, &restored_item)?;
+    feed_saved(state, feed_id, &channel);
+    record_changes(
+        state,
+        &[AuditEntry::item(
+            AuditAction::Restore,
+            feed_id,
+            actor,
+            None,
+            Some(&restored_item),
+        )],
+    );
+    feeds.insert(feed_id.to_string(), channel);
+    info!("Item restored from the trash: {}", item_id);
+    Ok(Some(restored_item))
+}
+
+// Marks an item as deleted in place, returning the item before and after
+fn trash_channel_item(
+    channel: &mut Channel,
+    item_id: &str,
+    now: chrono::DateTime<chrono::Utc>,
+) -> Option<(Item, Item)> {
+    let mut items = channel.items().to_vec();
+    let item = items.iter_mut().find(|item| is_live_item(item, item_id))?;
+    let previous_item = item.clone();
+    set_item_deleted(item, Some(now));
+    let deleted_item = item.clone();
+
+    ensure_feed_crafter_namespace(channel);
+    channel.set_items(items);
+    Some((previous_item, deleted_item))
 }
 
 pub fn edit_item(
     State(state): State<AppState>,
-    AxumPath(item_id): AxumPath<String>,
-    title: String,
-    description: Option<String>,
-    link: Option<String>,
-) -> Option<Item> {
-    let mut return_item: Option<Item> = None;
+    AxumPath((feed_id, item_id)): AxumPath<(String, String)>,
+    fields: ItemFields,
+    expiry: Option<ItemExpiry>,
+    publish_at: Option<chrono::DateTime<chrono::Utc>>,
+    actor: &AuditActor,
+) -> StorageResult<Option<Item>> {
+    let mut feeds = state.feeds.lock().unwrap();
+    edit_locked_item(
+        &state, &mut feeds, &feed_id, &item_id, fields, expiry, publish_at, actor,
+    )
+}
 
-    {
-        let mut channel = state.channel.lock().unwrap();
+// Edits an item while the caller holds the feeds lock, so it can check the item first
+#[allow(clippy::too_many_arguments)]
+pub fn edit_locked_item(
+    state: &AppState,
+    feeds: &mut BTreeMap<String, Channel>,
+    feed_id: &str,
+    item_id: &str,
+    fields: ItemFields,
+    expiry: Option<ItemExpiry>,
+    publish_at: Option<chrono::DateTime<chrono::Utc>>,
+    actor: &AuditActor,
+) -> StorageResult<Option<Item>> {
+    let Some(mut channel) = feeds.get(feed_id).cloned() else {
+        return Ok(None);
+    };
+    let now = chrono::Utc::now();
+    let Some((previous_item, updated_item)) =
+        edit_channel_item(&mut channel, item_id, fields, expiry, publish_at, now)
+    else {
+        return Ok(None);
+    };
+    channel.set_last_build_date(now.to_rfc2822());
+    if is_item_scheduled(&previous_item) && !is_item_scheduled(&updated_item) {
+        // Re-insert a published item so storage orders it as the newest
+        let ids = [item_id.to_string()];
+        state
+            .storage
+            .delete_items(feed_id, &channel, &ids)
+            .and_then(|_| state.storage.insert_item(feed_id, &channel, &updated_item))?;
+    } else {
+        state
+            .storage
+            .update_item(feed_id, &channel, &updated_item)?;
+    }
+    feed_saved(state, feed_id, &channel);
+    record_changes(
+        state,
+        &[AuditEntry::item(
+            AuditAction::Update,
+            feed_id,
+            actor,
+            Some(&previous_item),
+            Some(&updated_item),
+        )],
+    );
+    feeds.insert(feed_id.to_string(), channel);
+    // A replaced enclosure leaves its uploaded file behind
+    state.attachments.remove_orphaned(feeds, &[previous_item]);
+    Ok(Some(updated_item))
+}
 
-        let items: Vec<Item> = channel
-            .items()
-            .iter()
-            .map(|item| {
-                let matches = item.guid().map(|g| g.value() == item_id).unwrap_or(false);
-                if matches {
-                    // Create updated item
-                    let updated_item = create_item(
-                        title.clone(),
-                        description.clone().filter(|s| !s.trim().is_empty()),
-                        link.clone().filter(|s| !s.trim().is_empty()),
-                    );
-                    return_item = Some(updated_item.clone());
-                    updated_item
-                } else {
-                    item.clone()
+// Updates an item in place so readers keep seeing the same GUID and publish date,
+// returning the item before and after the edit
+fn edit_channel_item(
+    channel: &mut Channel,
+    item_id: &str,
+    fields: ItemFields,
+    expiry: Option<ItemExpiry>,
+    publish_at: Option<chrono::DateTime<chrono::Utc>>,
+    now: chrono::DateTime<chrono::Utc>,
+) -> Option<(Item, Item)> {
+    let mut items = channel.items().to_vec();
+    let index = items.iter().position(|item| is_live_item(item, item_id))?;
+    let item = &mut items[index];
+    let previous_item = item.clone();
+    set_item_fields(item, fields);
+    set_item_expiry(item, expiry);
+    // A new publish time reschedules the item, already published items can only move ahead.
+    // A scheduled item given a time that has passed is published now, at the top of the feed
+    let mut published = false;
+    if let Some(publish_at) = publish_at.filter(|p| *p > now || is_item_scheduled(item)) {
+        if publish_at > now {
+            schedule_item(item, publish_at);
+        } else {
+            item.set_pub_date(now.to_rfc2822());
+            set_extension_value(&mut item.extensions, "scheduled", None);
+            published = true;
+        }
+    }
+    set_item_updated(item, Some(now));
+    let updated_item = item.clone();
+    if published {
+        let item = items.remove(index);
+        items.insert(0, item);
+    }
+
+    ensure_feed_crafter_namespace(channel);
+    channel.set_items(items);
+    Some((previous_item, updated_item))
+}
+
+// A single change within a bulk request
+pub enum ItemOperation {
+    Create(Box<Item>),
+    Update {
+        id: String,
+        fields: Box<ItemFields>,
+        expiry: Option<ItemExpiry>,
+        publish_at: Option<chrono::DateTime<chrono::Utc>>,
+    },
+    Delete {
+        id: String,
+    },
+}
+
+// Outcome of one operation, with the created or updated item where there is one
+pub type OperationResult = Result<Option<Item>, String>;
+
+// Applies a batch of operations to a feed under one lock and saves it once. Either every
+// operation is applied or none are, the result holds the outcome of each in order
+pub fn apply_item_operations(
+    state: &AppState,
+    feed_id: &str,
+    operations: Vec<ItemOperation>,
+    actor: &AuditActor,
+) -> StorageResult<Option<Vec<OperationResult>>> {
+    let mut feeds = state.feeds.lock().unwrap();
+    let Some(original) = feeds.get(feed_id) else {
+        return Ok(None);
+    };
+    let mut channel = original.clone();
+    let original_items = original.items().to_vec();
+    let now = chrono::Utc::now();
+    // Only written once the whole batch has been saved
+    let mut entries = Vec::new();
+
+    let results: Vec<OperationResult> = operations
+        .into_iter()
+        .map(|operation| match operation {
+            ItemOperation::Create(item) => {
+                if item.extensions().contains_key(FEED_CRAFTER_PREFIX) {
+                    ensure_feed_crafter_namespace(&mut channel);
                 }
-            })
-            .collect();
+                let mut items = channel.items().to_vec();
+                items.insert(0, (*item).clone());
+                channel.set_items(items);
+                entries.push(AuditEntry::item(
+                    AuditAction::Create,
+                    feed_id,
+                    actor,
+                    None,
+                    Some(&item),
+                ));
+                Ok(Some(*item))
+            }
+            ItemOperation::Update {
+                id,
+                fields,
+                expiry,
+                publish_at,
+            } => edit_channel_item(&mut channel, &id, *fields, expiry, publish_at, now)
+                .map(|(previous_item, updated_item)| {
+                    entries.push(AuditEntry::item(
+                        AuditAction::Update,
+                        feed_id,
+                        actor,
+                        Some(&previous_item),
+                        Some(&updated_item),
+                    ));
+                    Some(updated_item)
+                })
+                .ok_or_else(|| format!("Item not found: {}", id)),
+            ItemOperation::Delete { id } => {
+                let (previous_item, _) = trash_channel_item(&mut channel, &id, now)
+                    .ok_or_else(|| format!("Item not found: {}", id))?;
+                entries.push(AuditEntry::item(
+                    AuditAction::Delete,
+                    feed_id,
+                    actor,
+                    Some(&previous_item),
+                    None,
+                ));
+                Ok(None)
+            }
+        })
+        .collect();
+
+    if results.iter().any(Result::is_err) {
+        return Ok(Some(results));
+    }
 
-        if return_item.is_some() {
-            channel.set_items(items);
-            channel.set_last_build_date(chrono::Utc::now().to_rfc2822());
-            write_channel(&channel, None, &RealFileSystem);
+    let evicted = evict_items(&mut channel);
+    entries.extend(eviction_entries(feed_id, &evicted));
+    channel.set_last_build_date(now.to_rfc2822());
+    state.storage.save_feed(feed_id, &channel)?;
+    feed_saved(state, feed_id, &channel);
+    record_changes(state, &entries);
+    feeds.insert(feed_id.to_string(), channel);
+    // Replaced enclosures and evicted items leave their uploaded files behind
+    let removed: Vec<Item> = original_items.into_iter().chain(evicted).collect();
+    state.attachments.remove_orphaned(&feeds, &removed);
+    Ok(Some(results))
+}
+
+// Default item cap and served item limit of feeds without their own, 0 or unset for none
+fn default_max_items() -> Option<usize> {
+    item_count_setting("MAX_ITEMS")
+}
+
+fn default_max_served_items() -> Option<usize> {
+    item_count_setting("MAX_SERVED_ITEMS")
+}
+
+fn item_count_setting(name: &str) -> Option<usize> {
+    let val = env::var(name).ok()?;
+    match val.parse::<usize>() {
+        Ok(count) => Some(count),
+        Err(_) => {
+            warn!("Invalid {} value: '{}', ignoring", name, val);
+            None
         }
     }
-    return_item
 }
 
-pub fn cleanup_old_items<F: FileSystem>(state: &AppState, fs: &F) -> usize {
-    let max_age_seconds = match env::var("MAX_ITEM_AGE_SECONDS") {
+fn default_max_item_age() -> Option<u64> {
+    match env::var("MAX_ITEM_AGE_SECONDS") {
         Ok(val) => match val.parse::<u64>() {
-            Ok(seconds) => seconds,
+            Ok(seconds) => Some(seconds),
             Err(_) => {
-                warn!(
-                    "Invalid MAX_ITEM_AGE_SECONDS value: '{}', skipping cleanup",
-                    val
-                );
-                return 0;
+                warn!("Invalid MAX_ITEM_AGE_SECONDS value: '{}', ignoring", val);
+                None
             }
         },
-        Err(_) => {
-            debug!("MAX_ITEM_AGE_SECONDS not set, skipping cleanup");
-            return 0;
-        }
-    };
+        Err(_) => None,
+    }
+}
+
+const DEFAULT_TRASH_RETENTION_SECONDS: u64 = 30 * 24 * 60 * 60;
 
-    if max_age_seconds == 0 {
-        debug!("MAX_ITEM_AGE_SECONDS is 0, items will be kept indefinitely");
-        return 0;
+// How long deleted items stay in the trash, 0 purges them on the next cleanup
+fn trash_retention_seconds() -> u64 {
+    match env::var("TRASH_RETENTION_SECONDS") {
+        Ok(val) => val.parse::<u64>().unwrap_or_else(|_| {
+            warn!(
+                "Invalid TRASH_RETENTION_SECONDS value: '{}', using the default",
+                val
+            );
+            DEFAULT_TRASH_RETENTION_SECONDS
+        }),
+        Err(_) => DEFAULT_TRASH_RETENTION_SECONDS,
     }
+}
 
-    let cutoff_date = chrono::Utc::now() - chrono::Duration::seconds(max_age_seconds as i64);
+// Whether an item has been in the trash for longer than the retention
+fn is_item_purged(feed_id: &str, item: &Item, trash_cutoff: chrono::DateTime<chrono::Utc>) -> bool {
+    // Items without a readable deletion time can't be aged, so they are purged
+    let is_purged = item_deleted_at(item).is_none_or(|deleted_at| deleted_at <= trash_cutoff);
+    if is_purged {
+        info!(
+            "Purging deleted item from '{}': '{}'",
+            feed_id,
+            item.title().unwrap_or("Untitled")
+        );
+    }
+    is_purged
+}
 
-    let items_removed = {
-        let mut channel = state.channel.lock().unwrap();
-        let original_count = channel.items().len();
+// Publishes scheduled items whose time has come, moving them to the top of their feed
+pub fn publish_scheduled_items(state: &AppState) -> usize {
+    let now = chrono::Utc::now();
+    let mut feeds = state.feeds.lock().unwrap();
+    let mut total_published = 0;
 
-        let items: Vec<Item> = channel
-            .items()
+    for (feed_id, channel) in feeds.iter_mut() {
+        let (mut due, items): (Vec<Item>, Vec<Item>) =
+            channel.items().iter().cloned().partition(|item| {
+                is_item_scheduled(item)
+                    && !is_item_deleted(item)
+                    && item
+                        .pub_date()
+                        .and_then(|d| parse_datetime(d).ok())
+                        .is_none_or(|publish_at| publish_at <= now)
+            });
+        if due.is_empty() {
+            continue;
+        }
+
+        // Newest first, like the rest of the feed
+        due.sort_by_key(|item| {
+            std::cmp::Reverse(item.pub_date().and_then(|d| parse_datetime(d).ok()))
+        });
+        for item in due.iter_mut() {
+            set_extension_value(&mut item.extensions, "scheduled", None);
+            info!(
+                "Publishing scheduled item in '{}': '{}'",
+                feed_id,
+                item.title().unwrap_or("Untitled")
+            );
+        }
+
+        let due_ids: Vec<String> = due
             .iter()
-            .filter(|item| {
-                if let Some(pub_date_str) = item.pub_date() {
-                    match chrono::DateTime::parse_from_rfc2822(pub_date_str) {
-                        Ok(pub_date) => {
-                            let is_old = pub_date.with_timezone(&chrono::Utc) < cutoff_date;
-                            if is_old {
-                                info!(
-                                    "Removing old item: '{}'",
-                                    item.title().unwrap_or("Untitled")
-                                );
-                            }
-                            !is_old
-                        }
-                        Err(_) => {
-                            warn!(
-                                "Invalid pub_date format for item: '{}'",
-                                item.title().unwrap_or("Untitled")
-                            );
-                            true // Keep items with invalid dates
-                        }
-                    }
-                } else {
-                    warn!(
-                        "Item has no pub_date: '{}'",
+            .filter_map(|item| item.guid().map(|g| g.value().to_string()))
+            .collect();
+        let mut updated = channel.clone();
+        updated.set_items(due.iter().cloned().chain(items).collect::<Vec<Item>>());
+        updated.set_last_build_date(now.to_rfc2822());
+
+        // Re-insert the items so storage orders them as the newest
+        let saved = state
+            .storage
+            .delete_items(feed_id, &updated, &due_ids)
+            .and_then(|_| {
+                due.iter()
+                    .rev()
+                    .try_for_each(|item| state.storage.insert_item(feed_id, &updated, item))
+            });
+        // The items stay scheduled and are tried again on the next run
+        if let Err(e) = saved {
+            error!("Failed to publish scheduled items in '{}': {}", feed_id, e);
+            continue;
+        }
+
+        feed_saved(state, feed_id, &updated);
+        let actor = AuditActor::system(AuditSource::Scheduler);
+        let entries: Vec<AuditEntry> = due
+            .iter()
+            .map(|item| AuditEntry::item(AuditAction::Publish, feed_id, &actor, None, Some(item)))
+            .collect();
+        record_changes(state, &entries);
+        *channel = updated;
+        total_published += due.len();
+    }
+
+    total_published
+}
+
+// An item's own expiry time wins, otherwise it expires once published before the cutoff
+fn is_item_expired(
+    feed_id: &str,
+    item: &Item,
+    cutoff_date: Option<chrono::DateTime<chrono::Utc>>,
+    now: chrono::DateTime<chrono::Utc>,
+) -> bool {
+    if let Some(expiry) = item_expiry(item) {
+        let is_expired = expiry.is_expired(now);
+        if is_expired {
+            info!(
+                "Removing expired item from '{}': '{}'",
+                feed_id,
+                item.title().unwrap_or("Untitled")
+            );
+        }
+        return is_expired;
+    }
+
+    let Some(cutoff_date) = cutoff_date else {
+        return false;
+    };
+    if let Some(pub_date_str) = item.pub_date() {
+        match chrono::DateTime::parse_from_rfc2822(pub_date_str) {
+            Ok(pub_date) => {
+                let is_old = pub_date.with_timezone(&chrono::Utc) < cutoff_date;
+                if is_old {
+                    info!(
+                        "Removing old item from '{}': '{}'",
+                        feed_id,
                         item.title().unwrap_or("Untitled")
                     );
-                    true // Keep items without pub_date
                 }
-            })
-            .cloned()
-            .collect();
+                is_old
+            }
+            Err(_) => {
+                warn!(
+                    "Invalid pub_date format for item: '{}'",
+                    item.title().unwrap_or("Untitled")
+                );
+                false // Keep items with invalid dates
+            }
+        }
+    } else {
+        warn!(
+            "Item has no pub_date: '{}'",
+            item.title().unwrap_or("Untitled")
+        );
+        false // Keep items without pub_date
+    }
+}
 
-        let removed_count = original_count - items.len();
+pub fn cleanup_old_items(state: &AppState) -> usize {
+    let default_max_age = default_max_item_age();
+    let now = chrono::Utc::now();
+    let trash_cutoff = now - chrono::Duration::seconds(trash_retention_seconds() as i64);
+    let mut feeds = state.feeds.lock().unwrap();
+    let mut total_removed = 0;
+    let mut removed_items = Vec::new();
+
+    for (feed_id, channel) in feeds.iter_mut() {
+        // A feed's own retention policy takes precedence over MAX_ITEM_AGE_SECONDS
+        let cutoff_date = match feed_max_item_age(channel).or(default_max_age) {
+            Some(0) => {
+                debug!(
+                    "Retention for feed '{}' is 0, only items with an expiry time will be removed",
+                    feed_id
+                );
+                None
+            }
+            Some(seconds) => Some(now - chrono::Duration::seconds(seconds as i64)),
+            None => {
+                debug!(
+                    "No retention policy for feed '{}', only items with an expiry time will be removed",
+                    feed_id
+                );
+                None
+            }
+        };
+
+        let (items, removed): (Vec<Item>, Vec<Item>) =
+            channel.items().iter().cloned().partition(|item| {
+                // Items in the trash are only removed once their retention has passed
+                if is_item_deleted(item) {
+                    !is_item_purged(feed_id, item, trash_cutoff)
+                } else {
+                    !is_item_expired(feed_id, item, cutoff_date, now)
+                }
+            });
+
+        // A cap lowered since the items were added is applied here
+        let mut updated = channel.clone();
+        updated.set_items(items);
+        let evicted = evict_items(&mut updated);
+        let removed_count = removed.len() + evicted.len();
 
         if removed_count > 0 {
-            channel.set_items(items);
-            channel.set_last_build_date(chrono::Utc::now().to_rfc2822());
-            write_channel(&channel, None, fs);
-            info!("Cleaned up {} old items from feed", removed_count);
+            let removed_ids: Vec<String> = removed
+                .iter()
+                .chain(&evicted)
+                .filter_map(|item| item.guid().map(|g| g.value().to_string()))
+                .collect();
+            updated.set_last_build_date(chrono::Utc::now().to_rfc2822());
+            // The items are kept and removal is tried again on the next run
+            if let Err(e) = state.storage.delete_items(feed_id, &updated, &removed_ids) {
+                error!("Failed to remove old items from '{}': {}", feed_id, e);
+                continue;
+            }
+            feed_saved(state, feed_id, &updated);
+            let actor = AuditActor::system(AuditSource::Cleanup);
+            let mut entries: Vec<AuditEntry> = removed
+                .iter()
+                .map(|item| {
+                    let action = if is_item_deleted(item) {
+                        AuditAction::Purge
+                    } else {
+                        AuditAction::Expire
+                    };
+                    AuditEntry::item(action, feed_id, &actor, Some(item), None)
+                })
+                .collect();
+            entries.extend(eviction_entries(feed_id, &evicted));
+            record_changes(state, &entries);
+            *channel = updated;
+            info!(
+                "Cleaned up {} old items from feed '{}'",
+                removed_count, feed_id
+            );
         }
 
-        removed_count
-    };
+        total_removed += removed_count;
+        removed_items.extend(removed);
+        removed_items.extend(evicted);
+    }
 
-    items_removed
+    state.attachments.remove_orphaned(&feeds, &removed_items);
+    total_removed
 }
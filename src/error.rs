@@ -0,0 +1,139 @@
+use crate::storage::StorageError;
+use axum::{
+    Json,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use log::error;
+use serde::Serialize;
+use std::fmt;
+
+// Errors returned by the API, each maps to an HTTP status and a machine-readable code
+#[derive(Debug)]
+pub enum ApiError {
+    // The request body or parameters could not be parsed
+    BadRequest(String),
+    Unauthorized(String),
+    Forbidden(String),
+    NotFound(String),
+    // The request conflicts with the current state, like reusing a feed id
+    Conflict(String),
+    // An If-Match precondition did not hold
+    PreconditionFailed(String),
+    // The request was understood but some of its fields are invalid
+    Validation(Vec<FieldError>),
+    // Details are logged, clients only see a generic message
+    Internal(String),
+}
+
+// A problem with a single field of a request
+#[derive(Debug, Serialize)]
+pub struct FieldError {
+    pub field: String,
+    pub message: String,
+}
+
+impl FieldError {
+    pub fn new(field: &str, message: &str) -> Self {
+        FieldError {
+            field: field.to_string(),
+            message: message.to_string(),
+        }
+    }
+}
+
+impl ApiError {
+    // Shorthand for a validation error on a single field
+    pub fn invalid(field: &str, message: &str) -> Self {
+        ApiError::Validation(vec![FieldError::new(field, message)])
+    }
+
+    pub fn status(&self) -> StatusCode {
+        match self {
+            ApiError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            ApiError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            ApiError::Forbidden(_) => StatusCode::FORBIDDEN,
+            ApiError::NotFound(_) => StatusCode::NOT_FOUND,
+            ApiError::Conflict(_) => StatusCode::CONFLICT,
+            ApiError::PreconditionFailed(_) => StatusCode::PRECONDITION_FAILED,
+            ApiError::Validation(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            ApiError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            ApiError::BadRequest(_) => "bad_request",
+            ApiError::Unauthorized(_) => "unauthorized",
+            ApiError::Forbidden(_) => "forbidden",
+            ApiError::NotFound(_) => "not_found",
+            ApiError::Conflict(_) => "conflict",
+            ApiError::PreconditionFailed(_) => "precondition_failed",
+            ApiError::Validation(_) => "validation_failed",
+            ApiError::Internal(_) => "internal_error",
+        }
+    }
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ApiError::BadRequest(message)
+            | ApiError::Unauthorized(message)
+            | ApiError::Forbidden(message)
+            | ApiError::NotFound(message)
+            | ApiError::Conflict(message)
+            | ApiError::PreconditionFailed(message) => write!(f, "{}", message),
+            ApiError::Validation(fields) => {
+                let messages: Vec<&str> = fields.iter().map(|f| f.message.as_str()).collect();
+                write!(f, "{}", messages.join(", "))
+            }
+            ApiError::Internal(_) => write!(f, "Internal server error"),
+        }
+    }
+}
+
+// Storage failures are never the client's fault
+impl From<StorageError> for ApiError {
+    fn from(e: StorageError) -> Self {
+        ApiError::Internal(e.to_string())
+    }
+}
+
+// Same envelope as successful responses, with the error details added
+#[derive(Serialize)]
+struct ApiErrorBody<'a> {
+    success: bool,
+    data: Option<()>,
+    message: String,
+    error: ApiErrorDetails<'a>,
+}
+
+#[derive(Serialize)]
+struct ApiErrorDetails<'a> {
+    code: &'static str,
+    #[serde(skip_serializing_if = "<[FieldError]>::is_empty")]
+    fields: &'a [FieldError],
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        if let ApiError::Internal(details) = &self {
+            error!("Internal error handling API request: {}", details);
+        }
+        let fields = match &self {
+            ApiError::Validation(fields) => fields.as_slice(),
+            _ => &[],
+        };
+        let body = ApiErrorBody {
+            success: false,
+            data: None,
+            message: self.to_string(),
+            error: ApiErrorDetails {
+                code: self.code(),
+                fields,
+            },
+        };
+        (self.status(), Json(body)).into_response()
+    }
+}
@@ -0,0 +1,349 @@
+use crate::common::{is_valid_feed_id, item_updated};
+use atom_syndication::{Category, Entry, Feed, FixedDateTime, Link, Person, Text};
+use rss::{Channel, Item, extension::atom::AtomExtension};
+use serde::Serialize;
+use uuid::Uuid;
+
+// Supported output formats for a feed
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum FeedFormat {
+    Rss,
+    Atom,
+    Json,
+}
+
+impl FeedFormat {
+    pub fn from_extension(extension: &str) -> Option<Self> {
+        match extension {
+            "xml" | "rss" => Some(FeedFormat::Rss),
+            "atom" => Some(FeedFormat::Atom),
+            "json" => Some(FeedFormat::Json),
+            _ => None,
+        }
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            FeedFormat::Rss => "application/xml",
+            FeedFormat::Atom => "application/atom+xml",
+            FeedFormat::Json => "application/feed+json",
+        }
+    }
+
+    // Picks the format with the highest quality value from an Accept header,
+    // falling back to RSS when nothing more specific is requested
+    pub fn negotiate(accept: Option<&str>) -> Self {
+        let Some(accept) = accept else {
+            return FeedFormat::Rss;
+        };
+
+        let mut best = (FeedFormat::Rss, 0.0_f32);
+        for media_range in accept.split(',') {
+            let mut parts = media_range.split(';').map(str::trim);
+            let media_type = parts.next().unwrap_or_default().to_ascii_lowercase();
+            let quality = parts
+                .find_map(|param| param.strip_prefix("q="))
+                .and_then(|q| q.parse::<f32>().ok())
+                .unwrap_or(1.0);
+
+            let format = match media_type.as_str() {
+                "application/rss+xml" | "application/xml" | "text/xml" => FeedFormat::Rss,
+                "application/atom+xml" => FeedFormat::Atom,
+                "application/feed+json" | "application/json" => FeedFormat::Json,
+                _ => continue,
+            };
+            if quality > best.1 {
+                best = (format, quality);
+            }
+        }
+        best.0
+    }
+}
+
+// Feed and format a served feed path like `/feed.xml` or `/feeds/news.atom` points at,
+// `/feed.{ext}` being the default feed
+pub fn parse_feed_path(path: &str) -> Option<(&str, FeedFormat)> {
+    let file_name = match path.strip_prefix("/feeds/") {
+        Some(file_name) => file_name,
+        None => path
+            .strip_prefix('/')
+            .filter(|file_name| file_name.starts_with("feed."))?,
+    };
+    let (feed_id, extension) = file_name.rsplit_once('.')?;
+    let format = FeedFormat::from_extension(extension)?;
+    is_valid_feed_id(feed_id).then_some((feed_id, format))
+}
+
+// The feed's own URL and the WebSub hub readers can subscribe to it through
+pub struct FeedLinks {
+    // Origin the feed is served from, root-relative enclosure URLs are resolved against it
+    pub base_url: String,
+    pub self_url: String,
+    pub hub_url: String,
+}
+
+impl FeedLinks {
+    fn atom_links(&self, format: FeedFormat) -> Vec<Link> {
+        let mut hub = Link::default();
+        hub.set_href(&self.hub_url);
+        hub.set_rel("hub");
+        let mut this = Link::default();
+        this.set_href(&self.self_url);
+        this.set_rel("self");
+        // RSS is served as plain XML for browsers, but advertised with its own type
+        let mime_type = match format {
+            FeedFormat::Rss => "application/rss+xml",
+            format => format.content_type(),
+        };
+        this.set_mime_type(Some(mime_type.to_string()));
+        vec![hub, this]
+    }
+}
+
+pub fn render_feed(
+    feed_id: &str,
+    channel: &Channel,
+    format: FeedFormat,
+    links: Option<&FeedLinks>,
+) -> String {
+    let resolved;
+    let channel = match links {
+        Some(links) => {
+            resolved = resolve_enclosure_urls(channel, &links.base_url);
+            &resolved
+        }
+        None => channel,
+    };
+    match format {
+        FeedFormat::Rss => render_rss(channel, links),
+        FeedFormat::Atom => render_atom(feed_id, channel, links),
+        FeedFormat::Json => render_json_feed(channel, links),
+    }
+}
+
+// Uploaded attachments are saved with root-relative URLs, readers need them absolute
+pub fn resolve_enclosure_urls(channel: &Channel, base_url: &str) -> Channel {
+    let mut channel = channel.clone();
+    let items = channel
+        .items()
+        .iter()
+        .cloned()
+        .map(|mut item| {
+            if let Some(enclosure) = item.enclosure.as_mut()
+                && enclosure.url.starts_with('/')
+                && !enclosure.url.starts_with("//")
+            {
+                enclosure.url = format!("{}{}", base_url.trim_end_matches('/'), enclosure.url);
+            }
+            item
+        })
+        .collect::<Vec<_>>();
+    channel.set_items(items);
+    channel
+}
+
+pub fn render_rss(channel: &Channel, links: Option<&FeedLinks>) -> String {
+    let Some(links) = links else {
+        return channel.to_string();
+    };
+    let mut channel = channel.clone();
+    channel.set_atom_ext(AtomExtension {
+        links: links.atom_links(FeedFormat::Rss),
+    });
+    channel.to_string()
+}
+
+// JSON Feed 1.1 structures (https://jsonfeed.org/version/1.1)
+#[derive(Serialize)]
+pub struct JsonFeed {
+    version: &'static str,
+    title: String,
+    home_page_url: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    feed_url: Option<String>,
+    description: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    hubs: Vec<JsonFeedHub>,
+    items: Vec<JsonFeedItem>,
+}
+
+#[derive(Serialize)]
+pub struct JsonFeedHub {
+    #[serde(rename = "type")]
+    hub_type: &'static str,
+    url: String,
+}
+
+#[derive(Serialize)]
+pub struct JsonFeedItem {
+    id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    url: Option<String>,
+    title: String,
+    content_text: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    date_published: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    date_modified: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    tags: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    authors: Vec<JsonFeedAuthor>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    attachments: Vec<JsonFeedAttachment>,
+}
+
+#[derive(Serialize)]
+pub struct JsonFeedAuthor {
+    name: String,
+}
+
+#[derive(Serialize)]
+pub struct JsonFeedAttachment {
+    url: String,
+    mime_type: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    size_in_bytes: Option<u64>,
+}
+
+// Stable identifier for an item, derived from its GUID
+pub fn item_id(item: &Item) -> String {
+    item.guid()
+        .map(|g| g.value().to_string())
+        .unwrap_or_default()
+}
+
+fn atom_item_id(item: &Item) -> String {
+    let id = item_id(item);
+    match Uuid::parse_str(&id) {
+        Ok(uuid) => uuid.urn().to_string(),
+        Err(_) => id,
+    }
+}
+
+fn parse_date(date: Option<&str>) -> Option<FixedDateTime> {
+    date.and_then(|d| chrono::DateTime::parse_from_rfc2822(d).ok())
+}
+
+pub fn render_atom(feed_id: &str, channel: &Channel, links: Option<&FeedLinks>) -> String {
+    let updated =
+        parse_date(channel.last_build_date()).unwrap_or_else(|| chrono::Utc::now().fixed_offset());
+
+    let entries: Vec<Entry> = channel
+        .items()
+        .iter()
+        .map(|item| {
+            let published = parse_date(item.pub_date());
+            let modified = item_updated(item).map(|d| d.fixed_offset());
+            let mut entry = Entry::default();
+            entry.set_id(atom_item_id(item));
+            entry.set_title(Text::plain(item.title().unwrap_or("Untitled")));
+            entry.set_updated(modified.or(published).unwrap_or(updated));
+            entry.set_published(published);
+            entry.set_summary(item.description().map(Text::plain));
+            let mut links = Vec::new();
+            if let Some(link) = item.link() {
+                let mut alternate = Link::default();
+                alternate.set_href(link);
+                links.push(alternate);
+            }
+            if let Some(enclosure) = item.enclosure() {
+                let mut link = Link::default();
+                link.set_href(enclosure.url());
+                link.set_rel("enclosure");
+                link.set_mime_type(Some(enclosure.mime_type().to_string()));
+                link.set_length(Some(enclosure.length().to_string()).filter(|l| l != "0"));
+                links.push(link);
+            }
+            entry.set_links(links);
+            entry.set_categories(
+                item.categories()
+                    .iter()
+                    .map(|category| {
+                        let mut atom_category = Category::default();
+                        atom_category.set_term(category.name());
+                        atom_category.set_scheme(category.domain().map(|s| s.to_string()));
+                        atom_category
+                    })
+                    .collect::<Vec<Category>>(),
+            );
+            if let Some(author) = item.author() {
+                let mut person = Person::default();
+                person.set_name(author);
+                entry.set_authors(vec![person]);
+            }
+            entry
+        })
+        .collect();
+
+    let mut author = Person::default();
+    author.set_name(channel.title());
+
+    let mut alternate = Link::default();
+    alternate.set_href(channel.link());
+
+    let mut feed = Feed::default();
+    feed.set_id(format!("urn:feed-crafter:{}", feed_id));
+    feed.set_title(Text::plain(channel.title()));
+    feed.set_subtitle(Some(Text::plain(channel.description())));
+    feed.set_updated(updated);
+    feed.set_authors(vec![author]);
+    let mut feed_links = vec![alternate];
+    if let Some(links) = links {
+        feed_links.extend(links.atom_links(FeedFormat::Atom));
+    }
+    feed.set_links(feed_links);
+    feed.set_entries(entries);
+    feed.to_string()
+}
+
+pub fn render_json_feed(channel: &Channel, links: Option<&FeedLinks>) -> String {
+    let json_feed = JsonFeed {
+        version: "https://jsonfeed.org/version/1.1",
+        title: channel.title().to_string(),
+        home_page_url: channel.link().to_string(),
+        feed_url: links.map(|links| links.self_url.clone()),
+        description: channel.description().to_string(),
+        hubs: links
+            .map(|links| JsonFeedHub {
+                hub_type: "WebSub",
+                url: links.hub_url.clone(),
+            })
+            .into_iter()
+            .collect(),
+        items: channel
+            .items()
+            .iter()
+            .map(|item| JsonFeedItem {
+                id: item_id(item),
+                url: item.link().map(|s| s.to_string()),
+                title: item.title().unwrap_or("Untitled").to_string(),
+                content_text: item.description().unwrap_or_default().to_string(),
+                date_published: parse_date(item.pub_date()).map(|d| d.to_rfc3339()),
+                date_modified: item_updated(item).map(|d| d.to_rfc3339()),
+                tags: item
+                    .categories()
+                    .iter()
+                    .map(|category| category.name().to_string())
+                    .collect(),
+                authors: item
+                    .author()
+                    .map(|name| JsonFeedAuthor {
+                        name: name.to_string(),
+                    })
+                    .into_iter()
+                    .collect(),
+                attachments: item
+                    .enclosure()
+                    .map(|enclosure| JsonFeedAttachment {
+                        url: enclosure.url().to_string(),
+                        mime_type: enclosure.mime_type().to_string(),
+                        size_in_bytes: enclosure.length().parse().ok().filter(|l| *l > 0),
+                    })
+                    .into_iter()
+                    .collect(),
+            })
+            .collect(),
+    };
+    serde_json::to_string(&json_feed).expect("Failed to serialize JSON feed")
+}
@@ -1,3 +1,15 @@
 pub mod api;
+pub mod attachments;
+pub mod audit;
+pub mod auth;
+pub mod backups;
+pub mod cache;
 pub mod common;
+pub mod error;
+pub mod formats;
+pub mod session;
+pub mod storage;
+pub mod stream;
 pub mod web;
+pub mod webhooks;
+pub mod websub;
@@ -1,25 +1,50 @@
 mod api;
+mod attachments;
+mod audit;
+mod auth;
+mod backups;
+mod cache;
 mod common;
+mod error;
+mod formats;
+mod session;
+mod storage;
+mod stream;
 mod web;
+mod webhooks;
+mod websub;
 
 use api::*;
+use attachments::AttachmentStore;
+use audit::AuditLog;
+use auth::{ApiKeyStore, require_api_key};
 use axum::{
     Router,
-    routing::{delete, get, post, put},
+    extract::DefaultBodyLimit,
+    middleware,
+    routing::{delete, get, patch, post, put},
 };
+use backups::BackupStore;
+use cache::FeedCache;
 use common::*;
 use dotenvy::dotenv;
-use log::{debug, info};
+use log::{debug, error, info};
+use session::{SessionStore, UserStore, require_feed_access, require_login, session_ttl_seconds};
 use std::{
     env, fs,
+    net::SocketAddr,
     sync::{Arc, Mutex},
 };
+use storage::open_storage;
+use stream::EventStream;
 use tokio::{
     net::TcpListener,
     time::{Duration, interval},
 };
 use tower_http::services::ServeDir;
 use web::*;
+use webhooks::WebhookStore;
+use websub::{HUB_ROUTE, WebSubHub};
 
 #[tokio::main]
 async fn main() {
@@ -27,33 +52,165 @@ async fn main() {
     env_logger::init();
 
     // Ensure the ./feed directory exists
-    fs::create_dir_all("./feed").expect("Failed to create ./feed directory");
+    fs::create_dir_all(FEED_DIRECTORY).expect("Failed to create ./feed directory");
 
-    // Initialize or load the RSS feed
-    let channel = initialize_feed(&RealFileSystem);
+    // Open the configured storage backend and load every feed, or create the default feed
+    let storage = open_storage().unwrap_or_else(|e| {
+        error!("Failed to open storage: {}", e);
+        std::process::exit(1);
+    });
+    let feeds = load_feeds(storage.as_ref()).unwrap_or_else(|e| {
+        error!("Failed to load feeds: {}", e);
+        std::process::exit(1);
+    });
+    let api_keys = ApiKeyStore::load().unwrap_or_else(|e| {
+        error!("Failed to load API keys: {}", e);
+        std::process::exit(1);
+    });
+    let users = UserStore::load().unwrap_or_else(|e| {
+        error!("Failed to load users: {}", e);
+        std::process::exit(1);
+    });
+    let webhooks = WebhookStore::load().unwrap_or_else(|e| {
+        error!("Failed to load webhooks: {}", e);
+        std::process::exit(1);
+    });
+    let websub = WebSubHub::load().unwrap_or_else(|e| {
+        error!("Failed to load WebSub subscriptions: {}", e);
+        std::process::exit(1);
+    });
     let app_state = AppState {
-        channel: Arc::new(Mutex::new(channel)),
+        feeds: Arc::new(Mutex::new(feeds)),
+        storage,
+        api_keys: Arc::new(Mutex::new(api_keys)),
+        users: Arc::new(Mutex::new(users)),
+        sessions: Arc::new(Mutex::new(SessionStore::new(session_ttl_seconds()))),
+        attachments: Arc::new(AttachmentStore::load()),
+        backups: Arc::new(BackupStore::load()),
+        audit: Arc::new(AuditLog::load()),
+        webhooks: Arc::new(webhooks),
+        websub: Arc::new(websub),
+        events: Arc::new(EventStream::new()),
+        feed_cache: Arc::new(FeedCache::new()),
     };
 
-    // Start the cleanup timer
+    // Requests may carry an attachment, leave room for the other form fields
+    let body_limit = DefaultBodyLimit::max(app_state.attachments.max_bytes() as usize + 64 * 1024);
+
+    // Start the cleanup timer and the scheduled item publisher
     start_cleanup_timer(app_state.clone());
+    start_publish_scheduler(app_state.clone());
+    app_state.webhooks.start();
+    app_state.websub.start();
 
-    // Build our application with routes
-    let app = Router::new()
-        // Web interface routes
-        .route("/", get(index))
-        .route("/feed.xml", get(serve_file))
-        .route("/add", get(add_item_form))
-        .route("/add", post(web_add_item))
-        .route("/delete/{id}", post(web_delete_item))
-        .route("/edit/{id}", get(edit_item_form))
-        .route("/edit/{id}", post(web_edit_item))
-        .route("/health", get(health_check))
-        // API routes
+    // API routes, every request passes through the API key check
+    let api_routes = Router::new()
+        .route("/api/feeds", get(api_get_feeds))
+        .route("/api/feeds", post(api_add_feed))
+        .route("/api/feeds/{feed}", get(api_get_feed))
+        .route("/api/feeds/{feed}", put(api_edit_feed))
+        .route("/api/feeds/{feed}", delete(api_delete_feed))
+        .route("/api/feeds/{feed}/backups", get(api_get_backups))
+        .route(
+            "/api/feeds/{feed}/backups/{id}/restore",
+            post(api_restore_backup),
+        )
+        .route("/api/feeds/{feed}/items", get(api_get_items))
+        .route("/api/feeds/{feed}/items", post(api_add_item))
+        .route("/api/feeds/{feed}/items/bulk", post(api_bulk_items))
+        .route("/api/feeds/{feed}/items/{id}", get(api_get_item))
+        .route("/api/feeds/{feed}/items/{id}", delete(api_delete_item))
+        .route("/api/feeds/{feed}/items/{id}", put(api_edit_item))
+        .route("/api/feeds/{feed}/items/{id}", patch(api_patch_item))
+        .route(
+            "/api/feeds/{feed}/items/{id}/restore",
+            post(api_restore_item),
+        )
+        .route("/api/feeds/{feed}/trash", get(api_get_trash))
+        // Item routes without a feed segment operate on the default feed
+        .route("/api/backups", get(api_get_backups))
+        .route("/api/backups/{id}/restore", post(api_restore_backup))
         .route("/api/items", get(api_get_items))
         .route("/api/items", post(api_add_item))
+        .route("/api/items/bulk", post(api_bulk_items))
+        .route("/api/items/{id}", get(api_get_item))
         .route("/api/items/{id}", delete(api_delete_item))
         .route("/api/items/{id}", put(api_edit_item))
+        .route("/api/items/{id}", patch(api_patch_item))
+        .route("/api/items/{id}/restore", post(api_restore_item))
+        .route("/api/trash", get(api_get_trash))
+        .route("/api/audit", get(api_get_audit))
+        .route("/api/stream", get(api_stream))
+        .route("/api/webhooks", get(api_get_webhooks))
+        .route("/api/webhooks", post(api_add_webhook))
+        .route("/api/webhooks/deliveries", get(api_get_webhook_deliveries))
+        .route("/api/webhooks/{id}", delete(api_delete_webhook))
+        .route("/api/keys", get(api_get_keys))
+        .route("/api/keys", post(api_add_key))
+        .route("/api/keys/{id}", delete(api_delete_key))
+        .route_layer(middleware::from_fn_with_state(
+            app_state.clone(),
+            require_api_key,
+        ))
+        .layer(body_limit);
+
+    // Feed routes, public unless PUBLIC_FEEDS=false
+    let feed_routes = Router::new()
+        .route("/feed.xml", get(serve_file))
+        .route("/feed.atom", get(serve_file))
+        .route("/feed.json", get(serve_file))
+        .route("/feeds/{file}", get(serve_file))
+        .route(HUB_ROUTE, post(websub_hub))
+        .route("/attachments/{file}", get(serve_attachment))
+        .route_layer(middleware::from_fn_with_state(
+            app_state.clone(),
+            require_feed_access,
+        ));
+
+    // Web interface routes, these require a logged in user
+    let web_routes = Router::new()
+        .route("/", get(feeds_index))
+        .route("/manage", post(web_add_feed))
+        .route("/manage/{feed}", get(index))
+        .route("/manage/{feed}/settings", get(edit_feed_form))
+        .route("/manage/{feed}/settings", post(web_edit_feed))
+        .route("/manage/{feed}/backups", get(backups_index))
+        .route(
+            "/manage/{feed}/backups/{id}/restore",
+            post(web_restore_backup),
+        )
+        .route("/manage/{feed}/remove", post(web_delete_feed))
+        .route("/manage/{feed}/add", get(add_item_form))
+        .route("/manage/{feed}/add", post(web_add_item))
+        .route("/manage/{feed}/delete/{id}", post(web_delete_item))
+        .route("/manage/{feed}/restore/{id}", post(web_restore_item))
+        .route("/manage/{feed}/edit/{id}", get(edit_item_form))
+        .route("/manage/{feed}/edit/{id}", post(web_edit_item))
+        .route("/manage/{feed}/stream", get(web_stream))
+        .route("/audit", get(audit_index))
+        .route("/users", get(users_index))
+        .route("/users", post(web_add_user))
+        .route("/users/{username}/delete", post(web_delete_user))
+        .route("/logout", post(web_logout))
+        .route_layer(middleware::from_fn_with_state(
+            app_state.clone(),
+            require_login,
+        ))
+        .layer(body_limit);
+
+    // Build our application with routes
+    let app = Router::new()
+        .route("/login", get(login_form))
+        .route("/login", post(web_login))
+        .route("/setup", get(setup_form))
+        .route("/setup", post(web_setup))
+        .route("/health", get(health_check))
+        .route("/add", get(legacy_add_item).post(legacy_form_post))
+        .route("/edit/{id}", get(legacy_edit_item).post(legacy_form_post))
+        .route("/delete/{id}", post(legacy_form_post))
+        .merge(feed_routes)
+        .merge(web_routes)
+        .merge(api_routes)
         .nest_service("/static", ServeDir::new("static"))
         .with_state(app_state);
 
@@ -65,7 +222,13 @@ async fn main() {
         .expect("Failed to bind to address");
 
     info!("Server running on http://{}:{}", address, port);
-    axum::serve(listener, app).await.unwrap();
+    // The peer address lets /setup tell requests from this machine apart
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .await
+    .unwrap();
 }
 
 pub fn start_cleanup_timer(state: AppState) {
@@ -83,7 +246,7 @@ pub fn start_cleanup_timer(state: AppState) {
         loop {
             interval_timer.tick().await;
 
-            let removed_count = cleanup_old_items(&state, &RealFileSystem);
+            let removed_count = cleanup_old_items(&state);
             if removed_count > 0 {
                 debug!("Periodic cleanup removed {} items", removed_count);
             } else {
@@ -92,3 +255,24 @@ pub fn start_cleanup_timer(state: AppState) {
         }
     });
 }
+
+pub fn start_publish_scheduler(state: AppState) {
+    let publish_interval_seconds = env::var("PUBLISH_INTERVAL_SECONDS")
+        .unwrap_or_else(|_| "60".to_string()) // Default to 1 minute
+        .parse::<u64>()
+        .unwrap_or(60);
+
+    tokio::spawn(async move {
+        let mut interval_timer = interval(Duration::from_secs(publish_interval_seconds.max(1)));
+
+        // The first tick fires immediately, publishing anything that came due while stopped
+        loop {
+            interval_timer.tick().await;
+
+            let published_count = publish_scheduled_items(&state);
+            if published_count > 0 {
+                debug!("Scheduler published {} items", published_count);
+            }
+        }
+    });
+}
@@ -0,0 +1,376 @@
+use crate::auth::hash_token;
+use crate::common::*;
+use argon2::{
+    Argon2,
+    password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
+};
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    http::{HeaderMap, StatusCode, header},
+    middleware::Next,
+    response::{IntoResponse, Redirect, Response},
+};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, env, fmt, fs, io, net::SocketAddr, path::Path};
+use uuid::Uuid;
+
+pub const SESSION_COOKIE: &str = "feedcrafter_session";
+
+// Local web interface account, only the Argon2 hash of the password is stored
+#[derive(Clone, Serialize, Deserialize)]
+pub struct User {
+    pub username: String,
+    pub password_hash: String,
+    pub created: String,
+}
+
+pub fn hash_password(password: &str) -> String {
+    let salt =
+        SaltString::encode_b64(Uuid::new_v4().as_bytes()).expect("Failed to encode password salt");
+    Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .expect("Failed to hash password")
+        .to_string()
+}
+
+pub fn verify_password(password: &str, password_hash: &str) -> bool {
+    PasswordHash::new(password_hash)
+        .map(|hash| {
+            Argon2::default()
+                .verify_password(password.as_bytes(), &hash)
+                .is_ok()
+        })
+        .unwrap_or(false)
+}
+
+// Why an account couldn't be created
+#[derive(Debug)]
+pub enum UserError {
+    Invalid(String),
+    Save(io::Error),
+}
+
+impl fmt::Display for UserError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UserError::Invalid(message) => f.write_str(message),
+            UserError::Save(e) => write!(f, "Failed to save users: {}", e),
+        }
+    }
+}
+
+pub struct UserStore {
+    users: Vec<User>,
+    path: String,
+}
+
+impl UserStore {
+    // Loads accounts from USERS_FILE, when there are none the first one is created at /setup
+    pub fn load() -> io::Result<Self> {
+        let path =
+            env::var("USERS_FILE").unwrap_or_else(|_| format!("{}/users.json", FEED_DIRECTORY));
+        let users = if Path::new(&path).exists() {
+            let contents = fs::read_to_string(&path)?;
+            serde_json::from_str(&contents)?
+        } else {
+            warn!("No web interface users exist yet, create the first one at /setup");
+            Vec::new()
+        };
+
+        Ok(Self { users, path })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.users.is_empty()
+    }
+
+    pub fn users(&self) -> &[User] {
+        &self.users
+    }
+
+    pub fn create(&mut self, username: &str, password: &str) -> Result<(), UserError> {
+        let username = username.trim();
+        if username.is_empty() || username.len() > 64 {
+            return Err(UserError::Invalid(
+                "Username must be 1-64 characters".to_string(),
+            ));
+        }
+        if password.chars().count() < 8 {
+            return Err(UserError::Invalid(
+                "Password must be at least 8 characters".to_string(),
+            ));
+        }
+        if self.users.iter().any(|user| user.username == username) {
+            return Err(UserError::Invalid(
+                "A user with this name already exists".to_string(),
+            ));
+        }
+
+        let mut users = self.users.clone();
+        users.push(User {
+            username: username.to_string(),
+            password_hash: hash_password(password),
+            created: chrono::Utc::now().to_rfc2822(),
+        });
+        self.save(&users).map_err(UserError::Save)?;
+        self.users = users;
+        info!("User created: {}", username);
+        Ok(())
+    }
+
+    pub fn verify(&self, username: &str, password: &str) -> bool {
+        self.users
+            .iter()
+            .find(|user| user.username == username.trim())
+            .map(|user| verify_password(password, &user.password_hash))
+            .unwrap_or(false)
+    }
+
+    // The last account can't be removed, otherwise the interface would be open for /setup again
+    pub fn delete(&mut self, username: &str) -> io::Result<bool> {
+        if self.users.len() <= 1 {
+            return Ok(false);
+        }
+        let Some(index) = self.users.iter().position(|user| user.username == username) else {
+            return Ok(false);
+        };
+        let mut users = self.users.clone();
+        users.remove(index);
+        self.save(&users)?;
+        self.users = users;
+        info!("User deleted: {}", username);
+        Ok(true)
+    }
+
+    // The accounts in memory are only replaced once the file has been written
+    fn save(&self, users: &[User]) -> io::Result<()> {
+        let contents = serde_json::to_string_pretty(users)?;
+        RealFileSystem.write_atomic(&self.path, &contents)
+    }
+}
+
+// How the first account may be created: with SETUP_TOKEN when it is set, otherwise only from
+// this machine
+pub fn setup_token() -> Option<String> {
+    env::var("SETUP_TOKEN")
+        .ok()
+        .map(|token| token.trim().to_string())
+        .filter(|token| !token.is_empty())
+}
+
+// Whether the request came straight from a loopback address. Requests passed on by a proxy
+// carry forwarding headers and are never treated as local
+pub fn is_local_request(
+    connect_info: Option<&ConnectInfo<SocketAddr>>,
+    headers: &HeaderMap,
+) -> bool {
+    let forwarded = ["forwarded", "x-forwarded-for", "x-real-ip"]
+        .iter()
+        .any(|name| headers.contains_key(*name));
+    !forwarded && connect_info.is_some_and(|ConnectInfo(addr)| addr.ip().is_loopback())
+}
+
+// A logged in browser session, stored in the request extensions for web handlers
+#[derive(Clone)]
+pub struct Session {
+    pub username: String,
+    pub csrf_token: String,
+    expires_at: i64,
+}
+
+impl Session {
+    pub fn verify_csrf(&self, csrf_token: &str) -> Result<(), StatusCode> {
+        if !csrf_token.is_empty() && csrf_token == self.csrf_token {
+            Ok(())
+        } else {
+            warn!("Rejected form submission with invalid CSRF token");
+            Err(StatusCode::FORBIDDEN)
+        }
+    }
+}
+
+pub struct SessionStore {
+    sessions: HashMap<String, Session>,
+    ttl_seconds: i64,
+}
+
+fn random_token() -> String {
+    format!("{}{}", Uuid::new_v4().simple(), Uuid::new_v4().simple())
+}
+
+impl SessionStore {
+    pub fn new(ttl_seconds: i64) -> Self {
+        Self {
+            sessions: HashMap::new(),
+            ttl_seconds,
+        }
+    }
+
+    pub fn ttl_seconds(&self) -> i64 {
+        self.ttl_seconds
+    }
+
+    // Returns the token for the session cookie
+    pub fn create(&mut self, username: &str) -> String {
+        let now = chrono::Utc::now().timestamp();
+        self.sessions.retain(|_, session| session.expires_at > now);
+
+        let token = random_token();
+        self.sessions.insert(
+            hash_token(&token),
+            Session {
+                username: username.to_string(),
+                csrf_token: random_token(),
+                expires_at: now + self.ttl_seconds,
+            },
+        );
+        token
+    }
+
+    pub fn get(&mut self, token: &str) -> Option<Session> {
+        let key = hash_token(token);
+        let session = self.sessions.get(&key)?;
+        if session.expires_at <= chrono::Utc::now().timestamp() {
+            self.sessions.remove(&key);
+            return None;
+        }
+        Some(session.clone())
+    }
+
+    pub fn remove(&mut self, token: &str) {
+        self.sessions.remove(&hash_token(token));
+    }
+
+    pub fn remove_user(&mut self, username: &str) {
+        self.sessions
+            .retain(|_, session| session.username != username);
+    }
+}
+
+pub fn session_ttl_seconds() -> i64 {
+    env::var("SESSION_TTL_SECONDS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(7 * 24 * 60 * 60) // Default to 1 week
+}
+
+pub fn env_flag(name: &str, default: bool) -> bool {
+    env::var(name)
+        .map(|v| matches!(v.trim().to_ascii_lowercase().as_str(), "1" | "true" | "yes"))
+        .unwrap_or(default)
+}
+
+pub fn session_token(headers: &HeaderMap) -> Option<String> {
+    headers
+        .get_all(header::COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(';'))
+        .filter_map(|cookie| cookie.trim().split_once('='))
+        .find(|(name, _)| *name == SESSION_COOKIE)
+        .map(|(_, value)| value.to_string())
+}
+
+// Set-Cookie value for a session token, a max age of 0 clears the cookie
+pub fn session_cookie(token: &str, max_age: i64) -> String {
+    let secure = if env_flag("SESSION_COOKIE_SECURE", false) {
+        "; Secure"
+    } else {
+        ""
+    };
+    format!(
+        "{}={}; Path=/; Max-Age={}; HttpOnly; SameSite=Lax{}",
+        SESSION_COOKIE, token, max_age, secure
+    )
+}
+
+fn current_session(state: &AppState, headers: &HeaderMap) -> Option<Session> {
+    let token = session_token(headers)?;
+    state.sessions.lock().unwrap().get(&token)
+}
+
+// Only allow redirects to paths on this site after logging in. Browsers read `\` as `/` and
+// drop tabs and newlines, so `/\evil.example` would leave the site too
+pub fn safe_redirect_target(next: Option<&str>) -> String {
+    match next {
+        Some(next)
+            if next.starts_with('/')
+                && !next.starts_with("//")
+                && !next.chars().any(|c| c == '\\' || c.is_control()) =>
+        {
+            next.to_string()
+        }
+        _ => "/".to_string(),
+    }
+}
+
+pub async fn require_login(
+    State(state): State<AppState>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let Some(session) = current_session(&state, request.headers()) else {
+        if state.users.lock().unwrap().is_empty() {
+            return Redirect::to("/setup").into_response();
+        }
+        let target = request
+            .uri()
+            .path_and_query()
+            .map(|p| p.as_str())
+            .unwrap_or("/");
+        let next = url_encode(target);
+        return Redirect::to(&format!("/login?next={}", next)).into_response();
+    };
+
+    request.extensions_mut().insert(session);
+    next.run(request).await
+}
+
+// Feeds are public unless PUBLIC_FEEDS=false, then a session or a read API key is required
+pub async fn require_feed_access(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if env_flag("PUBLIC_FEEDS", true) || current_session(&state, request.headers()).is_some() {
+        return next.run(request).await;
+    }
+
+    // Every API key scope includes read access
+    let has_api_key = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .is_some_and(|token| {
+            state
+                .api_keys
+                .lock()
+                .unwrap()
+                .authenticate(token.trim())
+                .is_some()
+        });
+    if has_api_key {
+        return next.run(request).await;
+    }
+
+    (
+        StatusCode::UNAUTHORIZED,
+        [(header::WWW_AUTHENTICATE, "Bearer")],
+        "Authentication required",
+    )
+        .into_response()
+}
+
+fn url_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
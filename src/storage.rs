@@ -0,0 +1,440 @@
+use crate::common::*;
+use log::info;
+use rss::{Channel, Item};
+use rusqlite::{Connection, OptionalExtension, params};
+use std::{
+    env, fmt,
+    io::Read,
+    sync::{Arc, Mutex},
+};
+
+// Failures reading or writing feeds, so callers can recover instead of crashing
+#[derive(Debug)]
+pub enum StorageError {
+    Io(std::io::Error),
+    // A feed document that is not valid RSS
+    Feed(rss::Error),
+    Database(rusqlite::Error),
+    Json(serde_json::Error),
+    // Missing or invalid settings, like the environment variables for the default feed
+    Config(String),
+    Invalid(String),
+}
+
+impl fmt::Display for StorageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StorageError::Io(e) => write!(f, "I/O error: {}", e),
+            StorageError::Feed(e) => write!(f, "Invalid feed: {}", e),
+            StorageError::Database(e) => write!(f, "Database error: {}", e),
+            StorageError::Json(e) => write!(f, "Invalid stored data: {}", e),
+            StorageError::Config(message) | StorageError::Invalid(message) => {
+                write!(f, "{}", message)
+            }
+        }
+    }
+}
+
+impl std::error::Error for StorageError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            StorageError::Io(e) => Some(e),
+            StorageError::Feed(e) => Some(e),
+            StorageError::Database(e) => Some(e),
+            StorageError::Json(e) => Some(e),
+            StorageError::Config(_) | StorageError::Invalid(_) => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for StorageError {
+    fn from(e: std::io::Error) -> Self {
+        StorageError::Io(e)
+    }
+}
+
+impl From<rss::Error> for StorageError {
+    fn from(e: rss::Error) -> Self {
+        StorageError::Feed(e)
+    }
+}
+
+impl From<rusqlite::Error> for StorageError {
+    fn from(e: rusqlite::Error) -> Self {
+        StorageError::Database(e)
+    }
+}
+
+impl From<serde_json::Error> for StorageError {
+    fn from(e: serde_json::Error) -> Self {
+        StorageError::Json(e)
+    }
+}
+
+pub type StorageResult<T> = Result<T, StorageError>;
+
+// Persistent storage for feeds and their items.
+//
+// Mutations receive the already updated in-memory channel alongside the change, so
+// whole-document backends can persist it in a single write while row based backends
+// only touch what changed.
+pub trait Storage {
+    fn list_feeds(&self) -> StorageResult<Vec<String>>;
+    fn load_feed(&self, feed_id: &str) -> StorageResult<Channel>;
+    fn list_items(&self, feed_id: &str) -> StorageResult<Vec<Item>>;
+    fn get_item(&self, feed_id: &str, item_id: &str) -> StorageResult<Option<Item>>;
+
+    // Replaces the feed's metadata and all of its items
+    fn save_feed(&self, feed_id: &str, channel: &Channel) -> StorageResult<()>;
+    // Updates only the feed's metadata
+    fn update_feed(&self, feed_id: &str, channel: &Channel) -> StorageResult<()>;
+    fn rename_feed(&self, feed_id: &str, new_feed_id: &str) -> StorageResult<()>;
+    fn delete_feed(&self, feed_id: &str) -> StorageResult<()>;
+    // Moves a feed that can't be loaded out of the way under a new name, which is never
+    // listed as a feed, so it can be inspected or recovered by hand
+    fn quarantine_feed(&self, feed_id: &str, name: &str) -> StorageResult<()>;
+
+    fn insert_item(&self, feed_id: &str, channel: &Channel, item: &Item) -> StorageResult<()>;
+    fn update_item(&self, feed_id: &str, channel: &Channel, item: &Item) -> StorageResult<()>;
+    fn delete_items(
+        &self,
+        feed_id: &str,
+        channel: &Channel,
+        item_ids: &[String],
+    ) -> StorageResult<()>;
+
+    // Reports whether the backend is currently usable, for the health check
+    fn check(&self) -> StorageResult<()>;
+}
+
+pub type SharedStorage = Arc<dyn Storage + Send + Sync>;
+
+// Opens the backend selected by STORAGE_BACKEND (`xml` by default, or `sqlite`)
+pub fn open_storage() -> StorageResult<SharedStorage> {
+    let backend = env::var("STORAGE_BACKEND").unwrap_or_else(|_| "xml".to_string());
+    match backend.as_str() {
+        "xml" => {
+            info!("Using XML file storage in {}", FEED_DIRECTORY);
+            Ok(Arc::new(XmlStorage::new(RealFileSystem)))
+        }
+        "sqlite" => {
+            let path = env::var("SQLITE_PATH")
+                .unwrap_or_else(|_| format!("{}/feed-crafter.db", FEED_DIRECTORY));
+            info!("Using SQLite storage at {}", path);
+            Ok(Arc::new(SqliteStorage::open(&path)?))
+        }
+        other => Err(StorageError::Config(format!(
+            "Unknown STORAGE_BACKEND '{}', expected 'xml' or 'sqlite'",
+            other
+        ))),
+    }
+}
+
+// One RSS XML document per feed, rewritten on every change
+pub struct XmlStorage<F: FileSystem> {
+    pub fs: F,
+}
+
+impl<F: FileSystem> XmlStorage<F> {
+    pub fn new(fs: F) -> Self {
+        Self { fs }
+    }
+}
+
+impl<F: FileSystem> Storage for XmlStorage<F>
+where
+    F::Reader: Read,
+{
+    fn list_feeds(&self) -> StorageResult<Vec<String>> {
+        let mut feed_ids: Vec<String> = self
+            .fs
+            .list(FEED_DIRECTORY)?
+            .iter()
+            .filter_map(|name| name.strip_suffix(".xml"))
+            .filter(|feed_id| is_valid_feed_id(feed_id))
+            .map(|feed_id| feed_id.to_string())
+            .collect();
+        feed_ids.sort();
+        Ok(feed_ids)
+    }
+
+    fn load_feed(&self, feed_id: &str) -> StorageResult<Channel> {
+        read_channel(&self.fs, &feed_path(feed_id))
+    }
+
+    fn list_items(&self, feed_id: &str) -> StorageResult<Vec<Item>> {
+        Ok(self.load_feed(feed_id)?.items().to_vec())
+    }
+
+    fn get_item(&self, feed_id: &str, item_id: &str) -> StorageResult<Option<Item>> {
+        Ok(self
+            .list_items(feed_id)?
+            .into_iter()
+            .find(|item| item.guid().map(|g| g.value() == item_id).unwrap_or(false)))
+    }
+
+    fn save_feed(&self, feed_id: &str, channel: &Channel) -> StorageResult<()> {
+        write_channel(channel, Some(&feed_path(feed_id)), &self.fs)
+    }
+
+    fn update_feed(&self, feed_id: &str, channel: &Channel) -> StorageResult<()> {
+        self.save_feed(feed_id, channel)
+    }
+
+    fn rename_feed(&self, feed_id: &str, new_feed_id: &str) -> StorageResult<()> {
+        let channel = self.load_feed(feed_id)?;
+        self.save_feed(new_feed_id, &channel)?;
+        self.fs.remove(&feed_path(feed_id))?;
+        Ok(())
+    }
+
+    fn delete_feed(&self, feed_id: &str) -> StorageResult<()> {
+        self.fs.remove(&feed_path(feed_id))?;
+        Ok(())
+    }
+
+    fn quarantine_feed(&self, feed_id: &str, name: &str) -> StorageResult<()> {
+        // Without the .xml extension the file is no longer listed as a feed
+        self.fs
+            .rename(&feed_path(feed_id), &format!("{}/{}", FEED_DIRECTORY, name))?;
+        Ok(())
+    }
+
+    fn insert_item(&self, feed_id: &str, channel: &Channel, _item: &Item) -> StorageResult<()> {
+        self.save_feed(feed_id, channel)
+    }
+
+    fn update_item(&self, feed_id: &str, channel: &Channel, _item: &Item) -> StorageResult<()> {
+        self.save_feed(feed_id, channel)
+    }
+
+    fn delete_items(
+        &self,
+        feed_id: &str,
+        channel: &Channel,
+        _item_ids: &[String],
+    ) -> StorageResult<()> {
+        self.save_feed(feed_id, channel)
+    }
+
+    fn check(&self) -> StorageResult<()> {
+        // Every feed must still parse, and the directory must take new files
+        for feed_id in self.list_feeds()? {
+            self.load_feed(&feed_id).map_err(|e| {
+                StorageError::Invalid(format!("Feed '{}' can't be read: {}", feed_id, e))
+            })?;
+        }
+        let probe = format!("{}/.health-check", FEED_DIRECTORY);
+        self.fs.write_atomic(&probe, "ok")?;
+        self.fs.remove(&probe)?;
+        Ok(())
+    }
+}
+
+// Embedded SQLite database, channel metadata and items are stored as JSON rows
+pub struct SqliteStorage {
+    connection: Mutex<Connection>,
+}
+
+impl SqliteStorage {
+    pub fn open(path: &str) -> StorageResult<Self> {
+        let connection = Connection::open(path)?;
+        connection.execute_batch(
+            "PRAGMA foreign_keys = ON;
+            PRAGMA journal_mode = WAL;
+            CREATE TABLE IF NOT EXISTS feeds (
+                id TEXT PRIMARY KEY,
+                channel TEXT NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS items (
+                seq INTEGER PRIMARY KEY AUTOINCREMENT,
+                feed_id TEXT NOT NULL REFERENCES feeds(id) ON DELETE CASCADE ON UPDATE CASCADE,
+                guid TEXT NOT NULL,
+                item TEXT NOT NULL,
+                UNIQUE (feed_id, guid)
+            );",
+        )?;
+        Ok(Self {
+            connection: Mutex::new(connection),
+        })
+    }
+
+    // Channel metadata is stored without its items, which live in their own rows
+    fn channel_json(channel: &Channel) -> StorageResult<String> {
+        let mut metadata = channel.clone();
+        metadata.set_items(Vec::new());
+        Ok(serde_json::to_string(&metadata)?)
+    }
+
+    fn item_guid(item: &Item) -> StorageResult<String> {
+        item.guid()
+            .map(|g| g.value().to_string())
+            .ok_or_else(|| StorageError::Invalid("Item has no GUID".to_string()))
+    }
+
+    fn upsert_feed(connection: &Connection, feed_id: &str, channel: &Channel) -> StorageResult<()> {
+        connection.execute(
+            "INSERT INTO feeds (id, channel) VALUES (?1, ?2)
+            ON CONFLICT (id) DO UPDATE SET channel = excluded.channel",
+            params![feed_id, Self::channel_json(channel)?],
+        )?;
+        Ok(())
+    }
+}
+
+impl Storage for SqliteStorage {
+    fn list_feeds(&self) -> StorageResult<Vec<String>> {
+        let connection = self.connection.lock().unwrap();
+        let mut statement = connection.prepare("SELECT id FROM feeds ORDER BY id")?;
+        let feed_ids = statement
+            .query_map([], |row| row.get(0))?
+            .collect::<Result<Vec<String>, _>>()?;
+        // Quarantined feeds keep their rows under an id that isn't a valid feed id
+        Ok(feed_ids
+            .into_iter()
+            .filter(|feed_id| is_valid_feed_id(feed_id))
+            .collect())
+    }
+
+    fn load_feed(&self, feed_id: &str) -> StorageResult<Channel> {
+        let json: String = {
+            let connection = self.connection.lock().unwrap();
+            connection.query_row(
+                "SELECT channel FROM feeds WHERE id = ?1",
+                params![feed_id],
+                |row| row.get(0),
+            )?
+        };
+        let mut channel: Channel = serde_json::from_str(&json)?;
+        channel.set_items(self.list_items(feed_id)?);
+        Ok(channel)
+    }
+
+    fn list_items(&self, feed_id: &str) -> StorageResult<Vec<Item>> {
+        let connection = self.connection.lock().unwrap();
+        let mut statement =
+            connection.prepare("SELECT item FROM items WHERE feed_id = ?1 ORDER BY seq DESC")?;
+        let rows = statement
+            .query_map(params![feed_id], |row| row.get::<_, String>(0))?
+            .collect::<Result<Vec<String>, _>>()?;
+        rows.iter()
+            .map(|json| Ok(serde_json::from_str(json)?))
+            .collect()
+    }
+
+    fn get_item(&self, feed_id: &str, item_id: &str) -> StorageResult<Option<Item>> {
+        let connection = self.connection.lock().unwrap();
+        let json: Option<String> = connection
+            .query_row(
+                "SELECT item FROM items WHERE feed_id = ?1 AND guid = ?2",
+                params![feed_id, item_id],
+                |row| row.get(0),
+            )
+            .optional()?;
+        match json {
+            Some(json) => Ok(Some(serde_json::from_str(&json)?)),
+            None => Ok(None),
+        }
+    }
+
+    fn save_feed(&self, feed_id: &str, channel: &Channel) -> StorageResult<()> {
+        let mut connection = self.connection.lock().unwrap();
+        let transaction = connection.transaction()?;
+        Self::upsert_feed(&transaction, feed_id, channel)?;
+        transaction.execute("DELETE FROM items WHERE feed_id = ?1", params![feed_id])?;
+        // Items are listed newest first, so insert the oldest first to keep the order
+        for item in channel.items().iter().rev() {
+            transaction.execute(
+                "INSERT INTO items (feed_id, guid, item) VALUES (?1, ?2, ?3)",
+                params![
+                    feed_id,
+                    Self::item_guid(item)?,
+                    serde_json::to_string(item)?
+                ],
+            )?;
+        }
+        transaction.commit()?;
+        Ok(())
+    }
+
+    fn update_feed(&self, feed_id: &str, channel: &Channel) -> StorageResult<()> {
+        let connection = self.connection.lock().unwrap();
+        Self::upsert_feed(&connection, feed_id, channel)
+    }
+
+    fn rename_feed(&self, feed_id: &str, new_feed_id: &str) -> StorageResult<()> {
+        let connection = self.connection.lock().unwrap();
+        connection.execute(
+            "UPDATE feeds SET id = ?2 WHERE id = ?1",
+            params![feed_id, new_feed_id],
+        )?;
+        Ok(())
+    }
+
+    fn delete_feed(&self, feed_id: &str) -> StorageResult<()> {
+        let connection = self.connection.lock().unwrap();
+        connection.execute("DELETE FROM feeds WHERE id = ?1", params![feed_id])?;
+        Ok(())
+    }
+
+    fn quarantine_feed(&self, feed_id: &str, name: &str) -> StorageResult<()> {
+        self.rename_feed(feed_id, name)
+    }
+
+    fn insert_item(&self, feed_id: &str, channel: &Channel, item: &Item) -> StorageResult<()> {
+        let mut connection = self.connection.lock().unwrap();
+        let transaction = connection.transaction()?;
+        Self::upsert_feed(&transaction, feed_id, channel)?;
+        transaction.execute(
+            "INSERT INTO items (feed_id, guid, item) VALUES (?1, ?2, ?3)",
+            params![
+                feed_id,
+                Self::item_guid(item)?,
+                serde_json::to_string(item)?
+            ],
+        )?;
+        transaction.commit()?;
+        Ok(())
+    }
+
+    fn update_item(&self, feed_id: &str, channel: &Channel, item: &Item) -> StorageResult<()> {
+        let mut connection = self.connection.lock().unwrap();
+        let transaction = connection.transaction()?;
+        Self::upsert_feed(&transaction, feed_id, channel)?;
+        transaction.execute(
+            "UPDATE items SET item = ?3 WHERE feed_id = ?1 AND guid = ?2",
+            params![
+                feed_id,
+                Self::item_guid(item)?,
+                serde_json::to_string(item)?
+            ],
+        )?;
+        transaction.commit()?;
+        Ok(())
+    }
+
+    fn delete_items(
+        &self,
+        feed_id: &str,
+        channel: &Channel,
+        item_ids: &[String],
+    ) -> StorageResult<()> {
+        let mut connection = self.connection.lock().unwrap();
+        let transaction = connection.transaction()?;
+        Self::upsert_feed(&transaction, feed_id, channel)?;
+        for item_id in item_ids {
+            transaction.execute(
+                "DELETE FROM items WHERE feed_id = ?1 AND guid = ?2",
+                params![feed_id, item_id],
+            )?;
+        }
+        transaction.commit()?;
+        Ok(())
+    }
+
+    fn check(&self) -> StorageResult<()> {
+        let connection = self.connection.lock().unwrap();
+        connection.query_row("SELECT 1", [], |_| Ok(()))?;
+        Ok(())
+    }
+}
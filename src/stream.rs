@@ -0,0 +1,84 @@
+use crate::audit::AuditEntry;
+use crate::webhooks::{WebhookPayload, event_name};
+use axum::response::sse::{Event, KeepAlive, Sse};
+use futures_util::stream::{self, Stream};
+use std::convert::Infallible;
+use tokio::sync::broadcast::{self, Receiver, Sender, error::RecvError};
+
+// How many changes a slow client can fall behind before it misses some
+const STREAM_CAPACITY: usize = 256;
+
+// A change as sent to stream clients, named and shaped like the webhook notifications
+#[derive(Clone, Debug)]
+pub struct StreamEvent {
+    pub event: &'static str,
+    pub feed_id: String,
+    pub data: String,
+}
+
+// Saved changes are broadcast to every connected client, nothing is kept for clients that
+// connect later
+pub struct EventStream {
+    sender: Sender<StreamEvent>,
+}
+
+impl Default for EventStream {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl EventStream {
+    pub fn new() -> Self {
+        let (sender, _) = broadcast::channel(STREAM_CAPACITY);
+        Self { sender }
+    }
+
+    pub fn publish(&self, entries: &[AuditEntry]) {
+        if self.sender.receiver_count() == 0 {
+            return;
+        }
+        for entry in entries {
+            let event = event_name(entry);
+            let data = serde_json::to_string(&WebhookPayload::new(event, entry))
+                .expect("Failed to serialize stream event");
+            // Only fails when every client has disconnected since the check above
+            let _ = self.sender.send(StreamEvent {
+                event,
+                feed_id: entry.feed_id.clone(),
+                data,
+            });
+        }
+    }
+
+    pub fn subscribe(&self) -> Receiver<StreamEvent> {
+        self.sender.subscribe()
+    }
+
+    // Server-Sent Events response with the changes to one feed, or every feed. Clients that
+    // fall too far behind get a `stream.lagged` event with the number of changes they missed
+    pub fn sse(
+        &self,
+        feed_id: Option<String>,
+    ) -> Sse<impl Stream<Item = Result<Event, Infallible>> + use<>> {
+        let events = stream::unfold(self.subscribe(), move |mut receiver| {
+            let feed_id = feed_id.clone();
+            async move {
+                loop {
+                    let event = match receiver.recv().await {
+                        Ok(change) if feed_id.as_ref().is_none_or(|id| *id == change.feed_id) => {
+                            Event::default().event(change.event).data(change.data)
+                        }
+                        Ok(_) => continue,
+                        Err(RecvError::Lagged(missed)) => Event::default()
+                            .event("stream.lagged")
+                            .data(missed.to_string()),
+                        Err(RecvError::Closed) => return None,
+                    };
+                    return Some((Ok(event), receiver));
+                }
+            }
+        });
+        Sse::new(events).keep_alive(KeepAlive::default())
+    }
+}
@@ -1,48 +1,396 @@
+use crate::attachments::{public_base_url, read_multipart};
+use crate::audit::{AuditActor, AuditEntry, AuditFilter};
+use crate::auth::hash_token;
+use crate::backups::Backup;
+use crate::cache::{ContentEncoding, RenderedFeed, feed_cache_control};
 use crate::common::*;
+use crate::formats::*;
+use crate::session::*;
+use crate::storage::StorageError;
+use crate::websub::{HubMode, HubRequest, feed_links, is_local_topic, topic_feed};
 use askama::Template;
 use axum::{
     Json,
     body::Body,
-    extract::{Form, Path, State},
-    http::{Response, StatusCode},
+    extract::{ConnectInfo, Extension, Form, Multipart, Path, Query, State},
+    http::{HeaderMap, Response, StatusCode, Uri, header},
     response::{Html, IntoResponse, Redirect},
 };
-use log::info;
-use rss::{Channel, Item};
+use log::{error, info, warn};
+use rss::{Category, Channel, Enclosure, Item, Source};
 use serde::Deserialize;
-use std::{fs, path::Path as StdPath};
+use std::{fs, net::SocketAddr, sync::Arc};
 
 // Templates
+#[derive(Template)]
+#[template(path = "feeds.html")]
+struct FeedsTemplate {
+    feeds: Vec<(String, Channel)>,
+    username: String,
+    csrf_token: String,
+}
+
 #[derive(Template)]
 #[template(path = "index.html")]
 struct IndexTemplate {
     channel: Channel,
+    feed_id: String,
+    items: Vec<Item>,
+    scheduled: Vec<Item>,
+    trash: Vec<Item>,
+    missing_attachments: Vec<String>,
+    username: String,
+    csrf_token: String,
+}
+
+#[derive(Template)]
+#[template(path = "edit_feed.html")]
+struct EditFeedTemplate {
+    channel: Channel,
+    feed_id: String,
+    is_default_feed: bool,
+    max_item_age_seconds: Option<u64>,
+    max_items: Option<usize>,
+    max_served_items: Option<usize>,
+    csrf_token: String,
+}
+
+#[derive(Template)]
+#[template(path = "backups.html")]
+struct BackupsTemplate {
+    feed_id: String,
+    backups: Vec<Backup>,
+    retention: usize,
+    csrf_token: String,
+}
+
+#[derive(Template)]
+#[template(path = "audit.html")]
+struct AuditTemplate {
+    entries: Vec<AuditRow>,
+    total: usize,
+    feed_id: Option<String>,
+}
+
+// An audit entry with the item before and after the change rendered for display
+struct AuditRow {
+    entry: AuditEntry,
+    before: Option<String>,
+    after: Option<String>,
+}
+
+impl From<AuditEntry> for AuditRow {
+    fn from(entry: AuditEntry) -> Self {
+        let render = |item: &Option<Item>| {
+            item.as_ref()
+                .and_then(|item| serde_json::to_string_pretty(item).ok())
+        };
+        AuditRow {
+            before: render(&entry.before),
+            after: render(&entry.after),
+            entry,
+        }
+    }
 }
 
 #[derive(Template)]
 #[template(path = "add_item.html")]
-struct AddItemTemplate {}
+struct AddItemTemplate {
+    feed_id: String,
+    csrf_token: String,
+}
 
 #[derive(Template)]
 #[template(path = "edit_item.html")]
 struct EditItemTemplate {
     item: Item,
     item_id: String,
+    feed_id: String,
+    // Expiry and publish times in the `datetime-local` input format
+    expires_at: String,
+    never_expires: bool,
+    publish_at: String,
+    // Comma separated, as edited in the form
+    categories: String,
+    csrf_token: String,
+}
+
+#[derive(Template)]
+#[template(path = "login.html")]
+struct LoginTemplate {
+    next: String,
+    error: Option<String>,
+    // Shown instead of the login form until the first account exists
+    setup: bool,
+    // Setup asks for SETUP_TOKEN when it is set
+    setup_token_required: bool,
+}
+
+#[derive(Template)]
+#[template(path = "users.html")]
+struct UsersTemplate {
+    users: Vec<User>,
+    username: String,
+    csrf_token: String,
+    error: Option<String>,
 }
 
 // Form data structures
 #[derive(Deserialize)]
 pub struct NewItemForm {
+    #[serde(flatten)]
+    item: ItemForm,
+    expires_at: Option<String>,
+    never_expires: Option<String>,
+    publish_at: Option<String>,
+    #[serde(default)]
+    csrf_token: String,
+}
+
+#[derive(Deserialize)]
+pub struct EditItemForm {
+    #[serde(flatten)]
+    item: ItemForm,
+    expires_at: Option<String>,
+    never_expires: Option<String>,
+    publish_at: Option<String>,
+    #[serde(default)]
+    csrf_token: String,
+}
+
+// Item content fields shared by the add and edit forms
+#[derive(Deserialize)]
+pub struct ItemForm {
     title: String,
     description: Option<String>,
     link: Option<String>,
+    author: Option<String>,
+    comments: Option<String>,
+    // Comma separated category names
+    categories: Option<String>,
+    enclosure_url: Option<String>,
+    enclosure_length: Option<String>,
+    enclosure_type: Option<String>,
+    source_url: Option<String>,
+    source_title: Option<String>,
+}
+
+impl ItemForm {
+    fn into_fields(self) -> Result<ItemFields, StatusCode> {
+        if self.title.trim().is_empty() {
+            return Err(StatusCode::BAD_REQUEST);
+        }
+        let non_empty = |value: Option<String>| {
+            value
+                .map(|s| s.trim().to_string())
+                .filter(|s| !s.is_empty())
+        };
+
+        let enclosure = match non_empty(self.enclosure_url) {
+            Some(url) => Some(Enclosure {
+                url,
+                length: match non_empty(self.enclosure_length) {
+                    Some(length) => length
+                        .parse::<u64>()
+                        .map_err(|_| StatusCode::BAD_REQUEST)?
+                        .to_string(),
+                    None => "0".to_string(),
+                },
+                mime_type: non_empty(self.enclosure_type)
+                    .unwrap_or_else(|| "application/octet-stream".to_string()),
+            }),
+            None => None,
+        };
+
+        Ok(ItemFields {
+            title: self.title,
+            description: self.description,
+            link: self.link,
+            author: self.author,
+            comments: self.comments,
+            categories: self
+                .categories
+                .unwrap_or_default()
+                .split(',')
+                .map(|name| Category {
+                    name: name.trim().to_string(),
+                    domain: None,
+                })
+                .collect(),
+            enclosure,
+            source: non_empty(self.source_url).map(|url| Source {
+                url,
+                title: non_empty(self.source_title),
+            }),
+        })
+    }
 }
 
 #[derive(Deserialize)]
-pub struct EditItemForm {
+pub struct FeedForm {
+    id: String,
     title: String,
+    link: String,
     description: Option<String>,
-    link: Option<String>,
+    max_item_age_seconds: Option<String>,
+    max_items: Option<String>,
+    max_served_items: Option<String>,
+    #[serde(default)]
+    csrf_token: String,
+}
+
+// Subscription request sent to the WebSub hub
+#[derive(Deserialize)]
+pub struct HubForm {
+    #[serde(rename = "hub.mode")]
+    mode: Option<String>,
+    #[serde(rename = "hub.callback")]
+    callback: Option<String>,
+    #[serde(rename = "hub.topic")]
+    topic: Option<String>,
+    #[serde(rename = "hub.lease_seconds")]
+    lease_seconds: Option<String>,
+    #[serde(rename = "hub.secret")]
+    secret: Option<String>,
+}
+
+impl HubForm {
+    // Topics must be feeds served from `base_url`, this server's public origin
+    fn into_request(self, state: &AppState, base_url: &str) -> Result<HubRequest, String> {
+        let mode = match self.mode.as_deref() {
+            Some("subscribe") => HubMode::Subscribe,
+            Some("unsubscribe") => HubMode::Unsubscribe,
+            _ => return Err("hub.mode must be subscribe or unsubscribe".to_string()),
+        };
+        let callback = self
+            .callback
+            .filter(|callback| {
+                reqwest::Url::parse(callback)
+                    .is_ok_and(|url| matches!(url.scheme(), "http" | "https"))
+            })
+            .ok_or("hub.callback must be an http or https URL")?;
+        let topic = self
+            .topic
+            .filter(|topic| {
+                is_local_topic(topic, base_url)
+                    && topic_feed(topic).is_some_and(|(feed_id, _)| {
+                        state.feeds.lock().unwrap().contains_key(&feed_id)
+                    })
+            })
+            .ok_or("hub.topic must be the URL of a feed served here")?;
+        let lease_seconds = match self.lease_seconds.filter(|s| !s.trim().is_empty()) {
+            Some(value) => Some(
+                value
+                    .trim()
+                    .parse::<u64>()
+                    .map_err(|_| "hub.lease_seconds must be a whole number of seconds")?,
+            ),
+            None => None,
+        };
+        // Secrets are limited to 200 bytes by the WebSub spec
+        let secret = match self.secret.filter(|s| !s.is_empty()) {
+            Some(secret) if secret.len() >= 200 => {
+                return Err("hub.secret must be shorter than 200 bytes".to_string());
+            }
+            secret => secret,
+        };
+
+        Ok(HubRequest {
+            mode,
+            callback,
+            topic,
+            lease_seconds: state.websub.lease_seconds(lease_seconds),
+            secret,
+        })
+    }
+}
+
+// Forms without any other fields, like the delete buttons
+#[derive(Deserialize)]
+pub struct CsrfForm {
+    #[serde(default)]
+    csrf_token: String,
+}
+
+#[derive(Deserialize)]
+pub struct LoginForm {
+    username: String,
+    password: String,
+    next: Option<String>,
+    // Only sent by the setup form
+    setup_token: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct LoginQuery {
+    next: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct NewUserForm {
+    username: String,
+    password: String,
+    #[serde(default)]
+    csrf_token: String,
+}
+
+fn form_publish_at(
+    publish_at: Option<&str>,
+) -> Result<Option<chrono::DateTime<chrono::Utc>>, StatusCode> {
+    match publish_at.map(str::trim) {
+        Some(value) if !value.is_empty() => parse_datetime(value)
+            .map(Some)
+            .map_err(|_| StatusCode::BAD_REQUEST),
+        _ => Ok(None),
+    }
+}
+
+// The "never expires" checkbox wins over an expiry time
+fn form_expiry(
+    expires_at: Option<&str>,
+    never_expires: bool,
+) -> Result<Option<ItemExpiry>, StatusCode> {
+    if never_expires {
+        return Ok(Some(ItemExpiry::Never));
+    }
+    match expires_at.map(str::trim) {
+        Some(value) if !value.is_empty() => ItemExpiry::parse(value)
+            .map(Some)
+            .map_err(|_| StatusCode::BAD_REQUEST),
+        _ => Ok(None),
+    }
+}
+
+impl FeedForm {
+    fn into_settings(self) -> Result<FeedSettings, StatusCode> {
+        let id = self.id.trim().to_string();
+        if !is_valid_feed_id(&id) || self.title.trim().is_empty() || self.link.trim().is_empty() {
+            return Err(StatusCode::BAD_REQUEST);
+        }
+        let max_item_age_seconds = optional_number(self.max_item_age_seconds.as_deref())?;
+        let max_items = optional_number(self.max_items.as_deref())?;
+        let max_served_items = optional_number(self.max_served_items.as_deref())?;
+
+        Ok(FeedSettings {
+            id,
+            title: self.title,
+            link: self.link,
+            description: self.description.unwrap_or_default(),
+            max_item_age_seconds,
+            max_items,
+            max_served_items,
+        })
+    }
+}
+
+// A number field that may be left empty
+fn optional_number<T: std::str::FromStr>(value: Option<&str>) -> Result<Option<T>, StatusCode> {
+    match value.map(str::trim) {
+        Some(value) if !value.is_empty() => {
+            Some(value.parse().map_err(|_| StatusCode::BAD_REQUEST)).transpose()
+        }
+        _ => Ok(None),
+    }
 }
 
 // Health Check
@@ -59,9 +407,22 @@ struct CheckResult {
     message: Option<String>,
 }
 
-pub async fn index(State(state): State<AppState>) -> Result<Html<String>, StatusCode> {
-    let channel = state.channel.lock().unwrap().clone();
-    let template = IndexTemplate { channel };
+pub async fn feeds_index(
+    State(state): State<AppState>,
+    Extension(session): Extension<Session>,
+) -> Result<Html<String>, StatusCode> {
+    let feeds = state
+        .feeds
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|(id, channel)| (id.clone(), channel.clone()))
+        .collect();
+    let template = FeedsTemplate {
+        feeds,
+        username: session.username,
+        csrf_token: session.csrf_token,
+    };
 
     match template.render() {
         Ok(html) => Ok(Html(html)),
@@ -69,48 +430,422 @@ pub async fn index(State(state): State<AppState>) -> Result<Html<String>, Status
     }
 }
 
-pub async fn serve_file() -> impl IntoResponse {
-    let path = StdPath::new("./feed/feed.xml");
+pub async fn index(
+    State(state): State<AppState>,
+    Extension(session): Extension<Session>,
+    Path(feed_id): Path<String>,
+) -> Result<Html<String>, StatusCode> {
+    let channel = state
+        .feeds
+        .lock()
+        .unwrap()
+        .get(&feed_id)
+        .ok_or(StatusCode::NOT_FOUND)?
+        .clone();
+    let (trash, items): (Vec<Item>, Vec<Item>) =
+        channel.items().iter().cloned().partition(is_item_deleted);
+    let (scheduled, items) = items.into_iter().partition(is_item_scheduled);
+    let missing_attachments = state.attachments.missing(channel.items());
+    let template = IndexTemplate {
+        channel,
+        feed_id,
+        items,
+        scheduled,
+        trash,
+        missing_attachments,
+        username: session.username,
+        csrf_token: session.csrf_token,
+    };
+
+    match template.render() {
+        Ok(html) => Ok(Html(html)),
+        Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR),
+    }
+}
+
+pub async fn serve_file(
+    State(state): State<AppState>,
+    uri: Uri,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    // `/feed.{ext}` serves the default feed, `/feeds/{id}.{ext}` serves any other
+    let Some((feed_id, format)) = parse_feed_path(uri.path()) else {
+        return (StatusCode::NOT_FOUND, "Feed not found".to_string()).into_response();
+    };
+    let format = match format {
+        FeedFormat::Rss => FeedFormat::negotiate(
+            headers
+                .get(header::ACCEPT)
+                .and_then(|value| value.to_str().ok()),
+        ),
+        format => format,
+    };
+
+    let base_url = public_base_url(&headers);
+    let links = feed_links(&base_url, format!("{}{}", base_url, uri.path()));
+    let rendered = {
+        let feeds = state.feeds.lock().unwrap();
+        let Some(channel) = feeds.get(feed_id) else {
+            return (StatusCode::NOT_FOUND, "Feed not found".to_string()).into_response();
+        };
+        state
+            .feed_cache
+            .get_or_render(feed_id, format, &links.self_url, || {
+                let now = chrono::Utc::now();
+                // Items that have expired since the last build changed the served feed too
+                let last_modified = channel
+                    .items()
+                    .iter()
+                    .filter_map(|item| match item_expiry(item) {
+                        Some(ItemExpiry::At(expires_at)) if expires_at <= now => Some(expires_at),
+                        _ => None,
+                    })
+                    .chain(
+                        channel
+                            .last_build_date()
+                            .and_then(|date| parse_datetime(date).ok()),
+                    )
+                    .max();
+                let served = served_channel(channel);
+                RenderedFeed::new(
+                    render_feed(feed_id, &served, format, Some(&links)),
+                    last_modified,
+                )
+                .stale_at(next_item_expiry(&served))
+            })
+    };
+
+    let encoding = ContentEncoding::negotiate(
+        headers
+            .get(header::ACCEPT_ENCODING)
+            .and_then(|value| value.to_str().ok()),
+    );
+    // Compressed bodies aren't byte for byte the same, so their ETag is weak
+    let etag = match encoding {
+        ContentEncoding::Identity => rendered.etag.clone(),
+        _ => format!("W/{}", rendered.etag),
+    };
+    let mut response = Response::builder()
+        .header("Vary", "Accept, Accept-Encoding")
+        .header(header::ETAG, etag)
+        .header(
+            "Link",
+            format!(
+                "<{}>; rel=\"hub\", <{}>; rel=\"self\"",
+                links.hub_url, links.self_url
+            ),
+        );
+    if let Some(last_modified) = rendered.last_modified {
+        response = response.header(
+            header::LAST_MODIFIED,
+            last_modified
+                .format("%a, %d %b %Y %H:%M:%S GMT")
+                .to_string(),
+        );
+    }
+    if let Some(cache_control) = feed_cache_control() {
+        response = response.header(header::CACHE_CONTROL, cache_control);
+    }
+
+    if rendered.is_not_modified(&headers) {
+        return response
+            .status(StatusCode::NOT_MODIFIED)
+            .body(Body::empty())
+            .unwrap();
+    }
+    if encoding != ContentEncoding::Identity {
+        response = response.header(header::CONTENT_ENCODING, encoding.as_str());
+    }
+    response
+        .header("Content-Type", format.content_type())
+        .body(Body::from(rendered.encoded(encoding)))
+        .unwrap()
+}
+
+// WebSub hub, requests are accepted straight away and verified with the subscriber afterwards
+pub async fn websub_hub(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Form(form): Form<HubForm>,
+) -> impl IntoResponse {
+    let request = match form.into_request(&state, &public_base_url(&headers)) {
+        Ok(request) => request,
+        Err(message) => return (StatusCode::BAD_REQUEST, message).into_response(),
+    };
+    let Some(permit) = state.websub.try_reserve_verification() else {
+        return (
+            StatusCode::SERVICE_UNAVAILABLE,
+            [(header::RETRY_AFTER, "30")],
+            "Too many subscription requests are being verified, try again later",
+        )
+            .into_response();
+    };
 
-    match fs::read(path) {
-        Ok(contents) => {
-            let body = Body::from(contents);
-            Response::builder()
-                .header("Content-Type", "application/xml")
-                .body(body)
-                .unwrap()
+    let hub = Arc::clone(&state.websub);
+    tokio::spawn(async move {
+        let _permit = permit;
+        let (mode, callback) = (request.mode, request.callback.clone());
+        if let Err(e) = hub.verify(request).await {
+            warn!(
+                "WebSub {} of {} not verified: {}",
+                mode.as_str(),
+                callback,
+                e
+            );
         }
-        Err(_) => (
-            StatusCode::NOT_FOUND,
-            "File not found or couldn't be read".to_string(),
+    });
+    StatusCode::ACCEPTED.into_response()
+}
+
+pub async fn serve_attachment(
+    State(state): State<AppState>,
+    Path(file_name): Path<String>,
+) -> impl IntoResponse {
+    let Some(contents) = state
+        .attachments
+        .path(&file_name)
+        .and_then(|path| fs::read(path).ok())
+    else {
+        return (StatusCode::NOT_FOUND, "Attachment not found".to_string()).into_response();
+    };
+
+    // Uploads never run as active content, even when served inline
+    Response::builder()
+        .header(
+            "Content-Type",
+            mime_guess::from_path(&file_name)
+                .first_or_octet_stream()
+                .as_ref(),
         )
-            .into_response(),
+        .header("X-Content-Type-Options", "nosniff")
+        .header("Content-Security-Policy", "default-src 'none'; sandbox")
+        .header("Cache-Control", "public, max-age=31536000, immutable")
+        .body(Body::from(contents))
+        .unwrap()
+}
+
+// Storage failures are logged and shown as a plain 500 page
+fn storage_failure(e: StorageError) -> StatusCode {
+    error!("Storage error handling web request: {}", e);
+    StatusCode::INTERNAL_SERVER_ERROR
+}
+
+pub async fn web_add_feed(
+    State(state): State<AppState>,
+    Extension(session): Extension<Session>,
+    Form(form): Form<FeedForm>,
+) -> Result<Redirect, StatusCode> {
+    session.verify_csrf(&form.csrf_token)?;
+    let settings = form.into_settings()?;
+    let feed_id = settings.id.clone();
+
+    if add_feed(axum::extract::State(state), settings)
+        .map_err(storage_failure)?
+        .is_none()
+    {
+        return Err(StatusCode::CONFLICT);
+    }
+
+    Ok(Redirect::to(&format!("/manage/{}", feed_id)))
+}
+
+pub async fn backups_index(
+    State(state): State<AppState>,
+    Extension(session): Extension<Session>,
+    Path(feed_id): Path<String>,
+) -> Result<Html<String>, StatusCode> {
+    // Snapshots of deleted feeds can still be restored, so the feed doesn't need to exist
+    if !is_valid_feed_id(&feed_id) {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    let backups = state.backups.list(&feed_id).map_err(|e| {
+        error!("Failed to list backups of '{}': {}", feed_id, e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    let template = BackupsTemplate {
+        feed_id,
+        backups,
+        retention: state.backups.retention(),
+        csrf_token: session.csrf_token,
+    };
+    match template.render() {
+        Ok(html) => Ok(Html(html)),
+        Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR),
+    }
+}
+
+pub async fn web_restore_backup(
+    State(state): State<AppState>,
+    Extension(session): Extension<Session>,
+    Path((feed_id, backup_id)): Path<(String, String)>,
+    Form(form): Form<CsrfForm>,
+) -> Result<Redirect, StatusCode> {
+    session.verify_csrf(&form.csrf_token)?;
+    restore_feed(&state, &feed_id, &backup_id, &AuditActor::web(&session))
+        .map_err(storage_failure)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    Ok(Redirect::to(&format!("/manage/{}", feed_id)))
+}
+
+pub async fn edit_feed_form(
+    State(state): State<AppState>,
+    Extension(session): Extension<Session>,
+    Path(feed_id): Path<String>,
+) -> Result<Html<String>, StatusCode> {
+    let channel = state
+        .feeds
+        .lock()
+        .unwrap()
+        .get(&feed_id)
+        .ok_or(StatusCode::NOT_FOUND)?
+        .clone();
+
+    let max_item_age_seconds = feed_max_item_age(&channel);
+    let max_items = feed_max_items(&channel);
+    let max_served_items = feed_max_served_items(&channel);
+    let template = EditFeedTemplate {
+        channel,
+        is_default_feed: feed_id == DEFAULT_FEED,
+        feed_id,
+        max_item_age_seconds,
+        max_items,
+        max_served_items,
+        csrf_token: session.csrf_token,
+    };
+    match template.render() {
+        Ok(html) => Ok(Html(html)),
+        Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR),
+    }
+}
+
+pub async fn web_edit_feed(
+    State(state): State<AppState>,
+    Extension(session): Extension<Session>,
+    Path(feed_id): Path<String>,
+    Form(form): Form<FeedForm>,
+) -> Result<Redirect, StatusCode> {
+    session.verify_csrf(&form.csrf_token)?;
+    let settings = form.into_settings()?;
+    let new_feed_id = settings.id.clone();
+
+    if edit_feed(
+        axum::extract::State(state),
+        axum::extract::Path(feed_id),
+        settings,
+    )
+    .map_err(storage_failure)?
+    .is_none()
+    {
+        return Err(StatusCode::CONFLICT);
+    }
+    info!("Feed edited successfully: {}", new_feed_id);
+
+    Ok(Redirect::to(&format!("/manage/{}", new_feed_id)))
+}
+
+pub async fn web_delete_feed(
+    State(state): State<AppState>,
+    Extension(session): Extension<Session>,
+    Path(feed_id): Path<String>,
+    Form(form): Form<CsrfForm>,
+) -> Result<Redirect, StatusCode> {
+    session.verify_csrf(&form.csrf_token)?;
+    if feed_id == DEFAULT_FEED {
+        return Err(StatusCode::CONFLICT);
+    }
+    if delete_feed(axum::extract::State(state), axum::extract::Path(feed_id))
+        .map_err(storage_failure)?
+        .is_none()
+    {
+        return Err(StatusCode::NOT_FOUND);
     }
+
+    Ok(Redirect::to("/"))
 }
 
-pub async fn add_item_form() -> Result<Html<String>, StatusCode> {
-    let template = AddItemTemplate {};
+pub async fn add_item_form(
+    State(state): State<AppState>,
+    Extension(session): Extension<Session>,
+    Path(feed_id): Path<String>,
+) -> Result<Html<String>, StatusCode> {
+    if !state.feeds.lock().unwrap().contains_key(&feed_id) {
+        return Err(StatusCode::NOT_FOUND);
+    }
+
+    let template = AddItemTemplate {
+        feed_id,
+        csrf_token: session.csrf_token,
+    };
     match template.render() {
         Ok(html) => Ok(Html(html)),
         Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR),
     }
 }
 
+// The single-feed pages from before feeds could be managed separately, kept so bookmarks reach
+// the same pages of the default feed
+pub async fn legacy_add_item() -> Redirect {
+    Redirect::permanent(&format!("/manage/{}/add", DEFAULT_FEED))
+}
+
+pub async fn legacy_edit_item(Path(item_id): Path<String>) -> Redirect {
+    Redirect::permanent(&format!("/manage/{}/edit/{}", DEFAULT_FEED, item_id))
+}
+
+// Forms posted to the old routes lack the CSRF token and multipart body the new ones need, so
+// they are refused with a pointer to the new pages rather than redirected
+pub async fn legacy_form_post() -> (StatusCode, String) {
+    (
+        StatusCode::GONE,
+        format!(
+            "This form has moved, reload it from /manage/{} and submit it again",
+            DEFAULT_FEED
+        ),
+    )
+}
+
 pub async fn edit_item_form(
     State(state): State<AppState>,
-    Path(item_id): Path<String>,
+    Extension(session): Extension<Session>,
+    Path((feed_id, item_id)): Path<(String, String)>,
 ) -> Result<Html<String>, StatusCode> {
-    let channel = state.channel.lock().unwrap();
+    let feeds = state.feeds.lock().unwrap();
+    let channel = feeds.get(&feed_id).ok_or(StatusCode::NOT_FOUND)?;
 
     // Find the item with the matching GUID
     let item = channel
         .items()
         .iter()
-        .find(|item| item.guid().map(|g| g.value() == item_id).unwrap_or(false))
+        .find(|item| is_live_item(item, &item_id))
         .ok_or(StatusCode::NOT_FOUND)?
         .clone();
 
-    let template = EditItemTemplate { item, item_id };
+    let expiry = item_expiry(&item);
+    let template = EditItemTemplate {
+        categories: item
+            .categories()
+            .iter()
+            .map(|category| category.name())
+            .collect::<Vec<&str>>()
+            .join(", "),
+        expires_at: match expiry {
+            Some(ItemExpiry::At(expires_at)) => expires_at.format("%Y-%m-%dT%H:%M").to_string(),
+            _ => String::new(),
+        },
+        never_expires: expiry == Some(ItemExpiry::Never),
+        publish_at: item
+            .pub_date()
+            .filter(|_| is_item_scheduled(&item))
+            .and_then(|d| parse_datetime(d).ok())
+            .map(|d| d.format("%Y-%m-%dT%H:%M").to_string())
+            .unwrap_or_default(),
+        item,
+        item_id,
+        feed_id,
+        csrf_token: session.csrf_token,
+    };
     match template.render() {
         Ok(html) => Ok(Html(html)),
         Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR),
@@ -119,124 +854,443 @@ pub async fn edit_item_form(
 
 pub async fn web_add_item(
     State(state): State<AppState>,
-    Form(form): Form<NewItemForm>,
+    Extension(session): Extension<Session>,
+    Path(feed_id): Path<String>,
+    multipart: Multipart,
 ) -> Result<Redirect, StatusCode> {
-    if form.title.trim().is_empty() {
-        return Err(StatusCode::BAD_REQUEST);
+    // The form is sent as multipart so it can carry an attachment
+    let (form_fields, upload) = read_multipart(multipart)
+        .await
+        .map_err(|_| StatusCode::BAD_REQUEST)?;
+    let form: NewItemForm = serde_json::from_value(serde_json::Value::Object(
+        form_fields
+            .into_iter()
+            .map(|(name, value)| (name, serde_json::Value::String(value)))
+            .collect(),
+    ))
+    .map_err(|_| StatusCode::BAD_REQUEST)?;
+
+    session.verify_csrf(&form.csrf_token)?;
+    let expiry = form_expiry(form.expires_at.as_deref(), form.never_expires.is_some())?;
+    let publish_at = form_publish_at(form.publish_at.as_deref())?;
+    let mut fields = form.item.into_fields()?;
+    let has_upload = upload.is_some();
+    if let Some(upload) = upload {
+        let enclosure = state.attachments.save(&upload).map_err(|e| e.status())?;
+        fields.enclosure = Some(enclosure);
     }
 
-    let item = create_item(
-        form.title,
-        form.description.filter(|s| !s.trim().is_empty()),
-        form.link.filter(|s| !s.trim().is_empty()),
-    );
+    let mut item = create_item_from_fields(fields);
+    set_item_expiry(&mut item, expiry);
+    if let Some(publish_at) = publish_at.filter(|p| *p > chrono::Utc::now()) {
+        schedule_item(&mut item, publish_at);
+    }
 
-    add_item(axum::extract::State(state), item.clone());
+    let added = add_item(
+        axum::extract::State(state.clone()),
+        axum::extract::Path(feed_id.clone()),
+        item.clone(),
+        &AuditActor::web(&session),
+    );
+    if !matches!(added, Ok(Some(_))) {
+        if let Some(enclosure) = item.enclosure().filter(|_| has_upload) {
+            state.attachments.remove(enclosure.url());
+        }
+        added
+            .map_err(storage_failure)?
+            .ok_or(StatusCode::NOT_FOUND)?;
+    }
     info!("Item added successfully: {}", item.guid().unwrap().value);
 
-    Ok(Redirect::to("/"))
+    Ok(Redirect::to(&format!("/manage/{}", feed_id)))
 }
 
 pub async fn web_delete_item(
     State(state): State<AppState>,
-    Path(item_id): Path<String>,
+    Extension(session): Extension<Session>,
+    Path((feed_id, item_id)): Path<(String, String)>,
+    Form(form): Form<CsrfForm>,
 ) -> Result<Redirect, StatusCode> {
+    session.verify_csrf(&form.csrf_token)?;
     delete_item(
         axum::extract::State(state),
-        axum::extract::Path(item_id.clone()),
-    );
-    info!("Item deleted successfully: {}", item_id);
+        axum::extract::Path((feed_id.clone(), item_id.clone())),
+        &AuditActor::web(&session),
+    )
+    .map_err(storage_failure)?
+    .ok_or(StatusCode::NOT_FOUND)?;
+    info!("Item moved to the trash: {}", item_id);
 
-    Ok(Redirect::to("/"))
+    Ok(Redirect::to(&format!("/manage/{}", feed_id)))
+}
+
+pub async fn web_restore_item(
+    State(state): State<AppState>,
+    Extension(session): Extension<Session>,
+    Path((feed_id, item_id)): Path<(String, String)>,
+    Form(form): Form<CsrfForm>,
+) -> Result<Redirect, StatusCode> {
+    session.verify_csrf(&form.csrf_token)?;
+    restore_item(&state, &feed_id, &item_id, &AuditActor::web(&session))
+        .map_err(storage_failure)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    Ok(Redirect::to(&format!("/manage/{}", feed_id)))
+}
+
+// Live changes to the feed for its page, the same events as the API stream
+pub async fn web_stream(
+    State(state): State<AppState>,
+    Path(feed_id): Path<String>,
+) -> Result<impl IntoResponse, StatusCode> {
+    if !state.feeds.lock().unwrap().contains_key(&feed_id) {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    Ok(state.events.sse(Some(feed_id)))
 }
 
 pub async fn web_edit_item(
     State(state): State<AppState>,
-    Path(item_id): Path<String>,
+    Extension(session): Extension<Session>,
+    Path((feed_id, item_id)): Path<(String, String)>,
     Form(form): Form<EditItemForm>,
 ) -> Result<Redirect, StatusCode> {
-    if form.title.trim().is_empty() {
-        return Err(StatusCode::BAD_REQUEST);
+    session.verify_csrf(&form.csrf_token)?;
+    let expiry = form_expiry(form.expires_at.as_deref(), form.never_expires.is_some())?;
+    let publish_at = form_publish_at(form.publish_at.as_deref())?;
+    let mut fields = form.item.into_fields()?;
+
+    // The form only edits category names, so keep the domains of categories that remain
+    if let Some(existing) = state
+        .feeds
+        .lock()
+        .unwrap()
+        .get(&feed_id)
+        .and_then(|channel| {
+            channel
+                .items()
+                .iter()
+                .find(|item| is_live_item(item, &item_id))
+                .map(|item| item.categories().to_vec())
+        })
+    {
+        for category in fields.categories.iter_mut() {
+            category.domain = existing
+                .iter()
+                .find(|c| c.name() == category.name())
+                .and_then(|c| c.domain().map(|s| s.to_string()));
+        }
     }
 
     let item = edit_item(
         axum::extract::State(state),
-        axum::extract::Path(item_id),
-        form.title,
-        form.description,
-        form.link,
-    );
+        axum::extract::Path((feed_id.clone(), item_id)),
+        fields,
+        expiry,
+        publish_at,
+        &AuditActor::web(&session),
+    )
+    .map_err(storage_failure)?
+    .ok_or(StatusCode::NOT_FOUND)?;
+    info!("Item edited successfully: {}", item.guid().unwrap().value);
 
-    if item.is_none() {
-        return Err(StatusCode::NOT_FOUND);
+    Ok(Redirect::to(&format!("/manage/{}", feed_id)))
+}
+
+fn render_login(
+    next: String,
+    error: Option<String>,
+    setup: bool,
+) -> Result<Html<String>, StatusCode> {
+    let template = LoginTemplate {
+        next,
+        error,
+        setup,
+        setup_token_required: setup && setup_token().is_some(),
+    };
+    match template.render() {
+        Ok(html) => Ok(Html(html)),
+        Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR),
     }
-    info!(
-        "Item edited successfully: {}",
-        item.unwrap().guid().unwrap().value
-    );
+}
 
-    Ok(Redirect::to("/"))
+// Starts a session for the user and redirects to where they were going
+fn login_response(state: &AppState, username: &str, next: Option<&str>) -> Response<Body> {
+    let mut sessions = state.sessions.lock().unwrap();
+    let token = sessions.create(username);
+    let cookie = session_cookie(&token, sessions.ttl_seconds());
+    (
+        [(header::SET_COOKIE, cookie)],
+        Redirect::to(&safe_redirect_target(next)),
+    )
+        .into_response()
 }
 
-pub async fn health_check() -> impl IntoResponse {
-    let mut checks = std::collections::HashMap::new();
-    let mut overall_healthy = true;
+pub async fn login_form(
+    State(state): State<AppState>,
+    Query(query): Query<LoginQuery>,
+) -> Result<Response<Body>, StatusCode> {
+    if state.users.lock().unwrap().is_empty() {
+        return Ok(Redirect::to("/setup").into_response());
+    }
+    let next = safe_redirect_target(query.next.as_deref());
+    Ok(render_login(next, None, false)?.into_response())
+}
 
-    // File existence check
-    if StdPath::new("./feed/feed.xml").exists() {
-        checks.insert(
-            "feed_file".to_string(),
-            CheckResult {
-                status: "healthy".to_string(),
-                message: None,
-            },
-        );
-    } else {
-        checks.insert(
-            "feed_file".to_string(),
-            CheckResult {
-                status: "unhealthy".to_string(),
-                message: Some("feed.xml not found".to_string()),
-            },
-        );
-        overall_healthy = false;
-    }
-
-    // Directory permissions check
-    let feed_dir = StdPath::new("./feed");
-    if feed_dir.exists() {
-        let test_file = feed_dir.join(".health_temp");
-        match fs::write(&test_file, "test") {
-            Ok(_) => {
-                let _ = fs::remove_file(&test_file);
-                checks.insert(
-                    "directory_writable".to_string(),
-                    CheckResult {
-                        status: "healthy".to_string(),
-                        message: None,
-                    },
-                );
+pub async fn web_login(
+    State(state): State<AppState>,
+    Form(form): Form<LoginForm>,
+) -> Result<Response<Body>, StatusCode> {
+    if !state
+        .users
+        .lock()
+        .unwrap()
+        .verify(&form.username, &form.password)
+    {
+        info!("Failed login attempt for user: {}", form.username.trim());
+        let next = safe_redirect_target(form.next.as_deref());
+        let html = render_login(
+            next,
+            Some("Invalid username or password".to_string()),
+            false,
+        )?;
+        return Ok((StatusCode::UNAUTHORIZED, html).into_response());
+    }
+    info!("User logged in: {}", form.username.trim());
+
+    Ok(login_response(
+        &state,
+        form.username.trim(),
+        form.next.as_deref(),
+    ))
+}
+
+pub async fn web_logout(
+    State(state): State<AppState>,
+    Extension(session): Extension<Session>,
+    headers: HeaderMap,
+    Form(form): Form<CsrfForm>,
+) -> Result<Response<Body>, StatusCode> {
+    session.verify_csrf(&form.csrf_token)?;
+    if let Some(token) = session_token(&headers) {
+        state.sessions.lock().unwrap().remove(&token);
+    }
+    info!("User logged out: {}", session.username);
+
+    Ok((
+        [(header::SET_COOKIE, session_cookie("", 0))],
+        Redirect::to("/login"),
+    )
+        .into_response())
+}
+
+// The first account can be created with SETUP_TOKEN, or without it only from this machine
+fn setup_denied(connect_info: Option<&ConnectInfo<SocketAddr>>, headers: &HeaderMap) -> bool {
+    setup_token().is_none() && !is_local_request(connect_info, headers)
+}
+
+fn setup_denied_response() -> Response<Body> {
+    warn!("Rejected setup request from a remote address without SETUP_TOKEN configured");
+    (
+        StatusCode::FORBIDDEN,
+        "Set SETUP_TOKEN to create the first account remotely, or open /setup on the machine running Feed Crafter",
+    )
+        .into_response()
+}
+
+pub async fn setup_form(
+    State(state): State<AppState>,
+    connect_info: Option<Extension<ConnectInfo<SocketAddr>>>,
+    headers: HeaderMap,
+) -> Result<Response<Body>, StatusCode> {
+    if !state.users.lock().unwrap().is_empty() {
+        return Ok(Redirect::to("/login").into_response());
+    }
+    if setup_denied(connect_info.as_deref(), &headers) {
+        return Ok(setup_denied_response());
+    }
+    Ok(render_login("/".to_string(), None, true)?.into_response())
+}
+
+pub async fn web_setup(
+    State(state): State<AppState>,
+    connect_info: Option<Extension<ConnectInfo<SocketAddr>>>,
+    headers: HeaderMap,
+    Form(form): Form<LoginForm>,
+) -> Result<Response<Body>, StatusCode> {
+    if setup_denied(connect_info.as_deref(), &headers) {
+        return Ok(setup_denied_response());
+    }
+    {
+        let mut users = state.users.lock().unwrap();
+        // Setup is only available until the first account exists
+        if !users.is_empty() {
+            return Ok(Redirect::to("/login").into_response());
+        }
+        if let Some(token) = setup_token()
+            && form
+                .setup_token
+                .as_deref()
+                .is_none_or(|given| hash_token(given.trim()) != hash_token(&token))
+        {
+            warn!("Rejected setup request with an invalid setup token");
+            let html = render_login(
+                "/".to_string(),
+                Some("Invalid setup token".to_string()),
+                true,
+            )?;
+            return Ok((StatusCode::FORBIDDEN, html).into_response());
+        }
+        match users.create(&form.username, &form.password) {
+            Ok(()) => {}
+            Err(UserError::Invalid(message)) => {
+                let html = render_login("/".to_string(), Some(message), true)?;
+                return Ok((StatusCode::BAD_REQUEST, html).into_response());
             }
             Err(e) => {
-                checks.insert(
-                    "directory_writable".to_string(),
-                    CheckResult {
-                        status: "unhealthy".to_string(),
-                        message: Some(format!("Cannot write to feed directory: {}", e)),
-                    },
-                );
-                overall_healthy = false;
+                error!("{}", e);
+                return Err(StatusCode::INTERNAL_SERVER_ERROR);
             }
         }
-    } else {
-        checks.insert(
-            "directory_writable".to_string(),
-            CheckResult {
-                status: "unhealthy".to_string(),
-                message: Some("Feed directory does not exist".to_string()),
-            },
-        );
-        overall_healthy = false;
+    }
+
+    Ok(login_response(&state, form.username.trim(), Some("/")))
+}
+
+fn render_users(
+    state: &AppState,
+    session: Session,
+    error: Option<String>,
+) -> Result<Html<String>, StatusCode> {
+    let users = state.users.lock().unwrap().users().to_vec();
+    let template = UsersTemplate {
+        users,
+        username: session.username,
+        csrf_token: session.csrf_token,
+        error,
+    };
+    match template.render() {
+        Ok(html) => Ok(Html(html)),
+        Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR),
+    }
+}
+
+// How many of the newest audit entries the web page shows
+const AUDIT_PAGE_SIZE: usize = 200;
+
+#[derive(Deserialize)]
+pub struct AuditQuery {
+    feed: Option<String>,
+}
+
+pub async fn audit_index(
+    State(state): State<AppState>,
+    Query(query): Query<AuditQuery>,
+) -> Result<Html<String>, StatusCode> {
+    let feed_id = query.feed.filter(|feed| !feed.is_empty());
+    let filter = AuditFilter {
+        feed_id: feed_id.clone(),
+        ..AuditFilter::default()
+    };
+    let (entries, total) = state
+        .audit
+        .query(&filter, 0, AUDIT_PAGE_SIZE)
+        .map_err(|e| {
+            error!("Failed to read the audit log: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    let template = AuditTemplate {
+        entries: entries.into_iter().map(AuditRow::from).collect(),
+        total,
+        feed_id,
+    };
+    match template.render() {
+        Ok(html) => Ok(Html(html)),
+        Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR),
+    }
+}
+
+pub async fn users_index(
+    State(state): State<AppState>,
+    Extension(session): Extension<Session>,
+) -> Result<Html<String>, StatusCode> {
+    render_users(&state, session, None)
+}
+
+pub async fn web_add_user(
+    State(state): State<AppState>,
+    Extension(session): Extension<Session>,
+    Form(form): Form<NewUserForm>,
+) -> Result<Response<Body>, StatusCode> {
+    session.verify_csrf(&form.csrf_token)?;
+
+    let result = state
+        .users
+        .lock()
+        .unwrap()
+        .create(&form.username, &form.password);
+    match result {
+        Ok(()) => {}
+        Err(UserError::Invalid(message)) => {
+            let html = render_users(&state, session, Some(message))?;
+            return Ok((StatusCode::BAD_REQUEST, html).into_response());
+        }
+        Err(e) => {
+            error!("{}", e);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    }
+
+    Ok(Redirect::to("/users").into_response())
+}
+
+pub async fn web_delete_user(
+    State(state): State<AppState>,
+    Extension(session): Extension<Session>,
+    Path(username): Path<String>,
+    Form(form): Form<CsrfForm>,
+) -> Result<Redirect, StatusCode> {
+    session.verify_csrf(&form.csrf_token)?;
+
+    let deleted = state.users.lock().unwrap().delete(&username).map_err(|e| {
+        error!("Failed to save users: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    if !deleted {
+        return Err(StatusCode::CONFLICT);
+    }
+    // Sign the deleted user out everywhere
+    state.sessions.lock().unwrap().remove_user(&username);
+
+    Ok(Redirect::to("/users"))
+}
+
+pub async fn health_check(State(state): State<AppState>) -> impl IntoResponse {
+    let mut checks = std::collections::HashMap::new();
+    let mut overall_healthy = true;
+
+    // Storage backend check, which also makes sure the XML feed directory takes new files
+    match state.storage.check() {
+        Ok(()) => {
+            checks.insert(
+                "storage".to_string(),
+                CheckResult {
+                    status: "healthy".to_string(),
+                    message: None,
+                },
+            );
+        }
+        Err(e) => {
+            checks.insert(
+                "storage".to_string(),
+                CheckResult {
+                    status: "unhealthy".to_string(),
+                    message: Some(e.to_string()),
+                },
+            );
+            overall_healthy = false;
+        }
     }
 
     let health_status = HealthStatus {
@@ -0,0 +1,399 @@
+use crate::api::ApiItem;
+use crate::audit::{AuditAction, AuditEntry, AuditSource};
+use crate::common::{FEED_DIRECTORY, FileSystem, RealFileSystem};
+use hmac::{Hmac, Mac};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::collections::VecDeque;
+use std::env;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender, unbounded_channel};
+use uuid::Uuid;
+
+pub const WEBHOOK_EVENTS: [&str; 8] = [
+    "item.created",
+    "item.updated",
+    "item.deleted",
+    "item.expired",
+    "item.purged",
+    "item.published",
+    "item.restored",
+    "feed.restored",
+];
+
+const DEFAULT_MAX_ATTEMPTS: u32 = 5;
+const DEFAULT_RETRY_SECONDS: u64 = 30;
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+// How many delivery attempts are kept for the delivery log
+const DELIVERY_LOG_SIZE: usize = 200;
+
+// The event a recorded change is sent as
+pub fn event_name(entry: &AuditEntry) -> &'static str {
+    match entry.action {
+        AuditAction::Create => "item.created",
+        AuditAction::Update => "item.updated",
+        AuditAction::Delete => "item.deleted",
+        AuditAction::Expire => "item.expired",
+        AuditAction::Purge => "item.purged",
+        AuditAction::Publish => "item.published",
+        AuditAction::Restore if entry.item_id.is_some() => "item.restored",
+        AuditAction::Restore => "feed.restored",
+    }
+}
+
+// `sha256=` followed by the hex HMAC-SHA256 of the body, keyed with the webhook's secret
+pub fn signature(secret: &str, body: &[u8]) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts any key length");
+    mac.update(body);
+    let digest: String = mac
+        .finalize()
+        .into_bytes()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect();
+    format!("sha256={}", digest)
+}
+
+// Signature of a webhook delivery, over `{timestamp}.{body}` so receivers can reject replays
+// of an old delivery by its timestamp
+pub fn webhook_signature(secret: &str, timestamp: i64, body: &str) -> String {
+    signature(secret, format!("{}.{}", timestamp, body).as_bytes())
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Webhook {
+    pub id: String,
+    pub url: String,
+    pub secret: String,
+    // Events to send, every event when empty
+    #[serde(default)]
+    pub events: Vec<String>,
+    // Only changes to this feed are sent when set
+    #[serde(default)]
+    pub feed: Option<String>,
+    pub created: String,
+}
+
+impl Webhook {
+    fn matches(&self, feed_id: &str, event: &str) -> bool {
+        (self.events.is_empty() || self.events.iter().any(|e| e == event))
+            && self.feed.as_ref().is_none_or(|feed| feed == feed_id)
+    }
+}
+
+// Body of every notification, items are in the same shape as the items API
+#[derive(Serialize)]
+pub struct WebhookPayload<'a> {
+    event: &'a str,
+    feed: &'a str,
+    timestamp: &'a str,
+    actor: Option<&'a str>,
+    source: AuditSource,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    item: Option<ApiItem>,
+    // The item before an edit
+    #[serde(skip_serializing_if = "Option::is_none")]
+    previous: Option<ApiItem>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    backup: Option<&'a str>,
+}
+
+impl<'a> WebhookPayload<'a> {
+    pub fn new(event: &'a str, entry: &'a AuditEntry) -> Self {
+        // Removed items are sent as they were, everything else as it is now
+        let (item, previous) = match (&entry.before, &entry.after) {
+            (before, Some(after)) => (Some(after), before.as_ref()),
+            (before, None) => (before.as_ref(), None),
+        };
+        Self {
+            event,
+            feed: &entry.feed_id,
+            timestamp: &entry.timestamp,
+            actor: entry.actor.as_deref(),
+            source: entry.source,
+            item: item.map(ApiItem::from),
+            previous: previous.map(ApiItem::from),
+            backup: entry.backup_id.as_deref(),
+        }
+    }
+}
+
+// One attempt at sending a notification, retries share the delivery id
+#[derive(Clone, Serialize)]
+pub struct WebhookDelivery {
+    pub id: String,
+    pub webhook_id: String,
+    pub event: String,
+    pub attempt: u32,
+    pub delivered: bool,
+    pub status: Option<u16>,
+    pub error: Option<String>,
+    pub timestamp: String,
+}
+
+struct DeliveryJob {
+    id: String,
+    webhook: Webhook,
+    event: String,
+    body: String,
+}
+
+// Webhooks are stored in WEBHOOKS_FILE, notifications are queued by `notify` and sent by the
+// task started with `start`, the delivery log is only kept in memory
+pub struct WebhookStore {
+    path: PathBuf,
+    webhooks: Mutex<Vec<Webhook>>,
+    deliveries: Mutex<VecDeque<WebhookDelivery>>,
+    max_attempts: u32,
+    retry_delay: Duration,
+    client: reqwest::Client,
+    sender: UnboundedSender<DeliveryJob>,
+    receiver: Mutex<Option<UnboundedReceiver<DeliveryJob>>>,
+}
+
+impl WebhookStore {
+    pub fn new(path: PathBuf, max_attempts: u32, retry_delay: Duration) -> io::Result<Self> {
+        let webhooks = if path.exists() {
+            let contents = fs::read_to_string(&path)?;
+            serde_json::from_str(&contents)?
+        } else {
+            Vec::new()
+        };
+        let (sender, receiver) = unbounded_channel();
+        Ok(Self {
+            path,
+            webhooks: Mutex::new(webhooks),
+            deliveries: Mutex::new(VecDeque::new()),
+            max_attempts: max_attempts.max(1),
+            retry_delay,
+            client: reqwest::Client::builder()
+                .timeout(REQUEST_TIMEOUT)
+                .build()
+                .expect("Failed to create the webhook HTTP client"),
+            sender,
+            receiver: Mutex::new(Some(receiver)),
+        })
+    }
+
+    // Reads WEBHOOKS_FILE, WEBHOOK_MAX_ATTEMPTS and WEBHOOK_RETRY_SECONDS, the delay before
+    // each retry doubles
+    pub fn load() -> io::Result<Self> {
+        let path = env::var("WEBHOOKS_FILE")
+            .unwrap_or_else(|_| format!("{}/webhooks.json", FEED_DIRECTORY));
+        let max_attempts = match env::var("WEBHOOK_MAX_ATTEMPTS") {
+            Ok(val) => val.parse::<u32>().unwrap_or_else(|_| {
+                warn!(
+                    "Invalid WEBHOOK_MAX_ATTEMPTS value: '{}', using the default",
+                    val
+                );
+                DEFAULT_MAX_ATTEMPTS
+            }),
+            Err(_) => DEFAULT_MAX_ATTEMPTS,
+        };
+        let retry_seconds = match env::var("WEBHOOK_RETRY_SECONDS") {
+            Ok(val) => val.parse::<u64>().unwrap_or_else(|_| {
+                warn!(
+                    "Invalid WEBHOOK_RETRY_SECONDS value: '{}', using the default",
+                    val
+                );
+                DEFAULT_RETRY_SECONDS
+            }),
+            Err(_) => DEFAULT_RETRY_SECONDS,
+        };
+        Self::new(
+            PathBuf::from(path),
+            max_attempts,
+            Duration::from_secs(retry_seconds),
+        )
+    }
+
+    pub fn webhooks(&self) -> Vec<Webhook> {
+        self.webhooks.lock().unwrap().clone()
+    }
+
+    // Delivery attempts, newest first
+    pub fn deliveries(&self) -> Vec<WebhookDelivery> {
+        self.deliveries
+            .lock()
+            .unwrap()
+            .iter()
+            .rev()
+            .cloned()
+            .collect()
+    }
+
+    fn save(&self, webhooks: &[Webhook]) -> std::io::Result<()> {
+        if let Some(parent) = self.path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            fs::create_dir_all(parent)?;
+        }
+        let contents = serde_json::to_string_pretty(webhooks)?;
+        RealFileSystem.write_atomic(&self.path.to_string_lossy(), &contents)
+    }
+
+    // Adds a webhook, generating a secret unless one is given
+    pub fn create(
+        &self,
+        url: String,
+        secret: Option<String>,
+        events: Vec<String>,
+        feed: Option<String>,
+    ) -> std::io::Result<Webhook> {
+        let webhook = Webhook {
+            id: Uuid::new_v4().to_string(),
+            url,
+            secret: secret.unwrap_or_else(|| {
+                format!(
+                    "whsec_{}{}",
+                    Uuid::new_v4().simple(),
+                    Uuid::new_v4().simple()
+                )
+            }),
+            events,
+            feed,
+            created: chrono::Utc::now().to_rfc2822(),
+        };
+        let mut webhooks = self.webhooks.lock().unwrap();
+        let mut updated = webhooks.clone();
+        updated.push(webhook.clone());
+        self.save(&updated)?;
+        *webhooks = updated;
+        info!("Webhook created: {} ({})", webhook.url, webhook.id);
+        Ok(webhook)
+    }
+
+    pub fn delete(&self, id: &str) -> std::io::Result<Option<Webhook>> {
+        let mut webhooks = self.webhooks.lock().unwrap();
+        let Some(index) = webhooks.iter().position(|webhook| webhook.id == id) else {
+            return Ok(None);
+        };
+        let mut updated = webhooks.clone();
+        let webhook = updated.remove(index);
+        self.save(&updated)?;
+        *webhooks = updated;
+        info!("Webhook deleted: {} ({})", webhook.url, webhook.id);
+        Ok(Some(webhook))
+    }
+
+    // Queues a notification of each change for every webhook that wants it
+    pub fn notify(&self, entries: &[AuditEntry]) {
+        let webhooks = self.webhooks.lock().unwrap();
+        for entry in entries {
+            let event = event_name(entry);
+            let matching: Vec<&Webhook> = webhooks
+                .iter()
+                .filter(|webhook| webhook.matches(&entry.feed_id, event))
+                .collect();
+            if matching.is_empty() {
+                continue;
+            }
+
+            let body = serde_json::to_string(&WebhookPayload::new(event, entry))
+                .expect("Failed to serialize webhook payload");
+            for webhook in matching {
+                let job = DeliveryJob {
+                    id: Uuid::new_v4().to_string(),
+                    webhook: webhook.clone(),
+                    event: event.to_string(),
+                    body: body.clone(),
+                };
+                if self.sender.send(job).is_err() {
+                    warn!("Webhook queue is closed, dropping '{}' notification", event);
+                }
+            }
+        }
+    }
+
+    // Starts the task that sends queued notifications, each delivery retries on its own
+    pub fn start(self: &Arc<Self>) {
+        let Some(mut receiver) = self.receiver.lock().unwrap().take() else {
+            return;
+        };
+        let store = Arc::clone(self);
+        tokio::spawn(async move {
+            while let Some(job) = receiver.recv().await {
+                tokio::spawn(Arc::clone(&store).deliver(job));
+            }
+        });
+    }
+
+    async fn deliver(self: Arc<Self>, job: DeliveryJob) {
+        for attempt in 1..=self.max_attempts {
+            if attempt > 1 {
+                tokio::time::sleep(self.retry_delay * 2u32.saturating_pow(attempt - 2)).await;
+                // Stop retrying once the webhook has been removed
+                if !self.is_configured(&job.webhook.id) {
+                    return;
+                }
+            }
+
+            // Each attempt is signed with the time it is sent at
+            let timestamp = chrono::Utc::now().timestamp();
+            let result = self
+                .client
+                .post(&job.webhook.url)
+                .header("Content-Type", "application/json")
+                .header("X-FeedCrafter-Event", &job.event)
+                .header("X-FeedCrafter-Delivery", &job.id)
+                .header("X-FeedCrafter-Timestamp", timestamp.to_string())
+                .header(
+                    "X-FeedCrafter-Signature",
+                    webhook_signature(&job.webhook.secret, timestamp, &job.body),
+                )
+                .body(job.body.clone())
+                .send()
+                .await;
+            let (status, error) = match result {
+                Ok(response) if response.status().is_success() => {
+                    (Some(response.status().as_u16()), None)
+                }
+                Ok(response) => (
+                    Some(response.status().as_u16()),
+                    Some(format!("Unexpected response status {}", response.status())),
+                ),
+                Err(e) => (None, Some(e.to_string())),
+            };
+            let delivered = error.is_none();
+            if let Some(error) = &error {
+                warn!(
+                    "Webhook delivery {} to {} failed (attempt {} of {}): {}",
+                    job.id, job.webhook.url, attempt, self.max_attempts, error
+                );
+            }
+            self.log(WebhookDelivery {
+                id: job.id.clone(),
+                webhook_id: job.webhook.id.clone(),
+                event: job.event.clone(),
+                attempt,
+                delivered,
+                status,
+                error,
+                timestamp: chrono::Utc::now().to_rfc3339(),
+            });
+            if delivered {
+                return;
+            }
+        }
+    }
+
+    fn is_configured(&self, id: &str) -> bool {
+        self.webhooks
+            .lock()
+            .unwrap()
+            .iter()
+            .any(|webhook| webhook.id == id)
+    }
+
+    fn log(&self, delivery: WebhookDelivery) {
+        let mut deliveries = self.deliveries.lock().unwrap();
+        deliveries.push_back(delivery);
+        while deliveries.len() > DELIVERY_LOG_SIZE {
+            deliveries.pop_front();
+        }
+    }
+}
@@ -0,0 +1,485 @@
+use crate::common::{FEED_DIRECTORY, FileSystem, RealFileSystem, served_channel};
+use crate::formats::{FeedFormat, FeedLinks, parse_feed_path, render_feed};
+use crate::session::env_flag;
+use crate::webhooks::signature;
+use chrono::{DateTime, Utc};
+use log::{debug, info, warn};
+use rss::Channel;
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::fs;
+use std::io;
+use std::net::{IpAddr, SocketAddr};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender, unbounded_channel};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use uuid::Uuid;
+
+pub const HUB_ROUTE: &str = "/hub";
+
+const DEFAULT_LEASE_SECONDS: u64 = 10 * 24 * 60 * 60;
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+// Anyone can ask the hub to verify a callback, so only this many are checked at once
+const MAX_PENDING_VERIFICATIONS: usize = 16;
+const MAX_CONCURRENT_PUSHES: usize = 16;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum HubMode {
+    Subscribe,
+    Unsubscribe,
+}
+
+impl HubMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            HubMode::Subscribe => "subscribe",
+            HubMode::Unsubscribe => "unsubscribe",
+        }
+    }
+}
+
+// A subscribe or unsubscribe request, only applied once the subscriber has confirmed it
+#[derive(Clone, Debug)]
+pub struct HubRequest {
+    pub mode: HubMode,
+    pub callback: String,
+    pub topic: String,
+    pub lease_seconds: u64,
+    pub secret: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Subscription {
+    pub callback: String,
+    pub topic: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub secret: Option<String>,
+    pub expires: DateTime<Utc>,
+}
+
+// Feed and format a topic URL points at, when it is one of the served feed URLs
+pub fn topic_feed(topic: &str) -> Option<(String, FeedFormat)> {
+    let url = reqwest::Url::parse(topic).ok()?;
+    if !matches!(url.scheme(), "http" | "https") {
+        return None;
+    }
+    parse_feed_path(url.path()).map(|(feed_id, format)| (feed_id.to_string(), format))
+}
+
+// Whether the topic is served from `base_url`, the feeds' public origin
+pub fn is_local_topic(topic: &str, base_url: &str) -> bool {
+    match (reqwest::Url::parse(topic), reqwest::Url::parse(base_url)) {
+        (Ok(topic), Ok(base)) => topic.origin() == base.origin(),
+        _ => false,
+    }
+}
+
+// The topic moved to another feed id, keeping its origin and format
+fn renamed_topic(topic: &str, new_feed_id: &str) -> Option<String> {
+    let mut url = reqwest::Url::parse(topic).ok()?;
+    parse_feed_path(url.path())?;
+    let extension = url.path().rsplit_once('.')?.1.to_string();
+    url.set_path(&format!("/feeds/{}.{}", new_feed_id, extension));
+    Some(url.to_string())
+}
+
+// Addresses on the internet, as opposed to this machine or a private network
+pub fn is_public_address(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, ..] = ip.octets();
+            !(ip.is_loopback()
+                || ip.is_private()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+                || ip.is_multicast()
+                || ip.is_documentation()
+                // Carrier-grade NAT, 100.64.0.0/10
+                || (a == 100 && (64..128).contains(&b))
+                || a == 0)
+        }
+        IpAddr::V6(ip) => {
+            if let Some(mapped) = ip.to_ipv4_mapped() {
+                return is_public_address(IpAddr::V4(mapped));
+            }
+            let first = ip.segments()[0];
+            !(ip.is_loopback()
+                || ip.is_unspecified()
+                || ip.is_multicast()
+                // Unique local, fc00::/7
+                || (first & 0xfe00) == 0xfc00
+                // Link-local, fe80::/10
+                || (first & 0xffc0) == 0xfe80)
+        }
+    }
+}
+
+// Links advertised by a feed served from `base_url`
+pub fn feed_links(base_url: &str, self_url: String) -> FeedLinks {
+    FeedLinks {
+        base_url: base_url.to_string(),
+        self_url,
+        hub_url: format!("{}{}", base_url, HUB_ROUTE),
+    }
+}
+
+struct PushJob {
+    feed_id: String,
+    channel: Channel,
+    subscriptions: Vec<Subscription>,
+}
+
+// Subscriptions are stored in WEBSUB_FILE, feeds are queued by `publish` and pushed to their
+// subscribers by the task started with `start`
+pub struct WebSubHub {
+    path: PathBuf,
+    subscriptions: Mutex<Vec<Subscription>>,
+    lease_seconds: u64,
+    // Callbacks on this machine or a private network are refused unless allowed
+    allow_private_callbacks: bool,
+    verifications: Arc<Semaphore>,
+    pushes: Arc<Semaphore>,
+    sender: UnboundedSender<PushJob>,
+    receiver: Mutex<Option<UnboundedReceiver<PushJob>>>,
+}
+
+impl WebSubHub {
+    pub fn new(
+        path: PathBuf,
+        lease_seconds: u64,
+        allow_private_callbacks: bool,
+    ) -> io::Result<Self> {
+        let subscriptions = if path.exists() {
+            let contents = fs::read_to_string(&path)?;
+            serde_json::from_str(&contents)?
+        } else {
+            Vec::new()
+        };
+        let (sender, receiver) = unbounded_channel();
+        Ok(Self {
+            path,
+            subscriptions: Mutex::new(subscriptions),
+            lease_seconds: lease_seconds.max(1),
+            allow_private_callbacks,
+            verifications: Arc::new(Semaphore::new(MAX_PENDING_VERIFICATIONS)),
+            pushes: Arc::new(Semaphore::new(MAX_CONCURRENT_PUSHES)),
+            sender,
+            receiver: Mutex::new(Some(receiver)),
+        })
+    }
+
+    // Reads WEBSUB_FILE, WEBSUB_LEASE_SECONDS, the default and longest lease granted, and
+    // WEBSUB_ALLOW_PRIVATE_CALLBACKS
+    pub fn load() -> io::Result<Self> {
+        let path =
+            env::var("WEBSUB_FILE").unwrap_or_else(|_| format!("{}/websub.json", FEED_DIRECTORY));
+        let lease_seconds = match env::var("WEBSUB_LEASE_SECONDS") {
+            Ok(val) => val.parse::<u64>().unwrap_or_else(|_| {
+                warn!(
+                    "Invalid WEBSUB_LEASE_SECONDS value: '{}', using the default",
+                    val
+                );
+                DEFAULT_LEASE_SECONDS
+            }),
+            Err(_) => DEFAULT_LEASE_SECONDS,
+        };
+        Self::new(
+            PathBuf::from(path),
+            lease_seconds,
+            env_flag("WEBSUB_ALLOW_PRIVATE_CALLBACKS", false),
+        )
+    }
+
+    // A slot for verifying a request, None while too many are already being verified
+    pub fn try_reserve_verification(&self) -> Option<OwnedSemaphorePermit> {
+        Arc::clone(&self.verifications).try_acquire_owned().ok()
+    }
+
+    // Client for one callback, pinned to the addresses that were checked so a second lookup
+    // can't point it elsewhere. Redirects are never followed
+    async fn callback_client(&self, callback: &str) -> Result<reqwest::Client, String> {
+        let url = reqwest::Url::parse(callback).map_err(|e| e.to_string())?;
+        let host = url.host_str().ok_or("Callback has no host")?;
+        let port = url.port_or_known_default().ok_or("Callback has no port")?;
+        let addresses: Vec<SocketAddr> = tokio::net::lookup_host((host, port))
+            .await
+            .map_err(|e| format!("Callback host can't be resolved: {}", e))?
+            .collect();
+        if addresses.is_empty() {
+            return Err("Callback host can't be resolved".to_string());
+        }
+        if !self.allow_private_callbacks
+            && addresses
+                .iter()
+                .any(|address| !is_public_address(address.ip()))
+        {
+            return Err("Callback resolves to a private address".to_string());
+        }
+
+        reqwest::Client::builder()
+            .timeout(REQUEST_TIMEOUT)
+            .redirect(reqwest::redirect::Policy::none())
+            .resolve_to_addrs(host, &addresses)
+            .build()
+            .map_err(|e| e.to_string())
+    }
+
+    // Lease granted for a requested one, subscribers asking for none get the longest
+    pub fn lease_seconds(&self, requested: Option<u64>) -> u64 {
+        requested
+            .unwrap_or(self.lease_seconds)
+            .clamp(1, self.lease_seconds)
+    }
+
+    // Subscriptions whose lease hasn't run out
+    pub fn subscriptions(&self) -> Vec<Subscription> {
+        let now = Utc::now();
+        self.subscriptions
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|subscription| subscription.expires > now)
+            .cloned()
+            .collect()
+    }
+
+    fn save(&self, subscriptions: &[Subscription]) -> std::io::Result<()> {
+        if let Some(parent) = self.path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            fs::create_dir_all(parent)?;
+        }
+        let contents = serde_json::to_string_pretty(subscriptions)?;
+        RealFileSystem.write_atomic(&self.path.to_string_lossy(), &contents)
+    }
+
+    // Confirms the request with the subscriber by having it echo a challenge, then applies it
+    pub async fn verify(&self, request: HubRequest) -> Result<(), String> {
+        let challenge = Uuid::new_v4().simple().to_string();
+        let mut query = vec![
+            ("hub.mode", request.mode.as_str().to_string()),
+            ("hub.topic", request.topic.clone()),
+            ("hub.challenge", challenge.clone()),
+        ];
+        if request.mode == HubMode::Subscribe {
+            query.push(("hub.lease_seconds", request.lease_seconds.to_string()));
+        }
+
+        let response = self
+            .callback_client(&request.callback)
+            .await?
+            .get(&request.callback)
+            .query(&query)
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+        if !response.status().is_success() {
+            return Err(format!(
+                "Callback responded with status {}",
+                response.status()
+            ));
+        }
+        let body = response.text().await.map_err(|e| e.to_string())?;
+        if body.trim() != challenge {
+            return Err("Callback did not echo the challenge".to_string());
+        }
+
+        self.apply(&request)
+            .map_err(|e| format!("Failed to save subscriptions: {}", e))?;
+        info!(
+            "WebSub {} of {} to {} verified",
+            request.mode.as_str(),
+            request.callback,
+            request.topic
+        );
+        Ok(())
+    }
+
+    // Adds, renews or removes the callback's subscription to the topic, dropping expired ones
+    fn apply(&self, request: &HubRequest) -> std::io::Result<()> {
+        let now = Utc::now();
+        let mut subscriptions = self.subscriptions.lock().unwrap();
+        let mut updated: Vec<Subscription> = subscriptions
+            .iter()
+            .filter(|subscription| {
+                subscription.expires > now
+                    && !(subscription.callback == request.callback
+                        && subscription.topic == request.topic)
+            })
+            .cloned()
+            .collect();
+        if request.mode == HubMode::Subscribe {
+            updated.push(Subscription {
+                callback: request.callback.clone(),
+                topic: request.topic.clone(),
+                secret: request.secret.clone(),
+                expires: now + chrono::Duration::seconds(request.lease_seconds as i64),
+            });
+        }
+        self.save(&updated)?;
+        *subscriptions = updated;
+        Ok(())
+    }
+
+    // Moves the subscriptions of a renamed feed over to its new URLs
+    pub fn rename_feed(&self, feed_id: &str, new_feed_id: &str) -> std::io::Result<()> {
+        let mut subscriptions = self.subscriptions.lock().unwrap();
+        let mut moved = 0;
+        let updated: Vec<Subscription> = subscriptions
+            .iter()
+            .cloned()
+            .map(|mut subscription| {
+                if topic_feed(&subscription.topic).is_some_and(|(id, _)| id == feed_id)
+                    && let Some(topic) = renamed_topic(&subscription.topic, new_feed_id)
+                {
+                    subscription.topic = topic;
+                    moved += 1;
+                }
+                subscription
+            })
+            .collect();
+        if moved == 0 {
+            return Ok(());
+        }
+        self.save(&updated)?;
+        *subscriptions = updated;
+        info!(
+            "Moved {} WebSub subscriptions from feed '{}' to '{}'",
+            moved, feed_id, new_feed_id
+        );
+        Ok(())
+    }
+
+    // Drops the subscriptions of a deleted feed
+    pub fn remove_feed(&self, feed_id: &str) -> std::io::Result<()> {
+        let mut subscriptions = self.subscriptions.lock().unwrap();
+        let updated: Vec<Subscription> = subscriptions
+            .iter()
+            .filter(|subscription| {
+                topic_feed(&subscription.topic).is_none_or(|(id, _)| id != feed_id)
+            })
+            .cloned()
+            .collect();
+        let removed = subscriptions.len() - updated.len();
+        if removed == 0 {
+            return Ok(());
+        }
+        self.save(&updated)?;
+        *subscriptions = updated;
+        info!(
+            "Dropped {} WebSub subscriptions of deleted feed '{}'",
+            removed, feed_id
+        );
+        Ok(())
+    }
+
+    // Queues the published state of a saved feed for its subscribers
+    pub fn publish(&self, feed_id: &str, channel: &Channel) {
+        let subscriptions: Vec<Subscription> = self
+            .subscriptions()
+            .into_iter()
+            .filter(|subscription| {
+                topic_feed(&subscription.topic).is_some_and(|(id, _)| id == feed_id)
+            })
+            .collect();
+        if subscriptions.is_empty() {
+            return;
+        }
+
+        let job = PushJob {
+            feed_id: feed_id.to_string(),
+            channel: served_channel(channel),
+            subscriptions,
+        };
+        if self.sender.send(job).is_err() {
+            warn!(
+                "WebSub queue is closed, dropping update of feed '{}'",
+                feed_id
+            );
+        }
+    }
+
+    // Starts the task that pushes queued feeds, each subscriber gets its own request
+    pub fn start(self: &Arc<Self>) {
+        let Some(mut receiver) = self.receiver.lock().unwrap().take() else {
+            return;
+        };
+        let hub = Arc::clone(self);
+        tokio::spawn(async move {
+            while let Some(job) = receiver.recv().await {
+                let channel = Arc::new(job.channel);
+                for subscription in job.subscriptions {
+                    tokio::spawn(Arc::clone(&hub).push(
+                        job.feed_id.clone(),
+                        Arc::clone(&channel),
+                        subscription,
+                    ));
+                }
+            }
+        });
+    }
+
+    // Sends the feed in the topic's format, failed pushes are logged and not retried
+    async fn push(
+        self: Arc<Self>,
+        feed_id: String,
+        channel: Arc<Channel>,
+        subscription: Subscription,
+    ) {
+        let Ok(_permit) = Arc::clone(&self.pushes).acquire_owned().await else {
+            return;
+        };
+        let Ok(url) = reqwest::Url::parse(&subscription.topic) else {
+            return;
+        };
+        let Some((_, format)) = topic_feed(&subscription.topic) else {
+            return;
+        };
+        let links = feed_links(
+            &url.origin().ascii_serialization(),
+            subscription.topic.clone(),
+        );
+        let body = render_feed(&feed_id, &channel, format, Some(&links));
+
+        // The callback is checked again, its host may resolve elsewhere by now
+        let client = match self.callback_client(&subscription.callback).await {
+            Ok(client) => client,
+            Err(e) => {
+                warn!(
+                    "WebSub push of feed '{}' to {} refused: {}",
+                    feed_id, subscription.callback, e
+                );
+                return;
+            }
+        };
+        let mut request = client
+            .post(&subscription.callback)
+            .header("Content-Type", format.content_type())
+            .header(
+                "Link",
+                format!(
+                    "<{}>; rel=\"hub\", <{}>; rel=\"self\"",
+                    links.hub_url, links.self_url
+                ),
+            );
+        if let Some(secret) = &subscription.secret {
+            request = request.header("X-Hub-Signature", signature(secret, body.as_bytes()));
+        }
+        match request.body(body).send().await {
+            Ok(response) if response.status().is_success() => {
+                debug!("Pushed feed '{}' to {}", feed_id, subscription.callback);
+            }
+            Ok(response) => warn!(
+                "WebSub push of feed '{}' to {} failed with status {}",
+                feed_id,
+                subscription.callback,
+                response.status()
+            ),
+            Err(e) => warn!(
+                "WebSub push of feed '{}' to {} failed: {}",
+                feed_id, subscription.callback, e
+            ),
+        }
+    }
+}
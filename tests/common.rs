@@ -1,11 +1,25 @@
-use feed_crafter::common::FileSystem;
+use feed_crafter::attachments::AttachmentStore;
+use feed_crafter::audit::AuditLog;
+use feed_crafter::auth::ApiKeyStore;
+use feed_crafter::backups::BackupStore;
+use feed_crafter::cache::FeedCache;
+use feed_crafter::common::{AppState, FileSystem};
+use feed_crafter::session::{SessionStore, UserStore};
+use feed_crafter::storage::SqliteStorage;
+use feed_crafter::stream::EventStream;
+use feed_crafter::webhooks::WebhookStore;
+use feed_crafter::websub::WebSubHub;
+use rss::Channel;
+use std::collections::BTreeMap;
 use std::env;
-use std::sync::Mutex;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
 #[allow(dead_code)]
 static ENV_MUTEX: Mutex<()> = Mutex::new(());
 
 #[cfg(test)]
+#[derive(Default)]
 pub struct TempEnv {
     vars: Vec<(String, Option<String>)>,
 }
@@ -56,10 +70,14 @@ pub fn acquire_env_lock() -> std::sync::MutexGuard<'static, ()> {
 }
 
 #[cfg(test)]
+#[derive(Default)]
 pub struct MockFileSystem {
     pub file_exists: bool,
     pub file_content: Option<String>,
     pub written_files: std::cell::RefCell<std::collections::HashMap<String, String>>,
+    pub renamed_files: std::cell::RefCell<Vec<(String, String)>>,
+    // Simulates a crash or full disk, writes stop after this many bytes and fail
+    pub fail_writes_after: Option<usize>,
 }
 
 #[cfg(test)]
@@ -70,6 +88,8 @@ impl MockFileSystem {
             file_exists: false,
             file_content: None,
             written_files: std::cell::RefCell::new(std::collections::HashMap::new()),
+            renamed_files: std::cell::RefCell::new(Vec::new()),
+            fail_writes_after: None,
         }
     }
 
@@ -79,6 +99,8 @@ impl MockFileSystem {
             file_exists: true,
             file_content: Some(content),
             written_files: std::cell::RefCell::new(std::collections::HashMap::new()),
+            renamed_files: std::cell::RefCell::new(Vec::new()),
+            fail_writes_after: None,
         }
     }
 
@@ -88,6 +110,14 @@ impl MockFileSystem {
         self.written_files.borrow().get(path).cloned()
     }
 
+    #[allow(dead_code)]
+    pub fn with_failing_writes(bytes_written: usize) -> Self {
+        Self {
+            fail_writes_after: Some(bytes_written),
+            ..Self::new()
+        }
+    }
+
     #[allow(dead_code)]
     pub fn was_file_written(&self, path: &str) -> bool {
         self.written_files.borrow().contains_key(path)
@@ -98,11 +128,6 @@ impl MockFileSystem {
 impl FileSystem for MockFileSystem {
     type Reader = std::io::Cursor<Vec<u8>>;
 
-    #[allow(dead_code)]
-    fn exists(&self, _path: &str) -> bool {
-        self.file_exists
-    }
-
     #[allow(dead_code)]
     fn open(&self, _path: &str) -> Result<Self::Reader, std::io::Error> {
         match &self.file_content {
@@ -116,9 +141,94 @@ impl FileSystem for MockFileSystem {
 
     #[allow(dead_code)]
     fn write(&self, path: &str, contents: &str) -> Result<(), std::io::Error> {
+        if let Some(limit) = self.fail_writes_after {
+            // Leave a truncated file behind, like an interrupted write would
+            let partial: String = contents.chars().take(limit).collect();
+            self.written_files
+                .borrow_mut()
+                .insert(path.to_string(), partial);
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::StorageFull,
+                "No space left on device",
+            ));
+        }
         self.written_files
             .borrow_mut()
             .insert(path.to_string(), contents.to_string());
         Ok(())
     }
+
+    #[allow(dead_code)]
+    fn remove(&self, path: &str) -> Result<(), std::io::Error> {
+        self.written_files.borrow_mut().remove(path);
+        Ok(())
+    }
+
+    #[allow(dead_code)]
+    fn rename(&self, from: &str, to: &str) -> Result<(), std::io::Error> {
+        let mut written_files = self.written_files.borrow_mut();
+        if let Some(contents) = written_files.remove(from) {
+            written_files.insert(to.to_string(), contents);
+        }
+        self.renamed_files
+            .borrow_mut()
+            .push((from.to_string(), to.to_string()));
+        Ok(())
+    }
+
+    #[allow(dead_code)]
+    fn list(&self, _dir: &str) -> Result<Vec<String>, std::io::Error> {
+        if self.file_exists {
+            Ok(vec!["feed.xml".to_string()])
+        } else {
+            Ok(Vec::new())
+        }
+    }
+}
+
+// Application state backed by an in-memory SQLite database
+#[cfg(test)]
+#[allow(dead_code)]
+pub fn test_app_state(feeds: BTreeMap<String, Channel>) -> AppState {
+    AppState {
+        feeds: Arc::new(Mutex::new(feeds)),
+        storage: Arc::new(SqliteStorage::open(":memory:").unwrap()),
+        api_keys: Arc::new(Mutex::new(ApiKeyStore::load().unwrap())),
+        users: Arc::new(Mutex::new(UserStore::load().unwrap())),
+        sessions: Arc::new(Mutex::new(SessionStore::new(3600))),
+        attachments: Arc::new(AttachmentStore::new(
+            env::temp_dir().join(format!("feed-crafter-attachments-{}", uuid::Uuid::new_v4())),
+            1024,
+            vec!["image/*".to_string(), "application/pdf".to_string()],
+        )),
+        backups: Arc::new(BackupStore::new(
+            env::temp_dir().join(format!("feed-crafter-backups-{}", uuid::Uuid::new_v4())),
+            3,
+        )),
+        audit: Arc::new(AuditLog::new(
+            env::temp_dir().join(format!("feed-crafter-audit-{}.jsonl", uuid::Uuid::new_v4())),
+        )),
+        // Retries are quick so deliveries can be tested against a local server
+        webhooks: Arc::new(
+            WebhookStore::new(
+                env::temp_dir().join(format!(
+                    "feed-crafter-webhooks-{}.json",
+                    uuid::Uuid::new_v4()
+                )),
+                3,
+                Duration::from_millis(10),
+            )
+            .unwrap(),
+        ),
+        websub: Arc::new(
+            WebSubHub::new(
+                env::temp_dir().join(format!("feed-crafter-websub-{}.json", uuid::Uuid::new_v4())),
+                3600,
+                false,
+            )
+            .unwrap(),
+        ),
+        events: Arc::new(EventStream::new()),
+        feed_cache: Arc::new(FeedCache::new()),
+    }
 }
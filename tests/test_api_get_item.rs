@@ -0,0 +1,99 @@
+mod common;
+
+use axum::{
+    Router,
+    body::Body,
+    http::{Request, StatusCode, header},
+    routing::get,
+};
+use common::test_app_state;
+use feed_crafter::api::api_get_item;
+use feed_crafter::common::{DEFAULT_FEED, FeedSettings, create_feed_from_settings, create_item};
+use std::collections::BTreeMap;
+use tower::ServiceExt;
+
+fn app_with_item() -> (Router, String) {
+    let mut channel = create_feed_from_settings(&FeedSettings {
+        id: DEFAULT_FEED.to_string(),
+        title: "Notices".to_string(),
+        link: "https://example.com".to_string(),
+        description: "House notices".to_string(),
+        max_item_age_seconds: None,
+        max_items: None,
+        max_served_items: None,
+    });
+    let item = create_item("Maintenance tonight".to_string(), None, None);
+    let id = item.guid().unwrap().value().to_string();
+    channel.set_items(vec![item]);
+
+    let mut feeds = BTreeMap::new();
+    feeds.insert(DEFAULT_FEED.to_string(), channel);
+    let app = Router::new()
+        .route("/api/items/{id}", get(api_get_item))
+        .with_state(test_app_state(feeds));
+    (app, id)
+}
+
+fn get_request(uri: &str, if_none_match: Option<&str>) -> Request<Body> {
+    let mut request = Request::get(uri);
+    if let Some(etag) = if_none_match {
+        request = request.header(header::IF_NONE_MATCH, etag);
+    }
+    request.body(Body::empty()).unwrap()
+}
+
+#[tokio::test]
+async fn test_get_item_returns_item_with_etag() {
+    let (app, id) = app_with_item();
+
+    let response = app
+        .oneshot(get_request(&format!("/api/items/{}", id), None))
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(response.headers().contains_key(header::ETAG));
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["data"]["id"], id);
+    assert_eq!(json["data"]["title"], "Maintenance tonight");
+}
+
+#[tokio::test]
+async fn test_get_item_not_modified_for_matching_etag() {
+    let (app, id) = app_with_item();
+    let uri = format!("/api/items/{}", id);
+
+    let response = app.clone().oneshot(get_request(&uri, None)).await.unwrap();
+    let etag = response.headers()[header::ETAG]
+        .to_str()
+        .unwrap()
+        .to_string();
+
+    let response = app
+        .clone()
+        .oneshot(get_request(&uri, Some(&etag)))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+
+    let response = app
+        .oneshot(get_request(&uri, Some("\"stale\"")))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_get_missing_item_is_not_found() {
+    let (app, _) = app_with_item();
+
+    let response = app
+        .oneshot(get_request("/api/items/missing", None))
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
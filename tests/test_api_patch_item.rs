@@ -0,0 +1,215 @@
+mod common;
+
+use axum::{
+    Router,
+    body::Body,
+    http::{Request, StatusCode, header},
+    response::Response,
+    routing::get,
+};
+use common::test_app_state;
+use feed_crafter::api::{api_get_item, api_patch_item};
+use feed_crafter::audit::{AuditAction, AuditFilter, AuditSource};
+use feed_crafter::common::{
+    AppState, DEFAULT_FEED, FeedSettings, ItemFields, create_feed_from_settings, create_item,
+    create_item_from_fields, is_item_scheduled, schedule_item,
+};
+use rss::{Category, Item};
+use std::collections::BTreeMap;
+use tower::ServiceExt;
+
+fn app_with_item() -> (Router, String) {
+    let item = create_item_from_fields(ItemFields {
+        title: "Water outage".to_string(),
+        description: Some("Water is off until noon".to_string()),
+        link: Some("https://example.com/water".to_string()),
+        categories: vec![Category {
+            name: "maintenance".to_string(),
+            domain: None,
+        }],
+        ..Default::default()
+    });
+    let id = item.guid().unwrap().value().to_string();
+    (app_for(vec![item]).0, id)
+}
+
+fn app_for(items: Vec<Item>) -> (Router, AppState) {
+    let mut channel = create_feed_from_settings(&FeedSettings {
+        id: DEFAULT_FEED.to_string(),
+        title: "Notices".to_string(),
+        link: "https://example.com".to_string(),
+        description: "House notices".to_string(),
+        max_item_age_seconds: None,
+        max_items: None,
+        max_served_items: None,
+    });
+    channel.set_items(items);
+
+    let mut feeds = BTreeMap::new();
+    feeds.insert(DEFAULT_FEED.to_string(), channel.clone());
+    let state = test_app_state(feeds);
+    state.storage.save_feed(DEFAULT_FEED, &channel).unwrap();
+    let app = Router::new()
+        .route("/api/items/{id}", get(api_get_item).patch(api_patch_item))
+        .with_state(state.clone());
+    (app, state)
+}
+
+async fn patch(app: &Router, id: &str, if_match: Option<&str>, body: &str) -> Response {
+    let mut request = Request::patch(format!("/api/items/{}", id))
+        .header(header::CONTENT_TYPE, "application/merge-patch+json");
+    if let Some(etag) = if_match {
+        request = request.header(header::IF_MATCH, etag);
+    }
+    app.clone()
+        .oneshot(request.body(Body::from(body.to_string())).unwrap())
+        .await
+        .unwrap()
+}
+
+async fn json_body(response: Response) -> serde_json::Value {
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    serde_json::from_slice(&body).unwrap()
+}
+
+#[tokio::test]
+async fn test_patch_changes_only_given_fields() {
+    let (app, id) = app_with_item();
+
+    let response = patch(
+        &app,
+        &id,
+        None,
+        r#"{"link": "https://example.com/water-update", "description": null}"#,
+    )
+    .await;
+
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(response.headers().contains_key(header::ETAG));
+    let json = json_body(response).await;
+    assert_eq!(json["data"]["id"], id);
+    assert_eq!(json["data"]["title"], "Water outage");
+    assert_eq!(json["data"]["link"], "https://example.com/water-update");
+    assert!(json["data"]["description"].is_null());
+    assert_eq!(json["data"]["categories"][0]["name"], "maintenance");
+    assert!(json["data"]["updated"].is_string());
+}
+
+#[tokio::test]
+async fn test_patch_with_stale_etag_is_rejected() {
+    let (app, id) = app_with_item();
+    let response = app
+        .clone()
+        .oneshot(
+            Request::get(format!("/api/items/{}", id))
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    let etag = response.headers()[header::ETAG]
+        .to_str()
+        .unwrap()
+        .to_string();
+
+    let response = patch(&app, &id, Some(&etag), r#"{"title": "First edit"}"#).await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    // The first edit changed the item, so the original ETag no longer matches
+    let response = patch(&app, &id, Some(&etag), r#"{"title": "Second edit"}"#).await;
+    assert_eq!(response.status(), StatusCode::PRECONDITION_FAILED);
+}
+
+#[tokio::test]
+async fn test_patch_with_weak_etag_is_rejected() {
+    let (app, id) = app_with_item();
+    let response = app
+        .clone()
+        .oneshot(
+            Request::get(format!("/api/items/{}", id))
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    let etag = response.headers()[header::ETAG]
+        .to_str()
+        .unwrap()
+        .to_string();
+
+    // If-Match uses the strong comparison, so a weak validator never matches
+    let weak = format!("W/{}", etag);
+    let response = patch(&app, &id, Some(&weak), r#"{"title": "Edit"}"#).await;
+    assert_eq!(response.status(), StatusCode::PRECONDITION_FAILED);
+    let response = patch(&app, &id, Some(&etag), r#"{"title": "Edit"}"#).await;
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_patch_with_null_publish_at_publishes_the_item() {
+    let mut item = create_item_from_fields(ItemFields {
+        title: "Planned maintenance".to_string(),
+        ..Default::default()
+    });
+    schedule_item(&mut item, chrono::Utc::now() + chrono::Duration::days(1));
+    let id = item.guid().unwrap().value().to_string();
+    // Already due, but left for the scheduler
+    let mut due = create_item("Due".to_string(), None, None);
+    schedule_item(&mut due, chrono::Utc::now() - chrono::Duration::minutes(1));
+    let (app, state) = app_for(vec![
+        due,
+        item,
+        create_item("Published".to_string(), None, None),
+    ]);
+
+    // Patching another field keeps the schedule
+    let response = patch(&app, &id, None, r#"{"title": "Maintenance"}"#).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(json_body(response).await["data"]["scheduled"], true);
+
+    let response = patch(&app, &id, None, r#"{"publish_at": null}"#).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let json = json_body(response).await;
+    assert_eq!(json["data"]["scheduled"], false);
+    assert_eq!(json["data"]["title"], "Maintenance");
+
+    // Only this item is published, moved to the top of the feed in storage too
+    let stored = state.storage.load_feed(DEFAULT_FEED).unwrap();
+    let titles: Vec<&str> = stored.items().iter().filter_map(|i| i.title()).collect();
+    assert_eq!(titles, vec!["Maintenance", "Due", "Published"]);
+    assert!(is_item_scheduled(&stored.items()[1]));
+
+    // As a single update by the request's actor
+    let filter = AuditFilter {
+        item_id: Some(id.clone()),
+        ..Default::default()
+    };
+    let (entries, total) = state.audit.query(&filter, 0, 10).unwrap();
+    assert_eq!(total, 2);
+    assert!(
+        entries.iter().all(|entry| {
+            entry.action == AuditAction::Update && entry.source == AuditSource::Api
+        })
+    );
+}
+
+#[tokio::test]
+async fn test_patch_rejects_invalid_changes() {
+    let (app, id) = app_with_item();
+
+    // Removing the title is a validation error like any other invalid field
+    let response = patch(&app, &id, None, r#"{"title": null}"#).await;
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(
+        json_body(response).await["error"]["fields"][0]["field"],
+        "title"
+    );
+
+    let response = patch(&app, &id, None, r#"{"categories": "maintenance"}"#).await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    let response = patch(&app, "missing", None, r#"{"title": "New"}"#).await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
@@ -0,0 +1,225 @@
+mod common;
+
+use axum::body::Bytes;
+use axum::extract::{Path, State};
+use common::{TempEnv, acquire_env_lock, test_app_state};
+use feed_crafter::attachments::{Upload, attachment_file_name};
+use feed_crafter::audit::AuditActor;
+use feed_crafter::common::{
+    AppState, FeedSettings, ItemExpiry, add_item, cleanup_old_items, create_feed_from_settings,
+    create_item, delete_item, restore_feed, set_item_expiry,
+};
+use rss::Item;
+use std::collections::BTreeMap;
+
+fn png_upload(size: usize) -> Upload {
+    Upload {
+        file_name: "photo.PNG".to_string(),
+        content_type: Some("image/png".to_string()),
+        data: Bytes::from(vec![0u8; size]),
+    }
+}
+
+fn empty_state() -> AppState {
+    let channel = create_feed_from_settings(&FeedSettings {
+        id: "photos".to_string(),
+        title: "Photos".to_string(),
+        link: "https://example.com".to_string(),
+        description: "Photo feed".to_string(),
+        max_item_age_seconds: None,
+        max_items: None,
+        max_served_items: None,
+    });
+    let mut feeds = BTreeMap::new();
+    feeds.insert("photos".to_string(), channel.clone());
+    let state = test_app_state(feeds);
+    state.storage.save_feed("photos", &channel).unwrap();
+    state
+}
+
+fn add_item_with_upload(state: &AppState) -> (Item, std::path::PathBuf) {
+    let enclosure = state.attachments.save(&png_upload(100)).unwrap();
+    let path = state
+        .attachments
+        .path(attachment_file_name(&enclosure.url).unwrap())
+        .unwrap();
+    let mut item = create_item("Photo".to_string(), None, None);
+    item.set_enclosure(enclosure);
+    add_item(
+        State(state.clone()),
+        Path("photos".to_string()),
+        item.clone(),
+        &AuditActor::api(None),
+    )
+    .unwrap()
+    .unwrap();
+    (item, path)
+}
+
+#[test]
+fn test_save_sets_enclosure_from_upload() {
+    let state = empty_state();
+
+    let enclosure = state.attachments.save(&png_upload(100)).unwrap();
+
+    // Saved root-relative, the request's host never ends up in the feed
+    assert!(enclosure.url.starts_with("/attachments/"));
+    assert!(enclosure.url.ends_with(".png"));
+    assert_eq!(enclosure.length, "100");
+    assert_eq!(enclosure.mime_type, "image/png");
+
+    let path = state
+        .attachments
+        .path(attachment_file_name(&enclosure.url).unwrap())
+        .unwrap();
+    assert_eq!(std::fs::read(path).unwrap().len(), 100);
+}
+
+#[test]
+fn test_save_rejects_large_and_disallowed_uploads() {
+    let state = empty_state();
+
+    assert!(state.attachments.save(&png_upload(2048)).is_err());
+
+    // The extension decides the type, not the claimed content type
+    let html = Upload {
+        file_name: "page.html".to_string(),
+        content_type: Some("image/png".to_string()),
+        data: Bytes::from_static(b"<script></script>"),
+    };
+    assert!(state.attachments.save(&html).is_err());
+}
+
+#[test]
+fn test_path_rejects_names_outside_the_directory() {
+    let state = empty_state();
+
+    assert!(state.attachments.path("../users.json").is_none());
+    assert!(state.attachments.path("photo.png").is_none());
+    assert!(
+        state
+            .attachments
+            .path("67e55044-10b1-426f-9247-bb680e5fe0c8.png")
+            .is_some()
+    );
+}
+
+#[test]
+fn test_purging_deleted_item_removes_its_attachment() {
+    let _lock = acquire_env_lock();
+    let mut env = TempEnv::new();
+    env.set("TRASH_RETENTION_SECONDS", "0");
+    let state = empty_state();
+    let (item, path) = add_item_with_upload(&state);
+    assert!(path.exists());
+
+    delete_item(
+        State(state.clone()),
+        Path((
+            "photos".to_string(),
+            item.guid().unwrap().value().to_string(),
+        )),
+        &AuditActor::api(None),
+    )
+    .unwrap()
+    .unwrap();
+    // The item can still be restored from the trash, so its file is kept until it is purged
+    assert!(path.exists());
+
+    assert_eq!(cleanup_old_items(&state), 1);
+    assert!(!path.exists());
+}
+
+#[test]
+fn test_purging_keeps_attachment_still_referenced() {
+    let _lock = acquire_env_lock();
+    let mut env = TempEnv::new();
+    env.set("TRASH_RETENTION_SECONDS", "0");
+    let state = empty_state();
+    let (item, path) = add_item_with_upload(&state);
+
+    // A second item sharing the same file keeps it alive
+    let mut other = create_item("Copy".to_string(), None, None);
+    other.set_enclosure(item.enclosure().cloned());
+    add_item(
+        State(state.clone()),
+        Path("photos".to_string()),
+        other,
+        &AuditActor::api(None),
+    )
+    .unwrap()
+    .unwrap();
+
+    delete_item(
+        State(state.clone()),
+        Path((
+            "photos".to_string(),
+            item.guid().unwrap().value().to_string(),
+        )),
+        &AuditActor::api(None),
+    )
+    .unwrap()
+    .unwrap();
+
+    assert_eq!(cleanup_old_items(&state), 1);
+    assert!(path.exists());
+}
+
+#[test]
+fn test_cleanup_old_items_removes_attachments() {
+    let state = empty_state();
+    let (item, path) = add_item_with_upload(&state);
+    {
+        let mut feeds = state.feeds.lock().unwrap();
+        let channel = feeds.get_mut("photos").unwrap();
+        let mut expired = item.clone();
+        set_item_expiry(
+            &mut expired,
+            Some(ItemExpiry::At(
+                chrono::Utc::now() - chrono::Duration::hours(1),
+            )),
+        );
+        channel.set_items(vec![expired]);
+    }
+
+    assert_eq!(cleanup_old_items(&state), 1);
+    assert!(!path.exists());
+}
+
+#[test]
+fn test_restore_reports_attachments_removed_since_the_backup() {
+    let _lock = acquire_env_lock();
+    let mut env = TempEnv::new();
+    env.set("TRASH_RETENTION_SECONDS", "0");
+    let state = empty_state();
+    let (item, path) = add_item_with_upload(&state);
+    let backup_id = state.backups.list("photos").unwrap()[0].id.clone();
+    assert!(
+        state
+            .attachments
+            .missing(std::slice::from_ref(&item))
+            .is_empty()
+    );
+
+    delete_item(
+        State(state.clone()),
+        Path((
+            "photos".to_string(),
+            item.guid().unwrap().value().to_string(),
+        )),
+        &AuditActor::api(None),
+    )
+    .unwrap()
+    .unwrap();
+    cleanup_old_items(&state);
+    assert!(!path.exists());
+
+    // The item comes back, but its file doesn't
+    let restored = restore_feed(&state, "photos", &backup_id, &AuditActor::api(None))
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        state.attachments.missing(restored.items()),
+        vec![item.enclosure().unwrap().url().to_string()]
+    );
+}
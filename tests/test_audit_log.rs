@@ -0,0 +1,213 @@
+mod common;
+
+use axum::extract::{Path, State};
+use axum::{
+    Router,
+    body::Body,
+    http::{Request, StatusCode},
+    routing::get,
+};
+use common::test_app_state;
+use feed_crafter::api::api_get_audit;
+use feed_crafter::audit::{
+    AuditAction, AuditActor, AuditEntry, AuditFilter, AuditLog, AuditSource,
+};
+use feed_crafter::common::{
+    AppState, FeedSettings, ItemExpiry, ItemFields, add_item, cleanup_old_items,
+    create_feed_from_settings, create_item, delete_item, edit_item,
+};
+use std::collections::BTreeMap;
+use std::fs::OpenOptions;
+use std::io::Write;
+use tower::ServiceExt;
+
+fn temp_log() -> AuditLog {
+    AuditLog::new(
+        std::env::temp_dir().join(format!("feed-crafter-audit-{}.jsonl", uuid::Uuid::new_v4())),
+    )
+}
+
+fn alerts_state() -> AppState {
+    let channel = create_feed_from_settings(&FeedSettings {
+        id: "alerts".to_string(),
+        title: "Alerts".to_string(),
+        link: "https://example.com".to_string(),
+        description: "System alerts".to_string(),
+        max_item_age_seconds: None,
+        max_items: None,
+        max_served_items: None,
+    });
+    let mut feeds = BTreeMap::new();
+    feeds.insert("alerts".to_string(), channel.clone());
+    let state = test_app_state(feeds);
+    state.storage.save_feed("alerts", &channel).unwrap();
+    state
+}
+
+#[test]
+fn test_query_filters_newest_first() {
+    let log = temp_log();
+    let alice = AuditActor::new(Some("alice".to_string()), AuditSource::Web);
+    let first = create_item("First".to_string(), None, None);
+    let second = create_item("Second".to_string(), None, None);
+    log.record(&[
+        AuditEntry::item(AuditAction::Create, "alerts", &alice, None, Some(&first)),
+        AuditEntry::item(
+            AuditAction::Create,
+            "news",
+            &AuditActor::api(None),
+            None,
+            Some(&second),
+        ),
+    ]);
+    log.record(&[AuditEntry::item(
+        AuditAction::Delete,
+        "alerts",
+        &alice,
+        Some(&first),
+        None,
+    )]);
+
+    let (entries, total) = log.query(&AuditFilter::default(), 0, 10).unwrap();
+    assert_eq!(total, 3);
+    assert_eq!(entries[0].action, AuditAction::Delete);
+    assert_eq!(entries[2].title(), Some("First"));
+
+    let filter = AuditFilter {
+        feed_id: Some("alerts".to_string()),
+        actor: Some("alice".to_string()),
+        ..AuditFilter::default()
+    };
+    let (entries, total) = log.query(&filter, 1, 10).unwrap();
+    assert_eq!(total, 2);
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].action, AuditAction::Create);
+    assert_eq!(
+        entries[0].item_id.as_deref(),
+        first.guid().map(|g| g.value())
+    );
+
+    // A line torn by a crash is skipped rather than hiding the rest of the log
+    let path =
+        std::env::temp_dir().join(format!("feed-crafter-audit-{}.jsonl", uuid::Uuid::new_v4()));
+    let torn = AuditLog::new(path.clone());
+    torn.record(&[AuditEntry::restore(
+        "alerts",
+        &AuditActor::api(None),
+        "20240101T000000000000Z",
+    )]);
+    let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+    file.write_all(b"{\"timestamp\":").unwrap();
+    let (entries, total) = torn.query(&AuditFilter::default(), 0, 10).unwrap();
+    assert_eq!(total, 1);
+    assert_eq!(
+        entries[0].backup_id.as_deref(),
+        Some("20240101T000000000000Z")
+    );
+}
+
+#[test]
+fn test_changes_record_actor_and_item_state() {
+    let state = alerts_state();
+    let alice = AuditActor::new(Some("alice".to_string()), AuditSource::Web);
+    let guid = add_item(
+        State(state.clone()),
+        Path("alerts".to_string()),
+        create_item("Outage".to_string(), None, None),
+        &alice,
+    )
+    .unwrap()
+    .unwrap();
+    edit_item(
+        State(state.clone()),
+        Path(("alerts".to_string(), guid.value().to_string())),
+        ItemFields {
+            title: "Outage resolved".to_string(),
+            ..Default::default()
+        },
+        Some(ItemExpiry::parse("2000-01-01T00:00:00Z").unwrap()),
+        None,
+        &AuditActor::api(None),
+    )
+    .unwrap()
+    .unwrap();
+    // Missing items change nothing and record nothing
+    delete_item(
+        State(state.clone()),
+        Path(("alerts".to_string(), "missing".to_string())),
+        &alice,
+    )
+    .unwrap();
+    assert_eq!(cleanup_old_items(&state), 1);
+
+    let (entries, total) = state.audit.query(&AuditFilter::default(), 0, 10).unwrap();
+    assert_eq!(total, 3);
+
+    let expired = &entries[0];
+    assert_eq!(expired.action, AuditAction::Expire);
+    assert_eq!(expired.source, AuditSource::Cleanup);
+    assert!(expired.actor.is_none());
+    assert_eq!(expired.title(), Some("Outage resolved"));
+
+    let updated = &entries[1];
+    assert_eq!(updated.action, AuditAction::Update);
+    assert_eq!(updated.source, AuditSource::Api);
+    assert_eq!(updated.before.as_ref().unwrap().title(), Some("Outage"));
+    assert_eq!(
+        updated.after.as_ref().unwrap().title(),
+        Some("Outage resolved")
+    );
+
+    let created = &entries[2];
+    assert_eq!(created.action, AuditAction::Create);
+    assert_eq!(created.actor.as_deref(), Some("alice"));
+    assert_eq!(created.item_id.as_deref(), Some(guid.value()));
+    assert!(created.before.is_none());
+}
+
+#[tokio::test]
+async fn test_api_audit_filters_entries() {
+    let state = alerts_state();
+    for title in ["One", "Two", "Three"] {
+        add_item(
+            State(state.clone()),
+            Path("alerts".to_string()),
+            create_item(title.to_string(), None, None),
+            &AuditActor::api(None),
+        )
+        .unwrap()
+        .unwrap();
+    }
+    let app = Router::new()
+        .route("/api/audit", get(api_get_audit))
+        .with_state(state);
+
+    let response = app
+        .clone()
+        .oneshot(
+            Request::get("/api/audit?feed=alerts&action=create&limit=2")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["total"], 3);
+    assert_eq!(json["data"].as_array().unwrap().len(), 2);
+    assert_eq!(json["data"][0]["after"]["title"], "Three");
+    assert_eq!(json["data"][0]["source"], "api");
+
+    let response = app
+        .oneshot(
+            Request::get("/api/audit?action=rename")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
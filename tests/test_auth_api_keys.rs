@@ -0,0 +1,269 @@
+mod common;
+
+use axum::{
+    Router,
+    body::Body,
+    http::{Method, Request, StatusCode, header},
+    middleware,
+    routing::{get, post},
+};
+use common::{TempEnv, acquire_env_lock, test_app_state};
+use feed_crafter::api::{api_add_item, api_add_key, api_get_items};
+use feed_crafter::auth::{ApiKeyStore, Scope, hash_token, require_api_key, required_scope};
+use feed_crafter::common::{AppState, FeedSettings, create_feed_from_settings};
+use std::collections::BTreeMap;
+use std::fs;
+use std::sync::{Arc, Mutex};
+use tower::ServiceExt;
+
+fn temp_keys_file(name: &str) -> String {
+    let path = std::env::temp_dir().join(format!("feed-crafter-{}.json", name));
+    let _ = fs::remove_file(&path);
+    path.to_string_lossy().to_string()
+}
+
+#[test]
+fn test_scope_ordering_and_parsing() {
+    assert!(Scope::Admin > Scope::Write);
+    assert!(Scope::Write > Scope::Read);
+    assert_eq!("ADMIN".parse::<Scope>(), Ok(Scope::Admin));
+    assert_eq!(" read ".parse::<Scope>(), Ok(Scope::Read));
+    assert!("owner".parse::<Scope>().is_err());
+}
+
+#[test]
+fn test_hash_token_is_stable_sha256() {
+    assert_eq!(
+        hash_token("secret"),
+        "2bb80d537b1da3e38bd30361aa855686bde0eacd7162fef6a25fe97bf527a25b"
+    );
+    assert_ne!(hash_token("secret"), hash_token("Secret"));
+}
+
+#[test]
+fn test_required_scope() {
+    assert_eq!(required_scope(&Method::GET, "/api/items"), Scope::Read);
+    assert_eq!(required_scope(&Method::GET, "/api/feeds"), Scope::Read);
+    assert_eq!(required_scope(&Method::POST, "/api/items"), Scope::Write);
+    assert_eq!(
+        required_scope(&Method::DELETE, "/api/feeds/news/items/abc"),
+        Scope::Write
+    );
+    assert_eq!(required_scope(&Method::POST, "/api/feeds"), Scope::Admin);
+    assert_eq!(
+        required_scope(&Method::DELETE, "/api/feeds/news"),
+        Scope::Admin
+    );
+    assert_eq!(required_scope(&Method::GET, "/api/keys"), Scope::Admin);
+    assert_eq!(required_scope(&Method::GET, "/api/backups"), Scope::Admin);
+    assert_eq!(required_scope(&Method::GET, "/api/audit"), Scope::Admin);
+    assert_eq!(
+        required_scope(&Method::DELETE, "/api/webhooks/abc"),
+        Scope::Admin
+    );
+    assert_eq!(
+        required_scope(&Method::GET, "/api/feeds/news/backups"),
+        Scope::Admin
+    );
+    // A feed can be named like the backups route
+    assert_eq!(
+        required_scope(&Method::GET, "/api/feeds/backups/items"),
+        Scope::Read
+    );
+    // A feed whose id starts with "items" is still managed with admin keys only
+    assert_eq!(
+        required_scope(&Method::PUT, "/api/feeds/items-x"),
+        Scope::Admin
+    );
+    assert_eq!(
+        required_scope(&Method::DELETE, "/api/feeds/items-x"),
+        Scope::Admin
+    );
+    assert_eq!(
+        required_scope(&Method::POST, "/api/feeds/items-x/items"),
+        Scope::Write
+    );
+}
+
+#[test]
+fn test_load_keys_from_environment() {
+    let _guard = acquire_env_lock();
+    let mut temp_env = TempEnv::new();
+    temp_env.set(
+        "API_KEYS",
+        "reader:read:token-one, bot:write:token-two,broken",
+    );
+    temp_env.set("API_KEYS_FILE", &temp_keys_file("env-keys"));
+
+    let store = ApiKeyStore::load().unwrap();
+
+    assert!(store.is_enabled());
+    assert_eq!(store.keys().len(), 2);
+    let key = store.authenticate("token-two").unwrap();
+    assert_eq!(key.id, "env:bot");
+    assert_eq!(key.scope, Scope::Write);
+    assert!(store.authenticate("token-three").is_none());
+}
+
+#[test]
+fn test_created_keys_are_persisted_hashed() {
+    let _guard = acquire_env_lock();
+    let mut temp_env = TempEnv::new();
+    let path = temp_keys_file("managed-keys");
+    temp_env.set("API_KEYS", "");
+    temp_env.set("API_KEYS_FILE", &path);
+
+    let mut store = ApiKeyStore::load().unwrap();
+    assert!(!store.is_enabled());
+    let (key, token) = store.create("admin".to_string(), Scope::Admin).unwrap();
+    assert!(token.starts_with("fc_"));
+
+    let contents = fs::read_to_string(&path).unwrap();
+    assert!(!contents.contains(&token));
+    assert!(contents.contains(&hash_token(&token)));
+
+    let reloaded = ApiKeyStore::load().unwrap();
+    assert_eq!(reloaded.authenticate(&token).unwrap().id, key.id);
+
+    let mut reloaded = reloaded;
+    assert!(reloaded.delete(&key.id).unwrap().is_some());
+    assert!(reloaded.authenticate(&token).is_none());
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_failed_key_save_leaves_keys_unchanged() {
+    let _guard = acquire_env_lock();
+    let mut temp_env = TempEnv::new();
+    let path = std::env::temp_dir()
+        .join("feed-crafter-missing-dir")
+        .join("keys.json");
+    temp_env.set("API_KEYS", "");
+    temp_env.set("API_KEYS_FILE", &path.to_string_lossy());
+
+    let mut store = ApiKeyStore::load().unwrap();
+    assert!(store.create("admin".to_string(), Scope::Admin).is_err());
+    assert!(store.keys().is_empty());
+    assert!(!store.is_enabled());
+}
+
+// App state for an instance without any API keys, optionally with a bootstrap token
+fn unconfigured_state(bootstrap_token: Option<&str>) -> AppState {
+    let _guard = acquire_env_lock();
+    let mut temp_env = TempEnv::new();
+    temp_env.set("API_KEYS", "");
+    temp_env.set(
+        "API_KEYS_FILE",
+        &temp_keys_file(&uuid::Uuid::new_v4().to_string()),
+    );
+    temp_env.set("API_BOOTSTRAP_TOKEN", bootstrap_token.unwrap_or_default());
+
+    let mut feeds = BTreeMap::new();
+    feeds.insert(
+        "feed".to_string(),
+        create_feed_from_settings(&FeedSettings {
+            id: "feed".to_string(),
+            title: "Feed".to_string(),
+            link: "https://example.com".to_string(),
+            description: "Test feed".to_string(),
+            max_item_age_seconds: None,
+            max_items: None,
+            max_served_items: None,
+        }),
+    );
+    let mut state = test_app_state(feeds.clone());
+    state.storage.save_feed("feed", &feeds["feed"]).unwrap();
+    state.api_keys = Arc::new(Mutex::new(ApiKeyStore::load().unwrap()));
+    state
+}
+
+fn api_app(state: AppState) -> Router {
+    Router::new()
+        .route("/api/items", get(api_get_items))
+        .route("/api/items", post(api_add_item))
+        .route("/api/keys", post(api_add_key))
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            require_api_key,
+        ))
+        .with_state(state)
+}
+
+fn json_post(uri: &str, token: Option<&str>, body: &str) -> Request<Body> {
+    let mut request = Request::post(uri).header(header::CONTENT_TYPE, "application/json");
+    if let Some(token) = token {
+        request = request.header(header::AUTHORIZATION, format!("Bearer {}", token));
+    }
+    request.body(Body::from(body.to_string())).unwrap()
+}
+
+#[tokio::test]
+async fn test_unconfigured_api_is_read_only() {
+    let app = api_app(unconfigured_state(None));
+
+    let response = app
+        .clone()
+        .oneshot(json_post(
+            "/api/keys",
+            None,
+            r#"{"name":"intruder","scope":"admin"}"#,
+        ))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    let response = app
+        .clone()
+        .oneshot(json_post("/api/items", None, r#"{"title":"Spam"}"#))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    let response = app
+        .oneshot(Request::get("/api/items").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_bootstrap_token_creates_only_the_first_key() {
+    let state = unconfigured_state(Some("bootstrap-secret"));
+    let app = api_app(state.clone());
+
+    // The bootstrap token can't be used for anything but creating keys
+    let response = app
+        .clone()
+        .oneshot(json_post(
+            "/api/items",
+            Some("bootstrap-secret"),
+            r#"{"title":"Spam"}"#,
+        ))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    let response = app
+        .clone()
+        .oneshot(json_post(
+            "/api/keys",
+            Some("bootstrap-secret"),
+            r#"{"name":"owner","scope":"admin"}"#,
+        ))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(state.api_keys.lock().unwrap().is_enabled());
+
+    // Once a key exists the bootstrap token stops working
+    let response = app
+        .oneshot(json_post(
+            "/api/keys",
+            Some("bootstrap-secret"),
+            r#"{"name":"second","scope":"admin"}"#,
+        ))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    assert_eq!(state.api_keys.lock().unwrap().keys().len(), 1);
+}
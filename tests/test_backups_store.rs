@@ -0,0 +1,82 @@
+use feed_crafter::backups::BackupStore;
+use feed_crafter::common::{FeedSettings, create_feed_from_settings};
+use rss::Channel;
+use std::env;
+
+fn temp_store(retention: usize) -> BackupStore {
+    BackupStore::new(
+        env::temp_dir().join(format!("feed-crafter-backups-{}", uuid::Uuid::new_v4())),
+        retention,
+    )
+}
+
+fn channel(title: &str) -> Channel {
+    create_feed_from_settings(&FeedSettings {
+        id: "alerts".to_string(),
+        title: title.to_string(),
+        link: "https://example.com".to_string(),
+        description: "System alerts".to_string(),
+        max_item_age_seconds: None,
+        max_items: None,
+        max_served_items: None,
+    })
+}
+
+#[test]
+fn test_snapshot_can_be_read_back() {
+    let store = temp_store(5);
+
+    let backup = store
+        .snapshot("alerts", &channel("First"))
+        .unwrap()
+        .unwrap();
+
+    let backups = store.list("alerts").unwrap();
+    assert_eq!(backups.len(), 1);
+    assert_eq!(backups[0].id, backup.id);
+    assert_eq!(backups[0].size, backup.size);
+    let restored = store.read("alerts", &backup.id).unwrap().unwrap();
+    assert_eq!(restored.title(), "First");
+}
+
+#[test]
+fn test_snapshots_rotate_beyond_retention() {
+    let store = temp_store(2);
+
+    for title in ["First", "Second", "Third"] {
+        store.snapshot("alerts", &channel(title)).unwrap();
+    }
+
+    // Newest first, the oldest snapshot was removed
+    let backups = store.list("alerts").unwrap();
+    let titles: Vec<String> = backups
+        .iter()
+        .map(|b| {
+            store
+                .read("alerts", &b.id)
+                .unwrap()
+                .unwrap()
+                .title()
+                .to_string()
+        })
+        .collect();
+    assert_eq!(titles, vec!["Third", "Second"]);
+}
+
+#[test]
+fn test_disabled_and_invalid_backups() {
+    let store = temp_store(0);
+    assert!(
+        store
+            .snapshot("alerts", &channel("First"))
+            .unwrap()
+            .is_none()
+    );
+    assert!(store.list("alerts").unwrap().is_empty());
+
+    let store = temp_store(2);
+    store.snapshot("alerts", &channel("First")).unwrap();
+    assert!(store.read("alerts", "missing").unwrap().is_none());
+    assert!(store.read("alerts", "../alerts").unwrap().is_none());
+    assert!(store.list("..").unwrap().is_empty());
+}
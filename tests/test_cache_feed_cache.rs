@@ -0,0 +1,72 @@
+mod common;
+
+use axum::http::{HeaderMap, HeaderValue, header};
+use common::{TempEnv, acquire_env_lock};
+use feed_crafter::attachments::public_base_url;
+use feed_crafter::cache::{FeedCache, MAX_RENDERS, RenderedFeed};
+use feed_crafter::formats::FeedFormat;
+use std::cell::Cell;
+
+fn render(cache: &FeedCache, feed_id: &str, self_url: &str, renders: &Cell<usize>) {
+    cache.get_or_render(feed_id, FeedFormat::Rss, self_url, || {
+        renders.set(renders.get() + 1);
+        RenderedFeed::new(format!("<rss>{}</rss>", self_url), None)
+    });
+}
+
+#[test]
+fn test_feed_cache_evicts_the_least_recently_used_render() {
+    let cache = FeedCache::new();
+    let renders = Cell::new(0);
+    let hot = "https://feeds.example.com/feed.xml";
+    render(&cache, "feed", hot, &renders);
+
+    // Cycling through other URLs drops them one at a time, the render in use stays cached
+    for i in 0..MAX_RENDERS * 2 {
+        render(
+            &cache,
+            "feed",
+            &format!("http://host-{}.example/feed.xml", i),
+            &renders,
+        );
+        render(&cache, "feed", hot, &renders);
+        assert!(cache.len() <= MAX_RENDERS);
+    }
+    assert_eq!(renders.get(), 1 + MAX_RENDERS * 2);
+    assert_eq!(cache.len(), MAX_RENDERS);
+
+    // The oldest of the other renders is gone, the newest is still cached
+    render(&cache, "feed", "http://host-0.example/feed.xml", &renders);
+    assert_eq!(renders.get(), 2 + MAX_RENDERS * 2);
+    render(
+        &cache,
+        "feed",
+        &format!("http://host-{}.example/feed.xml", MAX_RENDERS * 2 - 1),
+        &renders,
+    );
+    assert_eq!(renders.get(), 2 + MAX_RENDERS * 2);
+
+    cache.invalidate("feed");
+    assert!(cache.is_empty());
+}
+
+#[test]
+fn test_public_base_url_normalizes_the_host() {
+    let _guard = acquire_env_lock();
+    let mut env = TempEnv::new();
+    env.set("PUBLIC_URL", "");
+
+    let mut headers = HeaderMap::new();
+    headers.insert(header::HOST, HeaderValue::from_static("Feeds.Example.COM."));
+    headers.insert("X-Forwarded-Proto", HeaderValue::from_static("HTTPS"));
+    assert_eq!(public_base_url(&headers), "https://feeds.example.com");
+
+    // Anything but a plain host falls back to localhost, unknown schemes to http
+    headers.insert(header::HOST, HeaderValue::from_static("evil.example/path"));
+    headers.insert("X-Forwarded-Proto", HeaderValue::from_static("javascript"));
+    assert_eq!(public_base_url(&headers), "http://localhost");
+
+    // PUBLIC_URL wins over the request
+    env.set("PUBLIC_URL", "https://news.example.org/");
+    assert_eq!(public_base_url(&headers), "https://news.example.org");
+}
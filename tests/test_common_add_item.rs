@@ -0,0 +1,53 @@
+mod common;
+
+use axum::extract::{Path, State};
+use common::test_app_state;
+use feed_crafter::audit::AuditActor;
+use feed_crafter::common::{FeedSettings, add_item, create_feed_from_settings, create_item};
+use std::collections::BTreeMap;
+
+#[test]
+fn test_add_item_storage_failure_leaves_feed_unchanged() {
+    let channel = create_feed_from_settings(&FeedSettings {
+        id: "alerts".to_string(),
+        title: "Alerts".to_string(),
+        link: "https://example.com".to_string(),
+        description: "System alerts".to_string(),
+        max_item_age_seconds: None,
+        max_items: None,
+        max_served_items: None,
+    });
+    let item = create_item("Disk full".to_string(), None, None);
+    let mut stored = channel.clone();
+    stored.set_items(vec![item.clone()]);
+
+    let mut feeds = BTreeMap::new();
+    feeds.insert("alerts".to_string(), channel);
+    let state = test_app_state(feeds);
+    // Storage already holds the item, so inserting it again violates its unique GUID
+    state.storage.save_feed("alerts", &stored).unwrap();
+
+    let result = add_item(
+        State(state.clone()),
+        Path("alerts".to_string()),
+        item,
+        &AuditActor::api(None),
+    );
+
+    assert!(result.is_err());
+    assert!(state.feeds.lock().unwrap()["alerts"].items().is_empty());
+}
+
+#[test]
+fn test_add_item_to_missing_feed_returns_none() {
+    let state = test_app_state(BTreeMap::new());
+
+    let result = add_item(
+        State(state),
+        Path("missing".to_string()),
+        create_item("Orphan".to_string(), None, None),
+        &AuditActor::api(None),
+    );
+
+    assert!(result.unwrap().is_none());
+}
@@ -0,0 +1,121 @@
+mod common;
+
+use common::test_app_state;
+use feed_crafter::audit::AuditActor;
+use feed_crafter::common::{
+    AppState, FeedSettings, ItemFields, ItemOperation, apply_item_operations,
+    create_feed_from_settings, create_item, is_item_deleted, published_channel,
+};
+use std::collections::BTreeMap;
+
+fn state_with_items(titles: &[&str]) -> (AppState, Vec<String>) {
+    let mut channel = create_feed_from_settings(&FeedSettings {
+        id: "alerts".to_string(),
+        title: "Alerts".to_string(),
+        link: "https://example.com".to_string(),
+        description: "System alerts".to_string(),
+        max_item_age_seconds: None,
+        max_items: None,
+        max_served_items: None,
+    });
+    let items: Vec<rss::Item> = titles
+        .iter()
+        .map(|title| create_item(title.to_string(), None, None))
+        .collect();
+    let ids = items
+        .iter()
+        .map(|item| item.guid().unwrap().value().to_string())
+        .collect();
+    channel.set_items(items);
+
+    let mut feeds = BTreeMap::new();
+    feeds.insert("alerts".to_string(), channel.clone());
+    let state = test_app_state(feeds);
+    state.storage.save_feed("alerts", &channel).unwrap();
+    (state, ids)
+}
+
+// Titles of the stored items that aren't in the trash
+fn stored_titles(state: &AppState) -> Vec<String> {
+    state
+        .storage
+        .list_items("alerts")
+        .unwrap()
+        .iter()
+        .filter(|item| !is_item_deleted(item))
+        .map(|item| item.title().unwrap().to_string())
+        .collect()
+}
+
+#[test]
+fn test_operations_are_applied_in_order_and_saved() {
+    let (state, ids) = state_with_items(&["First", "Second"]);
+
+    let results = apply_item_operations(
+        &state,
+        "alerts",
+        vec![
+            ItemOperation::Create(Box::new(create_item("Third".to_string(), None, None))),
+            ItemOperation::Update {
+                id: ids[0].clone(),
+                fields: Box::new(ItemFields {
+                    title: "First, edited".to_string(),
+                    ..Default::default()
+                }),
+                expiry: None,
+                publish_at: None,
+            },
+            ItemOperation::Delete { id: ids[1].clone() },
+        ],
+        &AuditActor::api(None),
+    )
+    .unwrap()
+    .unwrap();
+
+    assert!(results.iter().all(Result::is_ok));
+    assert_eq!(
+        results[0].as_ref().unwrap().as_ref().unwrap().title(),
+        Some("Third")
+    );
+    assert!(results[2].as_ref().unwrap().is_none());
+    assert_eq!(stored_titles(&state), vec!["Third", "First, edited"]);
+    // Deleted items are moved to the trash
+    let feeds = state.feeds.lock().unwrap();
+    assert_eq!(published_channel(&feeds["alerts"]).items().len(), 2);
+    assert!(is_item_deleted(&feeds["alerts"].items()[2]));
+}
+
+#[test]
+fn test_failed_operation_applies_nothing() {
+    let (state, ids) = state_with_items(&["First"]);
+
+    let results = apply_item_operations(
+        &state,
+        "alerts",
+        vec![
+            ItemOperation::Delete { id: ids[0].clone() },
+            ItemOperation::Delete {
+                id: "missing".to_string(),
+            },
+        ],
+        &AuditActor::api(None),
+    )
+    .unwrap()
+    .unwrap();
+
+    assert!(results[0].is_ok());
+    assert!(results[1].is_err());
+    assert_eq!(stored_titles(&state), vec!["First"]);
+    assert_eq!(state.feeds.lock().unwrap()["alerts"].items().len(), 1);
+}
+
+#[test]
+fn test_unknown_feed_returns_none() {
+    let (state, _) = state_with_items(&[]);
+
+    assert!(
+        apply_item_operations(&state, "missing", Vec::new(), &AuditActor::api(None))
+            .unwrap()
+            .is_none()
+    );
+}
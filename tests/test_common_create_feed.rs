@@ -13,7 +13,7 @@ fn test_create_feed_success() {
     temp_env.set("CHANNEL_LINK", "https://example.com");
     temp_env.set("CHANNEL_DESCRIPTION", "Test channel description");
 
-    let channel = create_feed();
+    let channel = create_feed().unwrap();
 
     assert_eq!(channel.title(), "Test Channel");
     assert_eq!(channel.link(), "https://example.com");
@@ -26,7 +26,6 @@ fn test_create_feed_success() {
 }
 
 #[test]
-#[should_panic(expected = "Expected a channel title in the environment")]
 fn test_create_feed_missing_title() {
     let _guard = acquire_env_lock();
     let mut temp_env = TempEnv::new();
@@ -34,11 +33,14 @@ fn test_create_feed_missing_title() {
     temp_env.set("CHANNEL_LINK", "https://example.com");
     temp_env.set("CHANNEL_DESCRIPTION", "Test channel description");
 
-    create_feed();
+    let error = create_feed().unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "Expected a channel title in the environment"
+    );
 }
 
 #[test]
-#[should_panic(expected = "Expected a channel link in the environment")]
 fn test_create_feed_missing_link() {
     let _guard = acquire_env_lock();
     let mut temp_env = TempEnv::new();
@@ -46,11 +48,14 @@ fn test_create_feed_missing_link() {
     temp_env.set("CHANNEL_TITLE", "Test Channel");
     temp_env.set("CHANNEL_DESCRIPTION", "Test channel description");
 
-    create_feed();
+    let error = create_feed().unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "Expected a channel link in the environment"
+    );
 }
 
 #[test]
-#[should_panic(expected = "Expected a channel description in the environment")]
 fn test_create_feed_missing_description() {
     let _guard = acquire_env_lock();
     let mut temp_env = TempEnv::new();
@@ -58,7 +63,11 @@ fn test_create_feed_missing_description() {
     temp_env.set("CHANNEL_TITLE", "Test Channel");
     temp_env.set("CHANNEL_LINK", "https://example.com");
 
-    create_feed();
+    let error = create_feed().unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "Expected a channel description in the environment"
+    );
 }
 
 #[test]
@@ -73,7 +82,7 @@ fn test_create_feed_unicode_content() {
         "This is a test with émojis 🎉 and ñoñ-ASCII characters",
     );
 
-    let channel = create_feed();
+    let channel = create_feed().unwrap();
 
     assert_eq!(channel.title(), "测试频道 🚀");
     assert_eq!(channel.link(), "https://example.com/测试");
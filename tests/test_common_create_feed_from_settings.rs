@@ -0,0 +1,49 @@
+use feed_crafter::common::{
+    FeedSettings, create_feed_from_settings, feed_max_item_age, is_valid_feed_id,
+};
+use rss::Channel;
+
+fn settings(max_item_age_seconds: Option<u64>) -> FeedSettings {
+    FeedSettings {
+        id: "alerts".to_string(),
+        title: "Alerts".to_string(),
+        link: "https://example.com/alerts".to_string(),
+        description: "System alerts".to_string(),
+        max_item_age_seconds,
+        max_items: None,
+        max_served_items: None,
+    }
+}
+
+#[test]
+fn test_create_feed_from_settings_success() {
+    let channel = create_feed_from_settings(&settings(None));
+
+    assert_eq!(channel.title(), "Alerts");
+    assert_eq!(channel.link(), "https://example.com/alerts");
+    assert_eq!(channel.description(), "System alerts");
+    assert!(channel.last_build_date().is_some());
+    assert_eq!(feed_max_item_age(&channel), None);
+}
+
+#[test]
+fn test_create_feed_from_settings_retention_round_trip() {
+    let channel = create_feed_from_settings(&settings(Some(86400)));
+    assert_eq!(feed_max_item_age(&channel), Some(86400));
+
+    // The retention policy must survive being written to and read from XML
+    let xml = channel.to_string();
+    assert!(xml.contains("xmlns:feedcrafter"));
+    let parsed = Channel::read_from(xml.as_bytes()).unwrap();
+    assert_eq!(feed_max_item_age(&parsed), Some(86400));
+}
+
+#[test]
+fn test_is_valid_feed_id() {
+    assert!(is_valid_feed_id("alerts"));
+    assert!(is_valid_feed_id("notice-board_2"));
+    assert!(!is_valid_feed_id(""));
+    assert!(!is_valid_feed_id("Alerts"));
+    assert!(!is_valid_feed_id("../feed"));
+    assert!(!is_valid_feed_id(&"a".repeat(65)));
+}
@@ -0,0 +1,161 @@
+mod common;
+
+use axum::{
+    Router,
+    body::Body,
+    extract::{Path, State},
+    http::{Request, StatusCode, header},
+    routing::{get, post},
+};
+use common::{acquire_env_lock, test_app_state};
+use feed_crafter::api::{api_delete_feed, api_edit_feed};
+use feed_crafter::common::{
+    DEFAULT_FEED, FeedSettings, create_feed_from_settings, delete_feed, edit_feed,
+};
+use feed_crafter::web::{legacy_add_item, legacy_edit_item, legacy_form_post};
+use std::collections::BTreeMap;
+use tower::ServiceExt;
+
+fn settings(id: &str) -> FeedSettings {
+    FeedSettings {
+        id: id.to_string(),
+        title: "Feed".to_string(),
+        link: "https://example.com".to_string(),
+        description: "A feed".to_string(),
+        max_item_age_seconds: None,
+        max_items: None,
+        max_served_items: None,
+    }
+}
+
+fn feeds() -> BTreeMap<String, rss::Channel> {
+    let mut feeds = BTreeMap::new();
+    feeds.insert(
+        DEFAULT_FEED.to_string(),
+        create_feed_from_settings(&settings(DEFAULT_FEED)),
+    );
+    feeds.insert(
+        "alerts".to_string(),
+        create_feed_from_settings(&settings("alerts")),
+    );
+    feeds
+}
+
+#[test]
+fn test_default_feed_keeps_its_id() {
+    let _guard = acquire_env_lock();
+    let state = test_app_state(feeds());
+
+    let renamed = edit_feed(
+        State(state.clone()),
+        Path(DEFAULT_FEED.to_string()),
+        settings("news"),
+    )
+    .unwrap();
+    assert!(renamed.is_none());
+    assert!(
+        delete_feed(State(state.clone()), Path(DEFAULT_FEED.to_string()))
+            .unwrap()
+            .is_none()
+    );
+    assert!(state.feeds.lock().unwrap().contains_key(DEFAULT_FEED));
+
+    // Its settings can still change, and other feeds can still be renamed
+    let mut retitled = settings(DEFAULT_FEED);
+    retitled.title = "Renamed title".to_string();
+    let channel = edit_feed(
+        State(state.clone()),
+        Path(DEFAULT_FEED.to_string()),
+        retitled,
+    )
+    .unwrap()
+    .unwrap();
+    assert_eq!(channel.title(), "Renamed title");
+    assert!(
+        edit_feed(
+            State(state.clone()),
+            Path("alerts".to_string()),
+            settings("news")
+        )
+        .unwrap()
+        .is_some()
+    );
+}
+
+#[tokio::test]
+async fn test_api_refuses_to_rename_or_delete_the_default_feed() {
+    let state = {
+        let _guard = acquire_env_lock();
+        test_app_state(feeds())
+    };
+    let app = Router::new()
+        .route("/api/feeds/{feed}", axum::routing::put(api_edit_feed))
+        .route("/api/feeds/{feed}", axum::routing::delete(api_delete_feed))
+        .with_state(state.clone());
+
+    let body = serde_json::json!({
+        "id": "news",
+        "title": "Feed",
+        "link": "https://example.com",
+        "description": "A feed"
+    });
+    let response = app
+        .clone()
+        .oneshot(
+            Request::put("/api/feeds/feed")
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(body.to_string()))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::CONFLICT);
+
+    let response = app
+        .oneshot(
+            Request::delete("/api/feeds/feed")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::CONFLICT);
+    assert!(state.feeds.lock().unwrap().contains_key(DEFAULT_FEED));
+}
+
+#[tokio::test]
+async fn test_legacy_routes_redirect_to_the_default_feed() {
+    let app: Router = Router::new()
+        .route("/add", get(legacy_add_item).post(legacy_form_post))
+        .route("/edit/{id}", get(legacy_edit_item).post(legacy_form_post))
+        .route("/delete/{id}", post(legacy_form_post));
+
+    for (uri, location) in [
+        ("/add", "/manage/feed/add"),
+        ("/edit/abc", "/manage/feed/edit/abc"),
+    ] {
+        let response = app
+            .clone()
+            .oneshot(Request::get(uri).body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::PERMANENT_REDIRECT);
+        assert_eq!(response.headers()[header::LOCATION], location);
+    }
+
+    // Old forms can't pass the new pages' checks, so they get an explanation instead
+    for uri in ["/add", "/edit/abc", "/delete/abc"] {
+        let response = app
+            .clone()
+            .oneshot(
+                Request::post(uri)
+                    .header(header::CONTENT_TYPE, "application/x-www-form-urlencoded")
+                    .body(Body::from("title=Outage"))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::GONE);
+        assert!(!response.headers().contains_key(header::LOCATION));
+    }
+}
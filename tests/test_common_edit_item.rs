@@ -0,0 +1,99 @@
+mod common;
+
+use axum::extract::{Path, State};
+use common::test_app_state;
+use feed_crafter::audit::AuditActor;
+use feed_crafter::common::{
+    FeedSettings, ItemFields, create_feed_from_settings, create_item, edit_item, item_updated,
+};
+use feed_crafter::formats::render_atom;
+use std::collections::BTreeMap;
+
+fn state_with_item() -> (feed_crafter::common::AppState, rss::Item) {
+    let mut channel = create_feed_from_settings(&FeedSettings {
+        id: "notices".to_string(),
+        title: "Notices".to_string(),
+        link: "https://example.com".to_string(),
+        description: "House notices".to_string(),
+        max_item_age_seconds: None,
+        max_items: None,
+        max_served_items: None,
+    });
+    let mut item = create_item("Original".to_string(), None, None);
+    item.set_pub_date("Mon, 01 Jan 2024 12:00:00 +0000".to_string());
+    channel.set_items(vec![item.clone()]);
+
+    let mut feeds = BTreeMap::new();
+    feeds.insert("notices".to_string(), channel.clone());
+    let state = test_app_state(feeds);
+    state.storage.save_feed("notices", &channel).unwrap();
+    (state, item)
+}
+
+#[test]
+fn test_edit_item_keeps_guid_and_pub_date() {
+    let (state, original) = state_with_item();
+    let guid = original.guid().unwrap().value().to_string();
+
+    let edited = edit_item(
+        State(state.clone()),
+        Path(("notices".to_string(), guid.clone())),
+        ItemFields {
+            title: "Edited".to_string(),
+            description: Some("New description".to_string()),
+            link: Some(String::new()),
+            ..Default::default()
+        },
+        None,
+        None,
+        &AuditActor::api(None),
+    )
+    .unwrap()
+    .unwrap();
+
+    assert_eq!(edited.guid().unwrap().value(), guid);
+    assert_eq!(edited.pub_date(), original.pub_date());
+    assert_eq!(edited.title(), Some("Edited"));
+    assert_eq!(edited.description(), Some("New description"));
+    assert_eq!(edited.link(), None);
+    assert!(item_updated(&edited).is_some());
+
+    let stored = state.storage.get_item("notices", &guid).unwrap().unwrap();
+    assert_eq!(stored.title(), Some("Edited"));
+    assert_eq!(item_updated(&stored), item_updated(&edited));
+
+    // Atom readers see the edit time as the entry's updated time
+    let feeds = state.feeds.lock().unwrap();
+    let atom: atom_syndication::Feed = render_atom("notices", &feeds["notices"], None)
+        .parse()
+        .unwrap();
+    let entry = &atom.entries()[0];
+    assert_eq!(
+        entry.updated().timestamp(),
+        item_updated(&edited).unwrap().timestamp()
+    );
+    assert_ne!(
+        entry.published().map(|d| d.timestamp()),
+        Some(entry.updated().timestamp())
+    );
+}
+
+#[test]
+fn test_edit_missing_item_returns_none() {
+    let (state, _) = state_with_item();
+
+    let edited = edit_item(
+        State(state),
+        Path(("notices".to_string(), "missing".to_string())),
+        ItemFields {
+            title: "Edited".to_string(),
+            ..Default::default()
+        },
+        None,
+        None,
+        &AuditActor::api(None),
+    )
+    .unwrap();
+
+    assert!(edited.is_none());
+}
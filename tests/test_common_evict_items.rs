@@ -0,0 +1,214 @@
+mod common;
+
+use axum::extract::{Path, State};
+use chrono::Utc;
+use common::{TempEnv, acquire_env_lock, test_app_state};
+use feed_crafter::audit::{AuditAction, AuditActor, AuditFilter};
+use feed_crafter::common::{
+    FeedSettings, ItemOperation, add_item, apply_item_operations, cleanup_old_items,
+    create_feed_from_settings, create_item, evict_items, feed_max_items, feed_max_served_items,
+    schedule_item, served_channel, set_feed_max_items, set_item_deleted,
+};
+use rss::{Channel, Item};
+use std::collections::BTreeMap;
+
+fn test_channel(max_items: Option<usize>, max_served_items: Option<usize>) -> Channel {
+    create_feed_from_settings(&FeedSettings {
+        id: "alerts".to_string(),
+        title: "Alerts".to_string(),
+        link: "https://example.com".to_string(),
+        description: "System alerts".to_string(),
+        max_item_age_seconds: None,
+        max_items,
+        max_served_items,
+    })
+}
+
+fn titles(items: &[Item]) -> Vec<&str> {
+    items.iter().filter_map(|item| item.title()).collect()
+}
+
+#[test]
+fn test_feed_item_limits_round_trip_through_xml() {
+    let channel = test_channel(Some(100), Some(20));
+    let parsed = Channel::read_from(channel.to_string().as_bytes()).unwrap();
+
+    assert_eq!(feed_max_items(&parsed), Some(100));
+    assert_eq!(feed_max_served_items(&parsed), Some(20));
+}
+
+#[test]
+fn test_evict_items_skips_items_in_the_trash() {
+    let _guard = acquire_env_lock();
+    let mut trashed = create_item("Trashed".to_string(), None, None);
+    set_item_deleted(&mut trashed, Some(Utc::now()));
+    let mut channel = test_channel(Some(2), None);
+    channel.set_items(vec![
+        create_item("Newest".to_string(), None, None),
+        trashed,
+        create_item("Middle".to_string(), None, None),
+        create_item("Oldest".to_string(), None, None),
+    ]);
+
+    let evicted = evict_items(&mut channel);
+
+    assert_eq!(titles(&evicted), vec!["Oldest"]);
+    assert_eq!(titles(channel.items()), vec!["Newest", "Trashed", "Middle"]);
+
+    // Without a cap nothing is evicted
+    let mut uncapped = test_channel(Some(0), None);
+    uncapped.set_items(evicted);
+    assert!(evict_items(&mut uncapped).is_empty());
+}
+
+#[test]
+fn test_scheduled_items_dont_count_towards_the_cap() {
+    let _guard = acquire_env_lock();
+    let mut feeds = BTreeMap::new();
+    let mut channel = test_channel(Some(2), None);
+    channel.set_items(vec![
+        create_item("Second".to_string(), None, None),
+        create_item("First".to_string(), None, None),
+    ]);
+    feeds.insert("alerts".to_string(), channel);
+    let state = test_app_state(feeds);
+
+    let mut scheduled = create_item("Scheduled".to_string(), None, None);
+    schedule_item(&mut scheduled, Utc::now() + chrono::Duration::days(1));
+    add_item(
+        State(state.clone()),
+        Path("alerts".to_string()),
+        scheduled,
+        &AuditActor::api(None),
+    )
+    .unwrap();
+
+    // The feed still shows both published items
+    let channel = state.feeds.lock().unwrap()["alerts"].clone();
+    assert_eq!(
+        titles(channel.items()),
+        vec!["Scheduled", "Second", "First"]
+    );
+
+    // Scheduled items past the cap are kept too, only published items are evicted
+    let mut later = create_item("Later".to_string(), None, None);
+    schedule_item(&mut later, Utc::now() + chrono::Duration::days(2));
+    let mut capped = test_channel(Some(1), None);
+    capped.set_items(vec![
+        create_item("Newest".to_string(), None, None),
+        later,
+        create_item("Oldest".to_string(), None, None),
+    ]);
+    assert_eq!(titles(&evict_items(&mut capped)), vec!["Oldest"]);
+    assert_eq!(titles(capped.items()), vec!["Newest", "Later"]);
+}
+
+#[test]
+fn test_add_item_evicts_the_oldest_items() {
+    let _guard = acquire_env_lock();
+    let mut feeds = BTreeMap::new();
+    feeds.insert("alerts".to_string(), test_channel(Some(2), None));
+    let state = test_app_state(feeds);
+
+    for title in ["First", "Second", "Third"] {
+        add_item(
+            State(state.clone()),
+            Path("alerts".to_string()),
+            create_item(title.to_string(), None, None),
+            &AuditActor::api(None),
+        )
+        .unwrap();
+    }
+
+    let channel = state.feeds.lock().unwrap()["alerts"].clone();
+    assert_eq!(titles(channel.items()), vec!["Third", "Second"]);
+    let stored = state.storage.load_feed("alerts").unwrap();
+    assert_eq!(titles(stored.items()), vec!["Third", "Second"]);
+
+    let filter = AuditFilter {
+        action: Some(AuditAction::Expire),
+        ..Default::default()
+    };
+    let (entries, total) = state.audit.query(&filter, 0, 10).unwrap();
+    assert_eq!(total, 1);
+    assert_eq!(
+        entries[0].before.as_ref().and_then(|item| item.title()),
+        Some("First")
+    );
+}
+
+#[test]
+fn test_bulk_create_evicts_past_the_default_cap() {
+    let _guard = acquire_env_lock();
+    let mut env = TempEnv::new();
+    env.set("MAX_ITEMS", "3");
+    let mut channel = test_channel(None, None);
+    channel.set_items(vec![
+        create_item("Second".to_string(), None, None),
+        create_item("First".to_string(), None, None),
+    ]);
+    let mut feeds = BTreeMap::new();
+    feeds.insert("alerts".to_string(), channel);
+    let state = test_app_state(feeds);
+
+    let operations = ["Third", "Fourth"]
+        .into_iter()
+        .map(|title| ItemOperation::Create(Box::new(create_item(title.to_string(), None, None))))
+        .collect();
+    apply_item_operations(&state, "alerts", operations, &AuditActor::api(None))
+        .unwrap()
+        .unwrap();
+
+    let feeds = state.feeds.lock().unwrap();
+    assert_eq!(
+        titles(feeds["alerts"].items()),
+        vec!["Fourth", "Third", "Second"]
+    );
+}
+
+#[test]
+fn test_cleanup_applies_a_lowered_cap() {
+    let _guard = acquire_env_lock();
+    let mut channel = test_channel(None, None);
+    channel.set_items(vec![
+        create_item("Third".to_string(), None, None),
+        create_item("Second".to_string(), None, None),
+        create_item("First".to_string(), None, None),
+    ]);
+    set_feed_max_items(&mut channel, Some(1));
+    let mut feeds = BTreeMap::new();
+    feeds.insert("alerts".to_string(), channel);
+    let state = test_app_state(feeds);
+
+    assert_eq!(cleanup_old_items(&state), 2);
+    assert_eq!(
+        titles(state.feeds.lock().unwrap()["alerts"].items()),
+        vec!["Third"]
+    );
+}
+
+#[test]
+fn test_served_channel_keeps_the_newest_items() {
+    let _guard = acquire_env_lock();
+    let mut channel = test_channel(None, Some(2));
+    channel.set_items(vec![
+        create_item("Third".to_string(), None, None),
+        create_item("Second".to_string(), None, None),
+        create_item("First".to_string(), None, None),
+    ]);
+
+    assert_eq!(
+        titles(served_channel(&channel).items()),
+        vec!["Third", "Second"]
+    );
+    // The full history stays in the channel itself
+    assert_eq!(channel.items().len(), 3);
+
+    // A feed's own limit takes precedence over MAX_SERVED_ITEMS
+    let mut env = TempEnv::new();
+    env.set("MAX_SERVED_ITEMS", "1");
+    assert_eq!(served_channel(&channel).items().len(), 2);
+    let mut unlimited = test_channel(None, None);
+    unlimited.set_items(channel.items().to_vec());
+    assert_eq!(titles(served_channel(&unlimited).items()), vec!["Third"]);
+}
@@ -0,0 +1,116 @@
+mod common;
+
+use chrono::{Duration, TimeZone, Utc};
+use common::{acquire_env_lock, test_app_state};
+use feed_crafter::common::{
+    FeedSettings, ItemExpiry, cleanup_old_items, create_feed_from_settings, create_item,
+    ensure_feed_crafter_namespace, item_expiry, set_item_expiry,
+};
+use rss::{Channel, Item};
+use std::collections::BTreeMap;
+
+fn test_channel(max_item_age_seconds: Option<u64>) -> Channel {
+    create_feed_from_settings(&FeedSettings {
+        id: "alerts".to_string(),
+        title: "Alerts".to_string(),
+        link: "https://example.com".to_string(),
+        description: "System alerts".to_string(),
+        max_item_age_seconds,
+        max_items: None,
+        max_served_items: None,
+    })
+}
+
+fn item_with_expiry(title: &str, expiry: Option<ItemExpiry>) -> Item {
+    let mut item = create_item(title.to_string(), None, None);
+    set_item_expiry(&mut item, expiry);
+    item
+}
+
+#[test]
+fn test_parse_item_expiry() {
+    let expected = Utc.with_ymd_and_hms(2024, 6, 1, 18, 0, 0).unwrap();
+
+    assert_eq!(ItemExpiry::parse("never"), Ok(ItemExpiry::Never));
+    assert_eq!(
+        ItemExpiry::parse("2024-06-01T20:00:00+02:00"),
+        Ok(ItemExpiry::At(expected))
+    );
+    assert_eq!(
+        ItemExpiry::parse("Sat, 01 Jun 2024 18:00:00 +0000"),
+        Ok(ItemExpiry::At(expected))
+    );
+    assert_eq!(
+        ItemExpiry::parse("2024-06-01T18:00"),
+        Ok(ItemExpiry::At(expected))
+    );
+    assert!(ItemExpiry::parse("tomorrow").is_err());
+}
+
+#[test]
+fn test_item_expiry_round_trips_through_xml() {
+    let expires_at = Utc.with_ymd_and_hms(2030, 1, 1, 0, 0, 0).unwrap();
+    let mut channel = test_channel(None);
+    ensure_feed_crafter_namespace(&mut channel);
+    channel.set_items(vec![
+        item_with_expiry("Expiring", Some(ItemExpiry::At(expires_at))),
+        item_with_expiry("Permanent", Some(ItemExpiry::Never)),
+        item_with_expiry("Default", None),
+    ]);
+
+    let xml = channel.to_string();
+    assert!(xml.contains("<feedcrafter:expiresAt>"));
+
+    let parsed = Channel::read_from(xml.as_bytes()).unwrap();
+    assert_eq!(
+        item_expiry(&parsed.items()[0]),
+        Some(ItemExpiry::At(expires_at))
+    );
+    assert_eq!(item_expiry(&parsed.items()[1]), Some(ItemExpiry::Never));
+    assert_eq!(item_expiry(&parsed.items()[2]), None);
+}
+
+#[test]
+fn test_cleanup_honours_item_expiry() {
+    let _guard = acquire_env_lock();
+    let now = Utc::now();
+    let old_date = (now - Duration::hours(2)).to_rfc2822();
+
+    let mut aged = item_with_expiry("Aged out", None);
+    aged.set_pub_date(old_date.clone());
+    let mut permanent = item_with_expiry("Permanent", Some(ItemExpiry::Never));
+    permanent.set_pub_date(old_date.clone());
+    let mut scheduled_end = item_with_expiry(
+        "Maintenance window",
+        Some(ItemExpiry::At(now + Duration::hours(1))),
+    );
+    scheduled_end.set_pub_date(old_date);
+    let expired = item_with_expiry("Expired", Some(ItemExpiry::At(now - Duration::minutes(1))));
+    let fresh = item_with_expiry("Fresh", None);
+
+    let mut channel = test_channel(Some(3600));
+    channel.set_items(vec![aged, permanent, scheduled_end, expired, fresh]);
+
+    // A feed that keeps items forever still drops items that have expired
+    let mut keep_forever = test_channel(Some(0));
+    keep_forever.set_items(vec![
+        item_with_expiry("Expired", Some(ItemExpiry::At(now - Duration::minutes(1)))),
+        item_with_expiry("Kept", None),
+    ]);
+
+    let mut feeds = BTreeMap::new();
+    feeds.insert("alerts".to_string(), channel);
+    feeds.insert("archive".to_string(), keep_forever);
+    let state = test_app_state(feeds);
+
+    assert_eq!(cleanup_old_items(&state), 3);
+
+    let feeds = state.feeds.lock().unwrap();
+    let titles: Vec<&str> = feeds["alerts"]
+        .items()
+        .iter()
+        .filter_map(|item| item.title())
+        .collect();
+    assert_eq!(titles, vec!["Permanent", "Maintenance window", "Fresh"]);
+    assert_eq!(feeds["archive"].items()[0].title(), Some("Kept"));
+}
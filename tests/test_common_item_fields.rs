@@ -0,0 +1,99 @@
+use feed_crafter::common::{ItemFields, create_item_from_fields, set_item_fields};
+use rss::{Category, Enclosure, Source};
+
+fn alert_fields() -> ItemFields {
+    ItemFields {
+        title: "Disk almost full".to_string(),
+        description: Some("Only 2% left on /var".to_string()),
+        link: Some("https://example.com/alerts/1".to_string()),
+        author: Some("alerts@example.com (Alert Bot)".to_string()),
+        comments: Some("https://example.com/alerts/1/comments".to_string()),
+        categories: vec![
+            Category {
+                name: "critical".to_string(),
+                domain: Some("severity".to_string()),
+            },
+            Category {
+                name: " ".to_string(),
+                domain: None,
+            },
+        ],
+        enclosure: Some(Enclosure {
+            url: "https://example.com/logs/disk.log".to_string(),
+            length: "2048".to_string(),
+            mime_type: "text/plain".to_string(),
+        }),
+        source: Some(Source {
+            url: "https://monitoring.example.com/feed.xml".to_string(),
+            title: Some("Monitoring".to_string()),
+        }),
+    }
+}
+
+#[test]
+fn test_create_item_from_fields() {
+    let item = create_item_from_fields(alert_fields());
+
+    assert!(item.guid().is_some());
+    assert!(item.pub_date().is_some());
+    assert_eq!(item.title(), Some("Disk almost full"));
+    assert_eq!(item.author(), Some("alerts@example.com (Alert Bot)"));
+    assert_eq!(
+        item.comments(),
+        Some("https://example.com/alerts/1/comments")
+    );
+    // Blank categories are dropped
+    assert_eq!(item.categories().len(), 1);
+    assert_eq!(item.categories()[0].name(), "critical");
+    assert_eq!(item.categories()[0].domain(), Some("severity"));
+    assert_eq!(item.enclosure().unwrap().length(), "2048");
+    assert_eq!(item.source().unwrap().title(), Some("Monitoring"));
+}
+
+#[test]
+fn test_item_fields_round_trip_through_xml() {
+    let item = create_item_from_fields(alert_fields());
+    let mut channel = rss::Channel::default();
+    channel.set_items(vec![item]);
+
+    let xml = channel.to_string();
+    assert!(xml.contains(r#"<category domain="severity">critical</category>"#));
+    assert!(xml.contains(
+        r#"<enclosure url="https://example.com/logs/disk.log" length="2048" type="text/plain"/>"#
+    ));
+
+    let parsed = rss::Channel::read_from(xml.as_bytes()).unwrap();
+    let parsed_item = &parsed.items()[0];
+    assert_eq!(parsed_item.categories()[0].name(), "critical");
+    assert_eq!(parsed_item.author(), Some("alerts@example.com (Alert Bot)"));
+    assert_eq!(
+        parsed_item.source().unwrap().url(),
+        "https://monitoring.example.com/feed.xml"
+    );
+}
+
+#[test]
+fn test_set_item_fields_clears_empty_values() {
+    let mut item = create_item_from_fields(alert_fields());
+
+    set_item_fields(
+        &mut item,
+        ItemFields {
+            title: "Resolved".to_string(),
+            author: Some(String::new()),
+            enclosure: Some(Enclosure {
+                url: " ".to_string(),
+                length: "0".to_string(),
+                mime_type: "text/plain".to_string(),
+            }),
+            ..Default::default()
+        },
+    );
+
+    assert_eq!(item.title(), Some("Resolved"));
+    assert_eq!(item.description(), None);
+    assert_eq!(item.author(), None);
+    assert!(item.categories().is_empty());
+    assert!(item.enclosure().is_none());
+    assert!(item.source().is_none());
+}
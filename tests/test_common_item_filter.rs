@@ -0,0 +1,86 @@
+use feed_crafter::common::{ItemFilter, create_item, parse_datetime};
+use rss::{Category, Item};
+
+fn item(title: &str, description: Option<&str>, category: Option<&str>, pub_date: &str) -> Item {
+    let mut item = create_item(title.to_string(), description.map(|d| d.to_string()), None);
+    item.set_pub_date(pub_date.to_string());
+    item.set_categories(
+        category
+            .map(|name| Category {
+                name: name.to_string(),
+                domain: None,
+            })
+            .into_iter()
+            .collect::<Vec<Category>>(),
+    );
+    item
+}
+
+#[test]
+fn test_empty_filter_matches_everything() {
+    let item = item("Disk full", None, None, "Mon, 01 Jan 2024 12:00:00 +0000");
+    assert!(ItemFilter::default().matches(&item));
+}
+
+#[test]
+fn test_query_searches_title_and_description() {
+    let by_title = item(
+        "Disk FULL on db1",
+        None,
+        None,
+        "Mon, 01 Jan 2024 12:00:00 +0000",
+    );
+    let by_description = item(
+        "Alert",
+        Some("The disk is full"),
+        None,
+        "Mon, 01 Jan 2024 12:00:00 +0000",
+    );
+    let other = item("Backup done", None, None, "Mon, 01 Jan 2024 12:00:00 +0000");
+    let filter = ItemFilter {
+        query: Some("full".to_string()),
+        ..Default::default()
+    };
+
+    assert!(filter.matches(&by_title));
+    assert!(filter.matches(&by_description));
+    assert!(!filter.matches(&other));
+}
+
+#[test]
+fn test_category_and_date_range() {
+    let critical = item(
+        "Disk full",
+        None,
+        Some("Critical"),
+        "Mon, 01 Jan 2024 12:00:00 +0000",
+    );
+    let category = ItemFilter {
+        category: Some("critical".to_string()),
+        ..Default::default()
+    };
+    assert!(category.matches(&critical));
+    assert!(!category.matches(&item(
+        "Info",
+        None,
+        Some("info"),
+        "Mon, 01 Jan 2024 12:00:00 +0000"
+    )));
+
+    let range = ItemFilter {
+        since: Some(parse_datetime("2024-01-01T12:00:00Z").unwrap()),
+        until: Some(parse_datetime("2024-01-02T00:00:00Z").unwrap()),
+        ..Default::default()
+    };
+    assert!(range.matches(&critical));
+    assert!(!range.matches(&item(
+        "Later",
+        None,
+        None,
+        "Tue, 02 Jan 2024 12:00:00 +0000"
+    )));
+
+    let mut undated = create_item("Undated".to_string(), None, None);
+    undated.set_pub_date(None);
+    assert!(!range.matches(&undated));
+}
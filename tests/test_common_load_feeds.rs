@@ -0,0 +1,128 @@
+mod common;
+
+use common::{MockFileSystem, TempEnv, acquire_env_lock};
+use feed_crafter::common::{DEFAULT_FEED, load_feeds};
+use feed_crafter::storage::XmlStorage;
+
+#[test]
+fn test_load_feeds_when_file_exists() {
+    let mock_xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+<rss version="2.0">
+    <channel>
+        <title>Test Feed</title>
+        <description>A test RSS feed</description>
+        <link>https://example.com</link>
+    </channel>
+</rss>"#;
+
+    let storage = XmlStorage::new(MockFileSystem::with_existing_file(mock_xml.to_string()));
+
+    let feeds = load_feeds(&storage).unwrap();
+
+    // Assert the channel was loaded from the mock XML
+    assert_eq!(feeds.len(), 1);
+    let channel = feeds.get(DEFAULT_FEED).unwrap();
+    assert_eq!(channel.title(), "Test Feed");
+    assert_eq!(channel.description(), "A test RSS feed");
+
+    // Verify no file was written (since we loaded from existing)
+    assert!(!storage.fs.was_file_written("./feed/feed.xml"));
+}
+
+#[test]
+fn test_load_feeds_when_file_does_not_exist() {
+    let _guard = acquire_env_lock();
+    let mut temp_env = TempEnv::new();
+
+    temp_env.set("CHANNEL_TITLE", "Test Channel");
+    temp_env.set("CHANNEL_LINK", "https://example.com");
+    temp_env.set("CHANNEL_DESCRIPTION", "Test channel description");
+
+    // This simulates a completely empty filesystem
+    let storage = XmlStorage::new(MockFileSystem::new());
+
+    let feeds = load_feeds(&storage).unwrap();
+
+    assert_eq!(feeds.len(), 1);
+    let channel = feeds.get(DEFAULT_FEED).unwrap();
+    assert_eq!(channel.title(), "Test Channel");
+    assert_eq!(channel.link(), "https://example.com");
+    assert_eq!(channel.description(), "Test channel description");
+
+    // Verify that the new feed was written to file
+    assert!(storage.fs.was_file_written("./feed/feed.xml"));
+    let written_content = storage.fs.get_written_content("./feed/feed.xml").unwrap();
+    assert!(written_content.contains("<?xml"));
+    assert!(written_content.contains("<rss"));
+}
+
+#[test]
+fn test_load_feeds_with_empty_filesystem() {
+    let _guard = acquire_env_lock();
+    let mut temp_env = TempEnv::new();
+
+    temp_env.set("CHANNEL_TITLE", "Test Channel");
+    temp_env.set("CHANNEL_LINK", "https://example.com");
+    temp_env.set("CHANNEL_DESCRIPTION", "Test channel description");
+
+    // This simulates a completely empty filesystem
+    let storage = XmlStorage::new(MockFileSystem::new());
+
+    let feeds = load_feeds(&storage).unwrap();
+
+    let channel = feeds.get(DEFAULT_FEED).unwrap();
+    assert_eq!(channel.title(), "Test Channel");
+    assert_eq!(channel.link(), "https://example.com");
+    assert_eq!(channel.description(), "Test channel description");
+
+    // Verify the new feed was written, without anything being moved aside
+    assert!(storage.fs.was_file_written("./feed/feed.xml"));
+    assert!(
+        storage
+            .fs
+            .renamed_files
+            .borrow()
+            .iter()
+            .all(|(from, _)| from != "./feed/feed.xml")
+    );
+}
+
+#[test]
+fn test_load_feeds_quarantines_corrupt_feed() {
+    let _guard = acquire_env_lock();
+    let mut temp_env = TempEnv::new();
+
+    temp_env.set("CHANNEL_TITLE", "Test Channel");
+    temp_env.set("CHANNEL_LINK", "https://example.com");
+    temp_env.set("CHANNEL_DESCRIPTION", "Test channel description");
+
+    let storage = XmlStorage::new(MockFileSystem::with_existing_file(
+        "<rss><channel><title>Trunc".to_string(),
+    ));
+
+    let feeds = load_feeds(&storage).unwrap();
+
+    // The broken file is moved aside before a fresh default feed is written in its place
+    let renamed = storage.fs.renamed_files.borrow();
+    assert_eq!(renamed[0].0, "./feed/feed.xml");
+    assert!(renamed[0].1.starts_with("./feed/feed.corrupt-"));
+    assert_eq!(feeds[DEFAULT_FEED].title(), "Test Channel");
+    assert!(storage.fs.was_file_written("./feed/feed.xml"));
+}
+
+#[test]
+fn test_load_feeds_reports_missing_settings() {
+    let _guard = acquire_env_lock();
+    let mut temp_env = TempEnv::new();
+
+    temp_env.set("CHANNEL_TITLE", "Test Channel");
+    temp_env.set("CHANNEL_LINK", "https://example.com");
+    unsafe {
+        std::env::remove_var("CHANNEL_DESCRIPTION");
+    }
+
+    let storage = XmlStorage::new(MockFileSystem::new());
+
+    assert!(load_feeds(&storage).is_err());
+    assert!(!storage.fs.was_file_written("./feed/feed.xml"));
+}
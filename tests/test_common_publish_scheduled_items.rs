@@ -0,0 +1,85 @@
+mod common;
+
+use chrono::{Duration, Utc};
+use common::test_app_state;
+use feed_crafter::common::{
+    FeedSettings, create_feed_from_settings, create_item, is_item_scheduled,
+    publish_scheduled_items, published_channel, schedule_item,
+};
+use rss::{Channel, Item};
+use std::collections::BTreeMap;
+
+fn test_channel() -> Channel {
+    create_feed_from_settings(&FeedSettings {
+        id: "notices".to_string(),
+        title: "Notices".to_string(),
+        link: "https://example.com".to_string(),
+        description: "House notices".to_string(),
+        max_item_age_seconds: None,
+        max_items: None,
+        max_served_items: None,
+    })
+}
+
+fn scheduled_item(title: &str, publish_at: chrono::DateTime<Utc>) -> Item {
+    let mut item = create_item(title.to_string(), None, None);
+    schedule_item(&mut item, publish_at);
+    item
+}
+
+fn titles(channel: &Channel) -> Vec<&str> {
+    channel
+        .items()
+        .iter()
+        .filter_map(|item| item.title())
+        .collect()
+}
+
+#[test]
+fn test_published_channel_hides_scheduled_items() {
+    let mut channel = test_channel();
+    channel.set_items(vec![
+        scheduled_item("Later", Utc::now() + Duration::hours(1)),
+        create_item("Now".to_string(), None, None),
+    ]);
+
+    let published = published_channel(&channel);
+
+    assert_eq!(titles(&published), vec!["Now"]);
+    assert_eq!(channel.items().len(), 2);
+    assert!(!published.to_string().contains("Later"));
+}
+
+#[test]
+fn test_publish_scheduled_items_promotes_due_items() {
+    let now = Utc::now();
+    let mut channel = test_channel();
+    channel.set_items(vec![
+        scheduled_item("Tomorrow", now + Duration::days(1)),
+        create_item("Published".to_string(), None, None),
+        scheduled_item("Due earlier", now - Duration::minutes(5)),
+        scheduled_item("Due now", now - Duration::seconds(1)),
+    ]);
+
+    let mut feeds = BTreeMap::new();
+    feeds.insert("notices".to_string(), channel.clone());
+    let state = test_app_state(feeds);
+    state.storage.save_feed("notices", &channel).unwrap();
+
+    assert_eq!(publish_scheduled_items(&state), 2);
+    assert_eq!(publish_scheduled_items(&state), 0);
+
+    let feeds = state.feeds.lock().unwrap();
+    let channel = &feeds["notices"];
+    assert_eq!(
+        titles(channel),
+        vec!["Due now", "Due earlier", "Tomorrow", "Published"]
+    );
+    let scheduled: Vec<bool> = channel.items().iter().map(is_item_scheduled).collect();
+    assert_eq!(scheduled, vec![false, false, true, false]);
+
+    // Storage keeps the promoted items at the top as well
+    let stored = state.storage.load_feed("notices").unwrap();
+    assert_eq!(titles(&stored)[..2], ["Due now", "Due earlier"]);
+    assert_eq!(titles(&published_channel(&stored)).len(), 3);
+}
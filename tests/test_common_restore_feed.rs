@@ -0,0 +1,79 @@
+mod common;
+
+use axum::extract::{Path, State};
+use common::test_app_state;
+use feed_crafter::audit::AuditActor;
+use feed_crafter::common::{
+    FeedSettings, add_feed, add_item, create_item, delete_feed, restore_feed,
+};
+use std::collections::BTreeMap;
+
+fn settings() -> FeedSettings {
+    FeedSettings {
+        id: "alerts".to_string(),
+        title: "Alerts".to_string(),
+        link: "https://example.com".to_string(),
+        description: "System alerts".to_string(),
+        max_item_age_seconds: None,
+        max_items: None,
+        max_served_items: None,
+    }
+}
+
+#[test]
+fn test_restore_replaces_feed_with_snapshot() {
+    let state = test_app_state(BTreeMap::new());
+    add_feed(State(state.clone()), settings()).unwrap().unwrap();
+    add_item(
+        State(state.clone()),
+        Path("alerts".to_string()),
+        create_item("First".to_string(), None, None),
+        &AuditActor::api(None),
+    )
+    .unwrap()
+    .unwrap();
+    add_item(
+        State(state.clone()),
+        Path("alerts".to_string()),
+        create_item("Second".to_string(), None, None),
+        &AuditActor::api(None),
+    )
+    .unwrap()
+    .unwrap();
+
+    // Every change was snapshotted, the second newest has only the first item
+    let backups = state.backups.list("alerts").unwrap();
+    assert_eq!(backups.len(), 3);
+    let restored = restore_feed(&state, "alerts", &backups[1].id, &AuditActor::api(None))
+        .unwrap()
+        .unwrap();
+
+    assert_eq!(restored.items().len(), 1);
+    assert_eq!(
+        state.feeds.lock().unwrap()["alerts"].items()[0].title(),
+        Some("First")
+    );
+    assert_eq!(state.storage.list_items("alerts").unwrap().len(), 1);
+}
+
+#[test]
+fn test_restore_recreates_deleted_feed() {
+    let state = test_app_state(BTreeMap::new());
+    add_feed(State(state.clone()), settings()).unwrap().unwrap();
+    let backup_id = state.backups.list("alerts").unwrap()[0].id.clone();
+    delete_feed(State(state.clone()), Path("alerts".to_string()))
+        .unwrap()
+        .unwrap();
+
+    restore_feed(&state, "alerts", &backup_id, &AuditActor::api(None))
+        .unwrap()
+        .unwrap();
+
+    assert!(state.feeds.lock().unwrap().contains_key("alerts"));
+    assert_eq!(state.storage.list_feeds().unwrap(), vec!["alerts"]);
+    assert!(
+        restore_feed(&state, "alerts", "missing", &AuditActor::api(None))
+            .unwrap()
+            .is_none()
+    );
+}
@@ -0,0 +1,128 @@
+mod common;
+
+use axum::extract::{Path, State};
+use common::{TempEnv, acquire_env_lock, test_app_state};
+use feed_crafter::audit::{AuditAction, AuditActor, AuditFilter};
+use feed_crafter::common::{
+    AppState, FeedSettings, ItemFields, add_item, cleanup_old_items, create_feed_from_settings,
+    create_item, delete_item, edit_item, is_item_deleted, published_channel, restore_item,
+    set_item_deleted,
+};
+use std::collections::BTreeMap;
+
+fn state_with_item(title: &str) -> (AppState, String) {
+    let channel = create_feed_from_settings(&FeedSettings {
+        id: "alerts".to_string(),
+        title: "Alerts".to_string(),
+        link: "https://example.com".to_string(),
+        description: "System alerts".to_string(),
+        max_item_age_seconds: None,
+        max_items: None,
+        max_served_items: None,
+    });
+    let mut feeds = BTreeMap::new();
+    feeds.insert("alerts".to_string(), channel.clone());
+    let state = test_app_state(feeds);
+    state.storage.save_feed("alerts", &channel).unwrap();
+    let guid = add_item(
+        State(state.clone()),
+        Path("alerts".to_string()),
+        create_item(title.to_string(), None, None),
+        &AuditActor::api(None),
+    )
+    .unwrap()
+    .unwrap();
+    (state, guid.value().to_string())
+}
+
+fn delete(state: &AppState, id: &str) -> bool {
+    delete_item(
+        State(state.clone()),
+        Path(("alerts".to_string(), id.to_string())),
+        &AuditActor::api(None),
+    )
+    .unwrap()
+    .is_some()
+}
+
+#[test]
+fn test_deleted_item_is_hidden_until_restored() {
+    let (state, id) = state_with_item("Outage");
+    assert!(delete(&state, &id));
+
+    {
+        let feeds = state.feeds.lock().unwrap();
+        assert!(published_channel(&feeds["alerts"]).items().is_empty());
+    }
+    let stored = state.storage.get_item("alerts", &id).unwrap().unwrap();
+    assert!(is_item_deleted(&stored));
+    // Items in the trash can't be deleted again or edited
+    assert!(!delete(&state, &id));
+    let edited = edit_item(
+        State(state.clone()),
+        Path(("alerts".to_string(), id.clone())),
+        ItemFields {
+            title: "Edited".to_string(),
+            ..Default::default()
+        },
+        None,
+        None,
+        &AuditActor::api(None),
+    )
+    .unwrap();
+    assert!(edited.is_none());
+
+    let restored = restore_item(&state, "alerts", &id, &AuditActor::api(None))
+        .unwrap()
+        .unwrap();
+    assert!(!is_item_deleted(&restored));
+    assert_eq!(restored.title(), Some("Outage"));
+    {
+        let feeds = state.feeds.lock().unwrap();
+        assert_eq!(published_channel(&feeds["alerts"]).items().len(), 1);
+    }
+    assert!(!is_item_deleted(
+        &state.storage.get_item("alerts", &id).unwrap().unwrap()
+    ));
+
+    // Only items in the trash can be restored
+    assert!(
+        restore_item(&state, "alerts", &id, &AuditActor::api(None))
+            .unwrap()
+            .is_none()
+    );
+}
+
+#[test]
+fn test_cleanup_purges_items_after_trash_retention() {
+    let _lock = acquire_env_lock();
+    let mut env = TempEnv::new();
+    env.set("TRASH_RETENTION_SECONDS", "3600");
+    let (state, id) = state_with_item("Outage");
+    assert!(delete(&state, &id));
+
+    // Still within the retention
+    assert_eq!(cleanup_old_items(&state), 0);
+    assert_eq!(state.feeds.lock().unwrap()["alerts"].items().len(), 1);
+
+    {
+        let mut feeds = state.feeds.lock().unwrap();
+        let channel = feeds.get_mut("alerts").unwrap();
+        let mut items = channel.items().to_vec();
+        set_item_deleted(
+            &mut items[0],
+            Some(chrono::Utc::now() - chrono::Duration::hours(2)),
+        );
+        channel.set_items(items);
+    }
+    assert_eq!(cleanup_old_items(&state), 1);
+    assert!(state.feeds.lock().unwrap()["alerts"].items().is_empty());
+    assert!(state.storage.get_item("alerts", &id).unwrap().is_none());
+
+    let filter = AuditFilter {
+        action: Some(AuditAction::Purge),
+        ..AuditFilter::default()
+    };
+    let (entries, _) = state.audit.query(&filter, 0, 10).unwrap();
+    assert_eq!(entries[0].item_id.as_deref(), Some(id.as_str()));
+}
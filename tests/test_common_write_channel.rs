@@ -1,7 +1,9 @@
 mod common;
 
 use common::{MockFileSystem, TempEnv, acquire_env_lock};
-use feed_crafter::common::{create_feed, write_channel};
+use feed_crafter::common::{
+    FeedSettings, FileSystem, RealFileSystem, create_feed, create_feed_from_settings, write_channel,
+};
 
 #[test]
 fn test_write_channel_success() {
@@ -15,9 +17,9 @@ fn test_write_channel_success() {
     let mock_fs = MockFileSystem::new();
 
     // Create a dummy channel
-    let channel = create_feed();
+    let channel = create_feed().unwrap();
 
-    write_channel(&channel, None, &mock_fs);
+    write_channel(&channel, None, &mock_fs).unwrap();
 
     // Verify the file was written to the default path
     assert!(mock_fs.was_file_written("./feed/feed.xml"));
@@ -25,3 +27,61 @@ fn test_write_channel_success() {
     assert!(written_content.contains("<?xml"));
     assert!(written_content.contains("<rss"));
 }
+
+#[test]
+fn test_write_channel_goes_through_temp_file() {
+    let mock_fs = MockFileSystem::new();
+    let channel = create_feed_from_settings(&test_settings());
+
+    write_channel(&channel, Some("./feed/alerts.xml"), &mock_fs).unwrap();
+
+    assert_eq!(
+        mock_fs.renamed_files.borrow().as_slice(),
+        [(
+            "./feed/alerts.xml.tmp".to_string(),
+            "./feed/alerts.xml".to_string()
+        )]
+    );
+    assert!(!mock_fs.was_file_written("./feed/alerts.xml.tmp"));
+    assert!(mock_fs.was_file_written("./feed/alerts.xml"));
+}
+
+#[test]
+fn test_write_channel_partial_write_leaves_no_truncated_feed() {
+    let mock_fs = MockFileSystem::with_failing_writes(20);
+    let channel = create_feed_from_settings(&test_settings());
+
+    let result = write_channel(&channel, Some("./feed/alerts.xml"), &mock_fs);
+
+    assert!(result.is_err());
+    // The truncated temp file is cleaned up and never renamed over the feed
+    assert!(mock_fs.renamed_files.borrow().is_empty());
+    assert!(!mock_fs.was_file_written("./feed/alerts.xml.tmp"));
+    assert!(!mock_fs.was_file_written("./feed/alerts.xml"));
+}
+
+fn test_settings() -> FeedSettings {
+    FeedSettings {
+        id: "alerts".to_string(),
+        title: "Alerts".to_string(),
+        link: "https://example.com".to_string(),
+        description: "System alerts".to_string(),
+        max_item_age_seconds: None,
+        max_items: None,
+        max_served_items: None,
+    }
+}
+
+#[test]
+fn test_real_file_system_write_atomic_replaces_file() {
+    let directory = std::env::temp_dir().join(format!("feed-crafter-{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&directory).unwrap();
+    let path = directory.join("alerts.xml").to_string_lossy().to_string();
+    std::fs::write(&path, "old contents").unwrap();
+
+    RealFileSystem.write_atomic(&path, "new contents").unwrap();
+
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "new contents");
+    assert!(!std::path::Path::new(&format!("{}.tmp", path)).exists());
+    std::fs::remove_dir_all(&directory).unwrap();
+}
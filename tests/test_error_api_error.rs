@@ -0,0 +1,121 @@
+mod common;
+
+use axum::{
+    Router,
+    body::Body,
+    http::{Request, StatusCode, header},
+    response::{IntoResponse, Response},
+    routing::{delete, post},
+};
+use common::test_app_state;
+use feed_crafter::api::{api_add_feed, api_delete_item};
+use feed_crafter::common::{DEFAULT_FEED, FeedSettings, create_feed_from_settings};
+use feed_crafter::error::{ApiError, FieldError};
+use feed_crafter::storage::StorageError;
+use std::collections::BTreeMap;
+use tower::ServiceExt;
+
+fn app() -> Router {
+    let channel = create_feed_from_settings(&FeedSettings {
+        id: DEFAULT_FEED.to_string(),
+        title: "Notices".to_string(),
+        link: "https://example.com".to_string(),
+        description: "House notices".to_string(),
+        max_item_age_seconds: None,
+        max_items: None,
+        max_served_items: None,
+    });
+    let mut feeds = BTreeMap::new();
+    feeds.insert(DEFAULT_FEED.to_string(), channel);
+    Router::new()
+        .route("/api/feeds", post(api_add_feed))
+        .route("/api/items/{id}", delete(api_delete_item))
+        .with_state(test_app_state(feeds))
+}
+
+async fn json_body(response: Response) -> serde_json::Value {
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    serde_json::from_slice(&body).unwrap()
+}
+
+fn add_feed_request(body: &str) -> Request<Body> {
+    Request::post("/api/feeds")
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from(body.to_string()))
+        .unwrap()
+}
+
+#[tokio::test]
+async fn test_validation_error_lists_fields() {
+    let response = ApiError::Validation(vec![
+        FieldError::new("title", "Title is required"),
+        FieldError::new("link", "Link is required"),
+    ])
+    .into_response();
+
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    let json = json_body(response).await;
+    assert_eq!(json["success"], false);
+    assert_eq!(json["message"], "Title is required, Link is required");
+    assert_eq!(json["error"]["code"], "validation_failed");
+    assert_eq!(json["error"]["fields"][1]["field"], "link");
+
+    let response =
+        ApiError::from(StorageError::Invalid("disk on fire".to_string())).into_response();
+    assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    let json = json_body(response).await;
+    assert_eq!(json["message"], "Internal server error");
+    assert!(json["error"].get("fields").is_none());
+}
+
+#[tokio::test]
+async fn test_feed_errors_use_matching_status() {
+    let app = app();
+
+    let response = app
+        .clone()
+        .oneshot(add_feed_request(
+            r#"{"id": "Bad Id", "title": "", "link": "x", "description": ""}"#,
+        ))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    let json = json_body(response).await;
+    assert_eq!(json["error"]["fields"][0]["field"], "id");
+    assert_eq!(json["error"]["fields"][1]["field"], "title");
+
+    let response = app
+        .clone()
+        .oneshot(add_feed_request("{not json"))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(json_body(response).await["error"]["code"], "bad_request");
+
+    let response = app
+        .oneshot(add_feed_request(&format!(
+            r#"{{"id": "{}", "title": "Again", "link": "https://example.com", "description": ""}}"#,
+            DEFAULT_FEED
+        )))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::CONFLICT);
+    assert_eq!(json_body(response).await["error"]["code"], "conflict");
+}
+
+#[tokio::test]
+async fn test_deleting_missing_item_is_not_found() {
+    let response = app()
+        .oneshot(
+            Request::delete("/api/items/missing")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    assert_eq!(json_body(response).await["error"]["code"], "not_found");
+}
@@ -0,0 +1,234 @@
+use feed_crafter::common::{ItemFields, create_item, create_item_from_fields};
+use feed_crafter::formats::{
+    FeedFormat, FeedLinks, parse_feed_path, render_atom, render_feed, render_json_feed,
+};
+use rss::{Channel, ChannelBuilder};
+
+fn test_channel() -> Channel {
+    let mut channel = ChannelBuilder::default()
+        .title("Test Channel")
+        .link("https://example.com")
+        .description("Test channel description")
+        .last_build_date(chrono::Utc::now().to_rfc2822())
+        .build();
+    channel.set_items(vec![create_item(
+        "Test <Title>".to_string(),
+        Some("Test Description".to_string()),
+        Some("https://example.com/item".to_string()),
+    )]);
+    channel
+}
+
+#[test]
+fn test_render_atom() {
+    let channel = test_channel();
+    let guid = channel.items()[0].guid().unwrap().value().to_string();
+
+    let atom = render_atom("feed", &channel, None);
+    let feed: atom_syndication::Feed = atom.parse().unwrap();
+
+    assert_eq!(feed.title().as_str(), "Test Channel");
+    assert_eq!(feed.entries().len(), 1);
+    let entry = &feed.entries()[0];
+    assert_eq!(entry.id(), format!("urn:uuid:{}", guid));
+    assert_eq!(entry.title().as_str(), "Test <Title>");
+    assert_eq!(entry.links()[0].href(), "https://example.com/item");
+    assert!(entry.published().is_some());
+}
+
+#[test]
+fn test_render_json_feed() {
+    let channel = test_channel();
+    let guid = channel.items()[0].guid().unwrap().value().to_string();
+
+    let json: serde_json::Value = serde_json::from_str(&render_json_feed(&channel, None)).unwrap();
+
+    assert_eq!(json["version"], "https://jsonfeed.org/version/1.1");
+    assert_eq!(json["title"], "Test Channel");
+    assert_eq!(json["home_page_url"], "https://example.com");
+    assert_eq!(json["items"][0]["id"], guid);
+    assert_eq!(json["items"][0]["title"], "Test <Title>");
+    assert_eq!(json["items"][0]["content_text"], "Test Description");
+    assert_eq!(json["items"][0]["url"], "https://example.com/item");
+    assert!(
+        chrono::DateTime::parse_from_rfc3339(json["items"][0]["date_published"].as_str().unwrap())
+            .is_ok()
+    );
+}
+
+#[test]
+fn test_negotiate_feed_format() {
+    assert_eq!(FeedFormat::negotiate(None), FeedFormat::Rss);
+    assert_eq!(FeedFormat::negotiate(Some("*/*")), FeedFormat::Rss);
+    assert_eq!(
+        FeedFormat::negotiate(Some("application/atom+xml")),
+        FeedFormat::Atom
+    );
+    assert_eq!(
+        FeedFormat::negotiate(Some("application/rss+xml;q=0.5, application/feed+json")),
+        FeedFormat::Json
+    );
+    assert_eq!(
+        FeedFormat::negotiate(Some("application/atom+xml;q=0.2, application/xml;q=0.9")),
+        FeedFormat::Rss
+    );
+}
+
+#[test]
+fn test_render_item_categories_and_enclosure() {
+    let mut channel = test_channel();
+    channel.set_items(vec![create_item_from_fields(ItemFields {
+        title: "Alert".to_string(),
+        author: Some("Alert Bot".to_string()),
+        categories: vec![rss::Category {
+            name: "critical".to_string(),
+            domain: None,
+        }],
+        enclosure: Some(rss::Enclosure {
+            url: "https://example.com/disk.log".to_string(),
+            length: "2048".to_string(),
+            mime_type: "text/plain".to_string(),
+        }),
+        ..Default::default()
+    })]);
+
+    let feed: atom_syndication::Feed = render_atom("feed", &channel, None).parse().unwrap();
+    let entry = &feed.entries()[0];
+    assert_eq!(entry.categories()[0].term(), "critical");
+    assert_eq!(entry.authors()[0].name(), "Alert Bot");
+    let enclosure = entry
+        .links()
+        .iter()
+        .find(|l| l.rel() == "enclosure")
+        .unwrap();
+    assert_eq!(enclosure.href(), "https://example.com/disk.log");
+    assert_eq!(enclosure.length(), Some("2048"));
+
+    let json: serde_json::Value = serde_json::from_str(&render_json_feed(&channel, None)).unwrap();
+    let item = &json["items"][0];
+    assert_eq!(item["tags"][0], "critical");
+    assert_eq!(item["authors"][0]["name"], "Alert Bot");
+    assert_eq!(item["attachments"][0]["mime_type"], "text/plain");
+    assert_eq!(item["attachments"][0]["size_in_bytes"], 2048);
+}
+
+#[test]
+fn test_parse_feed_path() {
+    assert_eq!(
+        parse_feed_path("/feed.xml"),
+        Some(("feed", FeedFormat::Rss))
+    );
+    assert_eq!(
+        parse_feed_path("/feeds/news.atom"),
+        Some(("news", FeedFormat::Atom))
+    );
+    assert_eq!(parse_feed_path("/feeds/news.txt"), None);
+    assert_eq!(parse_feed_path("/news.json"), None);
+    assert_eq!(parse_feed_path("/feeds/../news.json"), None);
+}
+
+#[test]
+fn test_render_feed_links() {
+    let channel = test_channel();
+    let links = FeedLinks {
+        base_url: "https://feeds.example.com".to_string(),
+        self_url: "https://feeds.example.com/feeds/news.xml".to_string(),
+        hub_url: "https://feeds.example.com/hub".to_string(),
+    };
+
+    let rss: rss::Channel = render_feed("news", &channel, FeedFormat::Rss, Some(&links))
+        .parse()
+        .unwrap();
+    let atom_links = rss.atom_ext().unwrap().links();
+    assert!(
+        atom_links
+            .iter()
+            .any(|l| l.rel() == "hub" && l.href() == "https://feeds.example.com/hub")
+    );
+    assert!(
+        atom_links
+            .iter()
+            .any(|l| l.rel() == "self" && l.href() == "https://feeds.example.com/feeds/news.xml")
+    );
+    // Without links the channel is written as stored
+    assert!(
+        render_feed("news", &channel, FeedFormat::Rss, None)
+            .find("atom:link")
+            .is_none()
+    );
+
+    let feed: atom_syndication::Feed =
+        render_feed("news", &channel, FeedFormat::Atom, Some(&links))
+            .parse()
+            .unwrap();
+    assert!(feed.links().iter().any(|l| l.rel() == "hub"));
+
+    let json: serde_json::Value = serde_json::from_str(&render_feed(
+        "news",
+        &channel,
+        FeedFormat::Json,
+        Some(&links),
+    ))
+    .unwrap();
+    assert_eq!(json["feed_url"], "https://feeds.example.com/feeds/news.xml");
+    assert_eq!(json["hubs"][0]["type"], "WebSub");
+    assert_eq!(json["hubs"][0]["url"], "https://feeds.example.com/hub");
+}
+
+#[test]
+fn test_render_feed_resolves_uploaded_enclosures() {
+    let mut channel = test_channel();
+    let enclosure = |url: &str| {
+        Some(rss::Enclosure {
+            url: url.to_string(),
+            length: "100".to_string(),
+            mime_type: "image/png".to_string(),
+        })
+    };
+    channel.set_items(vec![
+        create_item_from_fields(ItemFields {
+            title: "Upload".to_string(),
+            enclosure: enclosure("/attachments/photo.png"),
+            ..Default::default()
+        }),
+        create_item_from_fields(ItemFields {
+            title: "Linked".to_string(),
+            enclosure: enclosure("https://cdn.example.com/photo.png"),
+            ..Default::default()
+        }),
+    ]);
+    let links = FeedLinks {
+        base_url: "https://feeds.example.com".to_string(),
+        self_url: "https://feeds.example.com/feed.xml".to_string(),
+        hub_url: "https://feeds.example.com/hub".to_string(),
+    };
+
+    let rss: rss::Channel = render_feed("feed", &channel, FeedFormat::Rss, Some(&links))
+        .parse()
+        .unwrap();
+    assert_eq!(
+        rss.items()[0].enclosure().unwrap().url(),
+        "https://feeds.example.com/attachments/photo.png"
+    );
+    assert_eq!(
+        rss.items()[1].enclosure().unwrap().url(),
+        "https://cdn.example.com/photo.png"
+    );
+
+    let json: serde_json::Value = serde_json::from_str(&render_feed(
+        "feed",
+        &channel,
+        FeedFormat::Json,
+        Some(&links),
+    ))
+    .unwrap();
+    assert_eq!(
+        json["items"][0]["attachments"][0]["url"],
+        "https://feeds.example.com/attachments/photo.png"
+    );
+    // The stored channel keeps the relative URL
+    assert_eq!(
+        channel.items()[0].enclosure().unwrap().url(),
+        "/attachments/photo.png"
+    );
+}
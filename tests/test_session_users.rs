@@ -0,0 +1,220 @@
+mod common;
+
+use axum::{
+    Router,
+    body::Body,
+    extract::ConnectInfo,
+    http::{HeaderMap, HeaderValue, Request, StatusCode, header},
+    routing::post,
+};
+use common::{TempEnv, acquire_env_lock, test_app_state};
+use feed_crafter::common::AppState;
+use feed_crafter::session::{
+    SessionStore, UserError, UserStore, hash_password, is_local_request, safe_redirect_target,
+    session_token, verify_password,
+};
+use feed_crafter::web::web_setup;
+use std::collections::BTreeMap;
+use std::fs;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use tower::ServiceExt;
+
+#[test]
+fn test_password_hashing() {
+    let hash = hash_password("correct horse");
+
+    assert!(hash.starts_with("$argon2"));
+    assert!(!hash.contains("correct horse"));
+    assert!(verify_password("correct horse", &hash));
+    assert!(!verify_password("wrong horse", &hash));
+    assert!(!verify_password("correct horse", "not a hash"));
+    // Each hash uses its own salt
+    assert_ne!(hash, hash_password("correct horse"));
+}
+
+#[test]
+fn test_user_store_accounts() {
+    let _guard = acquire_env_lock();
+    let mut temp_env = TempEnv::new();
+    let path = std::env::temp_dir().join("feed-crafter-users.json");
+    let _ = fs::remove_file(&path);
+    temp_env.set("USERS_FILE", &path.to_string_lossy());
+
+    let mut users = UserStore::load().unwrap();
+    assert!(users.is_empty());
+    assert!(users.create("alice", "short").is_err());
+    assert!(users.create("alice", "password123").is_ok());
+    assert!(users.create("alice", "password456").is_err());
+
+    // The last account can't be removed
+    assert!(!users.delete("alice").unwrap());
+
+    let mut reloaded = UserStore::load().unwrap();
+    assert!(reloaded.verify("alice", "password123"));
+    assert!(!reloaded.verify("alice", "password456"));
+    assert!(!reloaded.verify("bob", "password123"));
+
+    reloaded.create("bob", "password456").unwrap();
+    assert!(reloaded.delete("alice").unwrap());
+    assert_eq!(reloaded.users().len(), 1);
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_session_store() {
+    let mut sessions = SessionStore::new(3600);
+    let token = sessions.create("alice");
+
+    let session = sessions.get(&token).unwrap();
+    assert_eq!(session.username, "alice");
+    assert!(session.verify_csrf(&session.csrf_token).is_ok());
+    assert!(session.verify_csrf("").is_err());
+    assert!(session.verify_csrf("forged").is_err());
+    assert!(sessions.get("unknown").is_none());
+
+    sessions.remove_user("alice");
+    assert!(sessions.get(&token).is_none());
+
+    let mut expired = SessionStore::new(0);
+    let token = expired.create("alice");
+    assert!(expired.get(&token).is_none());
+}
+
+#[test]
+fn test_session_cookie_parsing() {
+    let mut headers = HeaderMap::new();
+    assert!(session_token(&headers).is_none());
+
+    headers.insert(
+        header::COOKIE,
+        HeaderValue::from_static("theme=dark; feedcrafter_session=abc123"),
+    );
+    assert_eq!(session_token(&headers).as_deref(), Some("abc123"));
+}
+
+#[test]
+fn test_safe_redirect_target() {
+    assert_eq!(safe_redirect_target(Some("/manage/feed")), "/manage/feed");
+    assert_eq!(safe_redirect_target(Some("//evil.example")), "/");
+    assert_eq!(safe_redirect_target(Some("https://evil.example")), "/");
+    assert_eq!(safe_redirect_target(Some("/\\evil.example")), "/");
+    assert_eq!(safe_redirect_target(Some("/\t/evil.example")), "/");
+    assert_eq!(
+        safe_redirect_target(Some("/manage/feed?page=2")),
+        "/manage/feed?page=2"
+    );
+    assert_eq!(safe_redirect_target(None), "/");
+}
+
+#[test]
+fn test_failed_user_save_leaves_accounts_unchanged() {
+    let _guard = acquire_env_lock();
+    let mut temp_env = TempEnv::new();
+    let path = std::env::temp_dir()
+        .join("feed-crafter-missing-dir")
+        .join("users.json");
+    temp_env.set("USERS_FILE", &path.to_string_lossy());
+
+    let mut users = UserStore::load().unwrap();
+    assert!(matches!(
+        users.create("alice", "password123"),
+        Err(UserError::Save(_))
+    ));
+    assert!(users.is_empty());
+}
+
+#[test]
+fn test_is_local_request() {
+    let local = ConnectInfo(SocketAddr::from(([127, 0, 0, 1], 50000)));
+    let remote = ConnectInfo(SocketAddr::from(([192, 168, 1, 20], 50000)));
+    let mut forwarded = HeaderMap::new();
+    forwarded.insert("x-forwarded-for", HeaderValue::from_static("203.0.113.9"));
+
+    assert!(is_local_request(Some(&local), &HeaderMap::new()));
+    assert!(!is_local_request(Some(&remote), &HeaderMap::new()));
+    assert!(!is_local_request(None, &HeaderMap::new()));
+    // A proxy on the same machine passes on requests from anywhere
+    assert!(!is_local_request(Some(&local), &forwarded));
+}
+
+// State without any accounts
+fn setup_state() -> AppState {
+    let _guard = acquire_env_lock();
+    let mut temp_env = TempEnv::new();
+    let path =
+        std::env::temp_dir().join(format!("feed-crafter-users-{}.json", uuid::Uuid::new_v4()));
+    temp_env.set("USERS_FILE", &path.to_string_lossy());
+    let mut state = test_app_state(BTreeMap::new());
+    state.users = Arc::new(Mutex::new(UserStore::load().unwrap()));
+    state
+}
+
+fn setup_request(peer: [u8; 4], body: &str) -> Request<Body> {
+    let mut request = Request::post("/setup")
+        .header(header::CONTENT_TYPE, "application/x-www-form-urlencoded")
+        .body(Body::from(body.to_string()))
+        .unwrap();
+    request
+        .extensions_mut()
+        .insert(ConnectInfo(SocketAddr::from((peer, 50000))));
+    request
+}
+
+#[test]
+fn test_setup_without_token_is_only_allowed_locally() {
+    let state = setup_state();
+    let app = Router::new()
+        .route("/setup", post(web_setup))
+        .with_state(state.clone());
+    // SETUP_TOKEN is read while the handler runs
+    let _guard = acquire_env_lock();
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let status = |peer: [u8; 4], body: &str| {
+        runtime
+            .block_on(app.clone().oneshot(setup_request(peer, body)))
+            .unwrap()
+            .status()
+    };
+
+    assert_eq!(
+        status([203, 0, 113, 9], "username=intruder&password=password123"),
+        StatusCode::FORBIDDEN
+    );
+    assert!(state.users.lock().unwrap().is_empty());
+    assert_eq!(
+        status([127, 0, 0, 1], "username=owner&password=password123"),
+        StatusCode::SEE_OTHER
+    );
+    assert!(state.users.lock().unwrap().verify("owner", "password123"));
+}
+
+#[test]
+fn test_setup_with_token_requires_it() {
+    let state = setup_state();
+    let app = Router::new()
+        .route("/setup", post(web_setup))
+        .with_state(state.clone());
+    let _guard = acquire_env_lock();
+    let mut temp_env = TempEnv::new();
+    temp_env.set("SETUP_TOKEN", "let-me-in");
+
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let status = |body: &str| {
+        runtime
+            .block_on(app.clone().oneshot(setup_request([203, 0, 113, 9], body)))
+            .unwrap()
+            .status()
+    };
+
+    assert_eq!(
+        status("username=intruder&password=password123&setup_token=guess"),
+        StatusCode::FORBIDDEN
+    );
+    assert!(state.users.lock().unwrap().is_empty());
+    assert_eq!(
+        status("username=owner&password=password123&setup_token=let-me-in"),
+        StatusCode::SEE_OTHER
+    );
+    assert!(!state.users.lock().unwrap().is_empty());
+}
@@ -0,0 +1,118 @@
+use feed_crafter::common::{
+    FeedSettings, create_feed_from_settings, create_item, feed_max_item_age,
+};
+use feed_crafter::storage::{SqliteStorage, Storage};
+use rss::{Channel, Item};
+
+fn test_channel() -> Channel {
+    create_feed_from_settings(&FeedSettings {
+        id: "alerts".to_string(),
+        title: "Alerts".to_string(),
+        link: "https://example.com".to_string(),
+        description: "System alerts".to_string(),
+        max_item_age_seconds: Some(3600),
+        max_items: None,
+        max_served_items: None,
+    })
+}
+
+fn guid(item: &Item) -> String {
+    item.guid().unwrap().value().to_string()
+}
+
+#[test]
+fn test_sqlite_save_and_load_feed() {
+    let storage = SqliteStorage::open(":memory:").unwrap();
+    let mut channel = test_channel();
+    channel.set_items(vec![
+        create_item("Newest".to_string(), None, None),
+        create_item("Oldest".to_string(), Some("Description".to_string()), None),
+    ]);
+
+    storage.save_feed("alerts", &channel).unwrap();
+
+    assert_eq!(storage.list_feeds().unwrap(), vec!["alerts".to_string()]);
+    let loaded = storage.load_feed("alerts").unwrap();
+    assert_eq!(loaded.title(), "Alerts");
+    assert_eq!(feed_max_item_age(&loaded), Some(3600));
+    assert_eq!(loaded.items(), channel.items());
+}
+
+#[test]
+fn test_sqlite_item_operations() {
+    let storage = SqliteStorage::open(":memory:").unwrap();
+    let mut channel = test_channel();
+    storage.save_feed("alerts", &channel).unwrap();
+
+    let first = create_item("First".to_string(), None, None);
+    let second = create_item("Second".to_string(), None, None);
+    channel.set_items(vec![first.clone()]);
+    storage.insert_item("alerts", &channel, &first).unwrap();
+    channel.set_items(vec![second.clone(), first.clone()]);
+    storage.insert_item("alerts", &channel, &second).unwrap();
+
+    // Newest items are listed first
+    let items = storage.list_items("alerts").unwrap();
+    assert_eq!(items, vec![second.clone(), first.clone()]);
+
+    let mut updated = first.clone();
+    updated.set_title("First (edited)".to_string());
+    storage.update_item("alerts", &channel, &updated).unwrap();
+    let fetched = storage.get_item("alerts", &guid(&first)).unwrap().unwrap();
+    assert_eq!(fetched.title(), Some("First (edited)"));
+
+    storage
+        .delete_items("alerts", &channel, &[guid(&second)])
+        .unwrap();
+    assert!(
+        storage
+            .get_item("alerts", &guid(&second))
+            .unwrap()
+            .is_none()
+    );
+    assert_eq!(storage.list_items("alerts").unwrap().len(), 1);
+}
+
+#[test]
+fn test_sqlite_rename_and_delete_feed() {
+    let storage = SqliteStorage::open(":memory:").unwrap();
+    let mut channel = test_channel();
+    let item = create_item("Item".to_string(), None, None);
+    channel.set_items(vec![item.clone()]);
+    storage.save_feed("alerts", &channel).unwrap();
+
+    storage.rename_feed("alerts", "incidents").unwrap();
+    assert_eq!(storage.list_feeds().unwrap(), vec!["incidents".to_string()]);
+    assert!(
+        storage
+            .get_item("incidents", &guid(&item))
+            .unwrap()
+            .is_some()
+    );
+
+    storage.delete_feed("incidents").unwrap();
+    assert!(storage.list_feeds().unwrap().is_empty());
+    assert!(storage.list_items("incidents").unwrap().is_empty());
+}
+
+#[test]
+fn test_sqlite_quarantined_feed_is_not_listed() {
+    let storage = SqliteStorage::open(":memory:").unwrap();
+    let mut channel = test_channel();
+    let item = create_item("Item".to_string(), None, None);
+    channel.set_items(vec![item.clone()]);
+    storage.save_feed("alerts", &channel).unwrap();
+
+    storage
+        .quarantine_feed("alerts", "alerts.corrupt-20240101000000")
+        .unwrap();
+
+    assert!(storage.list_feeds().unwrap().is_empty());
+    // The items are kept with the quarantined feed
+    assert!(
+        storage
+            .get_item("alerts.corrupt-20240101000000", &guid(&item))
+            .unwrap()
+            .is_some()
+    );
+}
@@ -0,0 +1,39 @@
+mod common;
+
+use common::MockFileSystem;
+use feed_crafter::storage::{Storage, XmlStorage};
+
+const FEED_XML: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<rss version="2.0">
+    <channel>
+        <title>Test Feed</title>
+        <description>A test RSS feed</description>
+        <link>https://example.com</link>
+    </channel>
+</rss>"#;
+
+#[test]
+fn test_check_passes_for_readable_feeds() {
+    let storage = XmlStorage::new(MockFileSystem::with_existing_file(FEED_XML.to_string()));
+
+    assert!(storage.check().is_ok());
+    // The probe file doesn't stay behind
+    assert!(!storage.fs.was_file_written("./feed/.health-check"));
+}
+
+#[test]
+fn test_check_fails_for_unreadable_feed() {
+    let storage = XmlStorage::new(MockFileSystem::with_existing_file(
+        "<rss><channel><title>Trunc".to_string(),
+    ));
+
+    let error = storage.check().unwrap_err();
+    assert!(error.to_string().contains("Feed 'feed' can't be read"));
+}
+
+#[test]
+fn test_check_fails_when_directory_is_not_writable() {
+    let storage = XmlStorage::new(MockFileSystem::with_failing_writes(0));
+
+    assert!(storage.check().is_err());
+}
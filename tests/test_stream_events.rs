@@ -0,0 +1,144 @@
+mod common;
+
+use axum::extract::{Path, State};
+use axum::{
+    Router,
+    body::Body,
+    http::{Request, StatusCode},
+    routing::get,
+};
+use common::test_app_state;
+use feed_crafter::api::api_stream;
+use feed_crafter::audit::AuditActor;
+use feed_crafter::common::{
+    AppState, FeedSettings, ItemFields, add_item, create_feed_from_settings, create_item,
+    delete_item, edit_item,
+};
+use futures_util::StreamExt;
+use std::collections::BTreeMap;
+use tower::ServiceExt;
+
+fn state_with_feeds(ids: &[&str]) -> AppState {
+    let mut feeds = BTreeMap::new();
+    for id in ids {
+        let channel = create_feed_from_settings(&FeedSettings {
+            id: id.to_string(),
+            title: id.to_string(),
+            link: "https://example.com".to_string(),
+            description: "Test feed".to_string(),
+            max_item_age_seconds: None,
+            max_items: None,
+            max_served_items: None,
+        });
+        feeds.insert(id.to_string(), channel);
+    }
+    let state = test_app_state(feeds.clone());
+    for (id, channel) in &feeds {
+        state.storage.save_feed(id, channel).unwrap();
+    }
+    state
+}
+
+fn add(state: &AppState, feed_id: &str, title: &str) -> String {
+    add_item(
+        State(state.clone()),
+        Path(feed_id.to_string()),
+        create_item(title.to_string(), None, None),
+        &AuditActor::api(None),
+    )
+    .unwrap()
+    .unwrap()
+    .value()
+    .to_string()
+}
+
+// Splits Server-Sent Events into their event name and JSON data
+fn parse_events(text: &str) -> Vec<(String, serde_json::Value)> {
+    text.split("\n\n")
+        .filter_map(|block| {
+            let event = block.lines().find_map(|l| l.strip_prefix("event: "))?;
+            let data = block.lines().find_map(|l| l.strip_prefix("data: "))?;
+            Some((event.to_string(), serde_json::from_str(data).unwrap()))
+        })
+        .collect()
+}
+
+#[tokio::test]
+async fn test_stream_sends_changes_to_the_feed() {
+    let state = state_with_feeds(&["alerts", "news"]);
+    let app = Router::new()
+        .route("/api/stream", get(api_stream))
+        .with_state(state.clone());
+
+    let response = app
+        .oneshot(
+            Request::get("/api/stream?feed=alerts")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()["content-type"], "text/event-stream");
+
+    let id = add(&state, "alerts", "Outage");
+    // Other feeds aren't sent
+    add(&state, "news", "Release");
+    edit_item(
+        State(state.clone()),
+        Path(("alerts".to_string(), id.clone())),
+        ItemFields {
+            title: "Outage resolved".to_string(),
+            ..Default::default()
+        },
+        None,
+        None,
+        &AuditActor::api(None),
+    )
+    .unwrap()
+    .unwrap();
+    delete_item(
+        State(state.clone()),
+        Path(("alerts".to_string(), id.clone())),
+        &AuditActor::api(None),
+    )
+    .unwrap()
+    .unwrap();
+
+    let mut body = response.into_body().into_data_stream();
+    let mut text = String::new();
+    while parse_events(&text).len() < 3 {
+        let chunk = tokio::time::timeout(std::time::Duration::from_secs(5), body.next())
+            .await
+            .expect("Timed out waiting for stream events")
+            .unwrap()
+            .unwrap();
+        text.push_str(std::str::from_utf8(&chunk).unwrap());
+    }
+
+    let events = parse_events(&text);
+    let names: Vec<&str> = events.iter().map(|(name, _)| name.as_str()).collect();
+    assert_eq!(names, vec!["item.created", "item.updated", "item.deleted"]);
+    assert!(events.iter().all(|(_, data)| data["feed"] == "alerts"));
+    assert_eq!(events[1].1["item"]["title"], "Outage resolved");
+    assert_eq!(events[1].1["previous"]["title"], "Outage");
+    assert_eq!(events[2].1["item"]["id"], id);
+}
+
+#[tokio::test]
+async fn test_stream_of_unknown_feed_is_not_found() {
+    let state = state_with_feeds(&["alerts"]);
+    let app = Router::new()
+        .route("/api/stream", get(api_stream))
+        .with_state(state);
+
+    let response = app
+        .oneshot(
+            Request::get("/api/stream?feed=missing")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
@@ -0,0 +1,39 @@
+mod common;
+
+use axum::{
+    Router,
+    body::Body,
+    http::{Request, StatusCode},
+    routing::get,
+};
+use common::{acquire_env_lock, test_app_state};
+use feed_crafter::common::FEED_DIRECTORY;
+use feed_crafter::web::health_check;
+use std::collections::BTreeMap;
+use std::path::Path;
+use tower::ServiceExt;
+
+#[tokio::test]
+async fn test_health_check_relies_on_the_storage_check() {
+    let state = {
+        let _guard = acquire_env_lock();
+        test_app_state(BTreeMap::new())
+    };
+
+    let response = Router::new()
+        .route("/health", get(health_check))
+        .with_state(state)
+        .oneshot(Request::get("/health").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["checks"]["storage"]["status"], "healthy");
+    assert_eq!(json["checks"].as_object().unwrap().len(), 1);
+
+    // The probe leaves nothing behind in the feed directory
+    assert!(!Path::new(FEED_DIRECTORY).join(".health_temp").exists());
+}
@@ -0,0 +1,225 @@
+mod common;
+
+use axum::extract::{Path, State};
+use axum::{
+    Router,
+    body::Body,
+    http::{HeaderMap, Request, StatusCode, header},
+    routing::get,
+};
+use common::{TempEnv, acquire_env_lock, test_app_state};
+use feed_crafter::audit::AuditActor;
+use feed_crafter::cache::{ContentEncoding, feed_cache_control};
+use feed_crafter::common::{
+    AppState, FeedSettings, ItemExpiry, add_item, create_feed_from_settings, create_item,
+    set_item_expiry,
+};
+use feed_crafter::web::serve_file;
+use std::collections::BTreeMap;
+use std::io::Read;
+use tower::ServiceExt;
+
+fn alerts_state() -> AppState {
+    let channel = create_feed_from_settings(&FeedSettings {
+        id: "alerts".to_string(),
+        title: "Alerts".to_string(),
+        link: "https://example.com".to_string(),
+        description: "System alerts".to_string(),
+        max_item_age_seconds: None,
+        max_items: None,
+        max_served_items: None,
+    });
+    let mut feeds = BTreeMap::new();
+    feeds.insert("alerts".to_string(), channel.clone());
+    let state = test_app_state(feeds);
+    state.storage.save_feed("alerts", &channel).unwrap();
+    state
+}
+
+fn add(state: &AppState, title: &str) {
+    add_item(
+        State(state.clone()),
+        Path("alerts".to_string()),
+        create_item(title.to_string(), None, None),
+        &AuditActor::api(None),
+    )
+    .unwrap()
+    .unwrap();
+}
+
+async fn get_feed(
+    state: &AppState,
+    headers: &[(header::HeaderName, &str)],
+) -> (StatusCode, HeaderMap, Vec<u8>) {
+    let mut request = Request::get("/feeds/alerts.xml");
+    for (name, value) in headers {
+        request = request.header(name, *value);
+    }
+    let response = Router::new()
+        .route("/feeds/{file}", get(serve_file))
+        .with_state(state.clone())
+        .oneshot(request.body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    let status = response.status();
+    let headers = response.headers().clone();
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    (status, headers, body.to_vec())
+}
+
+#[tokio::test]
+async fn test_unchanged_feeds_are_not_modified() {
+    let state = alerts_state();
+    add(&state, "Outage");
+
+    let (status, headers, body) = get_feed(&state, &[]).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(String::from_utf8(body).unwrap().contains("Outage"));
+    assert!(headers.contains_key(header::CACHE_CONTROL));
+    let etag = headers[header::ETAG].to_str().unwrap().to_string();
+    let last_modified = headers[header::LAST_MODIFIED].to_str().unwrap().to_string();
+    assert!(last_modified.ends_with(" GMT"));
+
+    let (status, headers, body) = get_feed(&state, &[(header::IF_NONE_MATCH, &etag)]).await;
+    assert_eq!(status, StatusCode::NOT_MODIFIED);
+    assert_eq!(headers[header::ETAG], etag.as_str());
+    assert!(body.is_empty());
+    let (status, _, _) = get_feed(&state, &[(header::IF_MODIFIED_SINCE, &last_modified)]).await;
+    assert_eq!(status, StatusCode::NOT_MODIFIED);
+    // A stale ETag wins over a current date
+    let (status, _, _) = get_feed(
+        &state,
+        &[
+            (header::IF_NONE_MATCH, "\"stale\""),
+            (header::IF_MODIFIED_SINCE, &last_modified),
+        ],
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+
+    // Changes replace the cached render
+    add(&state, "Outage resolved");
+    let (status, headers, body) = get_feed(&state, &[(header::IF_NONE_MATCH, &etag)]).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_ne!(headers[header::ETAG], etag.as_str());
+    assert!(String::from_utf8(body).unwrap().contains("Outage resolved"));
+}
+
+#[tokio::test]
+async fn test_expired_items_are_not_served_before_cleanup() {
+    let state = alerts_state();
+    add(&state, "Outage");
+    let mut item = create_item("Maintenance window".to_string(), None, None);
+    set_item_expiry(
+        &mut item,
+        Some(ItemExpiry::At(
+            chrono::Utc::now() + chrono::Duration::milliseconds(1500),
+        )),
+    );
+    add_item(
+        State(state.clone()),
+        Path("alerts".to_string()),
+        item,
+        &AuditActor::api(None),
+    )
+    .unwrap()
+    .unwrap();
+
+    // Rendered and cached while the item is live
+    let (_, headers, body) = get_feed(&state, &[]).await;
+    assert!(
+        String::from_utf8(body)
+            .unwrap()
+            .contains("Maintenance window")
+    );
+    let etag = headers[header::ETAG].to_str().unwrap().to_string();
+
+    // Past its expiry the cached render is replaced, although no cleanup has run
+    tokio::time::sleep(std::time::Duration::from_millis(1600)).await;
+    let (status, headers, body) = get_feed(&state, &[(header::IF_NONE_MATCH, &etag)]).await;
+    assert_eq!(status, StatusCode::OK);
+    let body = String::from_utf8(body).unwrap();
+    assert!(!body.contains("Maintenance window"));
+    assert!(body.contains("Outage"));
+    assert_ne!(headers[header::ETAG], etag.as_str());
+    assert_eq!(state.feeds.lock().unwrap()["alerts"].items().len(), 2);
+}
+
+#[test]
+fn test_feed_cache_control() {
+    let _lock = acquire_env_lock();
+    let mut env = TempEnv::new();
+    env.set("PUBLIC_FEEDS", "true");
+    unsafe { std::env::remove_var("FEED_CACHE_CONTROL") };
+    assert_eq!(feed_cache_control().as_deref(), Some("public, max-age=60"));
+    // Private feeds must not be kept by shared caches
+    env.set("PUBLIC_FEEDS", "false");
+    assert_eq!(feed_cache_control().as_deref(), Some("private, max-age=60"));
+    env.set("FEED_CACHE_CONTROL", "no-cache");
+    assert_eq!(feed_cache_control().as_deref(), Some("no-cache"));
+    env.set("FEED_CACHE_CONTROL", "");
+    assert_eq!(feed_cache_control(), None);
+}
+
+#[tokio::test]
+async fn test_feeds_are_compressed_when_accepted() {
+    let state = alerts_state();
+    add(&state, "Outage");
+    let (_, plain_headers, plain) = get_feed(&state, &[]).await;
+    assert!(!plain_headers.contains_key(header::CONTENT_ENCODING));
+
+    let (_, headers, body) = get_feed(&state, &[(header::ACCEPT_ENCODING, "gzip")]).await;
+    assert_eq!(headers[header::CONTENT_ENCODING], "gzip");
+    assert_eq!(
+        headers[header::ETAG].to_str().unwrap(),
+        format!("W/{}", plain_headers[header::ETAG].to_str().unwrap())
+    );
+    let mut decoded = Vec::new();
+    flate2::read::GzDecoder::new(body.as_slice())
+        .read_to_end(&mut decoded)
+        .unwrap();
+    assert_eq!(decoded, plain);
+
+    let (_, headers, body) = get_feed(&state, &[(header::ACCEPT_ENCODING, "gzip;q=0.5, br")]).await;
+    assert_eq!(headers[header::CONTENT_ENCODING], "br");
+    let mut decoded = Vec::new();
+    brotli::Decompressor::new(body.as_slice(), 4096)
+        .read_to_end(&mut decoded)
+        .unwrap();
+    assert_eq!(decoded, plain);
+
+    // The weak ETag of a compressed copy still validates
+    let etag = headers[header::ETAG].to_str().unwrap().to_string();
+    let (status, _, _) = get_feed(
+        &state,
+        &[
+            (header::ACCEPT_ENCODING, "br"),
+            (header::IF_NONE_MATCH, &etag),
+        ],
+    )
+    .await;
+    assert_eq!(status, StatusCode::NOT_MODIFIED);
+}
+
+#[test]
+fn test_negotiate_content_encoding() {
+    assert_eq!(ContentEncoding::negotiate(None), ContentEncoding::Identity);
+    assert_eq!(
+        ContentEncoding::negotiate(Some("gzip, deflate, br")),
+        ContentEncoding::Brotli
+    );
+    assert_eq!(
+        ContentEncoding::negotiate(Some("br;q=0.2, gzip")),
+        ContentEncoding::Gzip
+    );
+    assert_eq!(
+        ContentEncoding::negotiate(Some("br;q=0, gzip;q=0")),
+        ContentEncoding::Identity
+    );
+    assert_eq!(
+        ContentEncoding::negotiate(Some("deflate")),
+        ContentEncoding::Identity
+    );
+}
@@ -0,0 +1,201 @@
+mod common;
+
+use axum::{
+    Router,
+    extract::State as AxumState,
+    http::{HeaderMap, StatusCode},
+    routing::post,
+};
+use common::test_app_state;
+use feed_crafter::audit::AuditActor;
+use feed_crafter::common::{
+    AppState, FeedSettings, ItemFields, add_item, create_feed_from_settings, create_item, edit_item,
+};
+use feed_crafter::webhooks::{WebhookDelivery, WebhookStore, signature, webhook_signature};
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::net::TcpListener;
+
+// Requests received by the stand-in, and how many of the first ones it should fail
+#[derive(Clone, Default)]
+struct Receiver {
+    requests: Arc<Mutex<Vec<(HeaderMap, String)>>>,
+    failures: Arc<Mutex<usize>>,
+}
+
+async fn receive(
+    AxumState(receiver): AxumState<Receiver>,
+    headers: HeaderMap,
+    body: String,
+) -> StatusCode {
+    receiver.requests.lock().unwrap().push((headers, body));
+    let mut failures = receiver.failures.lock().unwrap();
+    if *failures > 0 {
+        *failures -= 1;
+        StatusCode::INTERNAL_SERVER_ERROR
+    } else {
+        StatusCode::NO_CONTENT
+    }
+}
+
+// Starts a local HTTP server standing in for the webhook's receiver
+async fn stand_in(failures: usize) -> (String, Receiver) {
+    let receiver = Receiver::default();
+    *receiver.failures.lock().unwrap() = failures;
+    let app = Router::new()
+        .route("/hook", post(receive))
+        .with_state(receiver.clone());
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}/hook", listener.local_addr().unwrap());
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    (url, receiver)
+}
+
+fn alerts_state() -> AppState {
+    let channel = create_feed_from_settings(&FeedSettings {
+        id: "alerts".to_string(),
+        title: "Alerts".to_string(),
+        link: "https://example.com".to_string(),
+        description: "System alerts".to_string(),
+        max_item_age_seconds: None,
+        max_items: None,
+        max_served_items: None,
+    });
+    let mut feeds = BTreeMap::new();
+    feeds.insert("alerts".to_string(), channel.clone());
+    let state = test_app_state(feeds);
+    state.storage.save_feed("alerts", &channel).unwrap();
+    state.webhooks.start();
+    state
+}
+
+fn add(state: &AppState, title: &str) -> String {
+    add_item(
+        axum::extract::State(state.clone()),
+        axum::extract::Path("alerts".to_string()),
+        create_item(title.to_string(), None, None),
+        &AuditActor::api(None),
+    )
+    .unwrap()
+    .unwrap()
+    .value()
+    .to_string()
+}
+
+async fn wait_for_deliveries(state: &AppState, count: usize) -> Vec<WebhookDelivery> {
+    for _ in 0..200 {
+        let deliveries = state.webhooks.deliveries();
+        if deliveries.len() >= count {
+            return deliveries;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    panic!("Expected {} webhook deliveries", count);
+}
+
+#[test]
+fn test_signature_is_hmac_sha256() {
+    assert_eq!(
+        signature("key", b"The quick brown fox jumps over the lazy dog"),
+        "sha256=f7bc83f430538424b13298e6aa6fb143ef4d59a14946175997479dbc2d1a3cd8"
+    );
+}
+
+#[tokio::test]
+async fn test_subscribed_changes_are_delivered_signed() {
+    let (url, receiver) = stand_in(0).await;
+    let state = alerts_state();
+    let webhook = state
+        .webhooks
+        .create(
+            url,
+            Some("s3cret".to_string()),
+            vec!["item.updated".to_string()],
+            None,
+        )
+        .unwrap();
+
+    // Only the subscribed event is sent
+    let id = add(&state, "Outage");
+    edit_item(
+        axum::extract::State(state.clone()),
+        axum::extract::Path(("alerts".to_string(), id.clone())),
+        ItemFields {
+            title: "Outage resolved".to_string(),
+            ..Default::default()
+        },
+        None,
+        None,
+        &AuditActor::api(None),
+    )
+    .unwrap()
+    .unwrap();
+
+    let deliveries = wait_for_deliveries(&state, 1).await;
+    assert!(deliveries[0].delivered);
+    assert_eq!(deliveries[0].webhook_id, webhook.id);
+    assert_eq!(deliveries[0].status, Some(204));
+
+    let requests = receiver.requests.lock().unwrap();
+    assert_eq!(requests.len(), 1);
+    let (headers, body) = &requests[0];
+    assert_eq!(headers["x-feedcrafter-event"], "item.updated");
+    // The signature covers the timestamp too, so an old delivery can't be replayed as new
+    let timestamp: i64 = headers["x-feedcrafter-timestamp"]
+        .to_str()
+        .unwrap()
+        .parse()
+        .unwrap();
+    assert!((chrono::Utc::now().timestamp() - timestamp).abs() < 60);
+    assert_eq!(
+        headers["x-feedcrafter-signature"].to_str().unwrap(),
+        webhook_signature("s3cret", timestamp, body)
+    );
+    assert_ne!(
+        headers["x-feedcrafter-signature"].to_str().unwrap(),
+        signature("s3cret", body.as_bytes())
+    );
+    let payload: serde_json::Value = serde_json::from_str(body).unwrap();
+    assert_eq!(payload["feed"], "alerts");
+    assert_eq!(payload["item"]["id"], id);
+    assert_eq!(payload["item"]["title"], "Outage resolved");
+    assert_eq!(payload["previous"]["title"], "Outage");
+}
+
+#[tokio::test]
+async fn test_failed_deliveries_are_retried_and_logged() {
+    let (url, receiver) = stand_in(1).await;
+    let state = alerts_state();
+    state
+        .webhooks
+        .create(url, None, Vec::new(), Some("alerts".to_string()))
+        .unwrap();
+
+    add(&state, "Outage");
+
+    let deliveries = wait_for_deliveries(&state, 2).await;
+    // Newest first, both attempts are the same delivery
+    assert_eq!(deliveries[0].attempt, 2);
+    assert!(deliveries[0].delivered);
+    assert_eq!(deliveries[1].attempt, 1);
+    assert!(!deliveries[1].delivered);
+    assert_eq!(deliveries[1].status, Some(500));
+    assert_eq!(deliveries[0].id, deliveries[1].id);
+
+    let requests = receiver.requests.lock().unwrap();
+    assert_eq!(requests.len(), 2);
+    assert_eq!(requests[0].1, requests[1].1);
+}
+
+#[test]
+fn test_corrupt_webhooks_file_is_an_error() {
+    let path = std::env::temp_dir().join(format!(
+        "feed-crafter-webhooks-{}.json",
+        uuid::Uuid::new_v4()
+    ));
+    std::fs::write(&path, "[{").unwrap();
+
+    assert!(WebhookStore::new(path.clone(), 3, Duration::from_millis(10)).is_err());
+    std::fs::remove_file(&path).unwrap();
+}
@@ -0,0 +1,329 @@
+mod common;
+
+use axum::{
+    Router,
+    body::Body,
+    extract::{Query, State as AxumState},
+    http::{HeaderMap, Request, StatusCode},
+    routing::post,
+};
+use common::test_app_state;
+use feed_crafter::audit::AuditActor;
+use feed_crafter::common::{
+    AppState, FeedSettings, add_item, create_feed_from_settings, create_item, delete_feed,
+    edit_feed,
+};
+use feed_crafter::web::websub_hub;
+use feed_crafter::webhooks::signature;
+use feed_crafter::websub::{HubMode, HubRequest, WebSubHub, is_public_address};
+use std::collections::{BTreeMap, HashMap};
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::net::TcpListener;
+use tower::ServiceExt;
+
+const TOPIC: &str = "http://feeds.example.com/feeds/alerts.atom";
+
+// Content pushed to the stand-in, and whether it echoes verification challenges
+#[derive(Clone, Default)]
+struct Subscriber {
+    pushes: Arc<Mutex<Vec<(HeaderMap, String)>>>,
+    refuse: bool,
+}
+
+async fn verify(
+    AxumState(subscriber): AxumState<Subscriber>,
+    Query(query): Query<HashMap<String, String>>,
+) -> String {
+    if subscriber.refuse {
+        return "no".to_string();
+    }
+    query["hub.challenge"].clone()
+}
+
+async fn receive(
+    AxumState(subscriber): AxumState<Subscriber>,
+    headers: HeaderMap,
+    body: String,
+) -> StatusCode {
+    subscriber.pushes.lock().unwrap().push((headers, body));
+    StatusCode::NO_CONTENT
+}
+
+// Starts a local HTTP server standing in for a subscriber's callback
+async fn stand_in(refuse: bool) -> (String, Subscriber) {
+    let subscriber = Subscriber {
+        refuse,
+        ..Subscriber::default()
+    };
+    let app = Router::new()
+        .route("/callback", post(receive).get(verify))
+        .with_state(subscriber.clone());
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}/callback", listener.local_addr().unwrap());
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    (url, subscriber)
+}
+
+fn alerts_state() -> AppState {
+    let channel = create_feed_from_settings(&FeedSettings {
+        id: "alerts".to_string(),
+        title: "Alerts".to_string(),
+        link: "https://example.com".to_string(),
+        description: "System alerts".to_string(),
+        max_item_age_seconds: None,
+        max_items: None,
+        max_served_items: None,
+    });
+    let mut feeds = BTreeMap::new();
+    feeds.insert("alerts".to_string(), channel.clone());
+    let mut state = test_app_state(feeds);
+    state.storage.save_feed("alerts", &channel).unwrap();
+    // The stand-in subscriber listens on this machine
+    state.websub = Arc::new(
+        WebSubHub::new(
+            std::env::temp_dir().join(format!("feed-crafter-websub-{}.json", uuid::Uuid::new_v4())),
+            3600,
+            true,
+        )
+        .unwrap(),
+    );
+    state.websub.start();
+    state
+}
+
+async fn hub_request(state: &AppState, body: String) -> StatusCode {
+    Router::new()
+        .route("/hub", post(websub_hub))
+        .with_state(state.clone())
+        .oneshot(
+            Request::post("/hub")
+                .header("Host", "feeds.example.com")
+                .header("Content-Type", "application/x-www-form-urlencoded")
+                .body(Body::from(body))
+                .unwrap(),
+        )
+        .await
+        .unwrap()
+        .status()
+}
+
+fn form(mode: &str, callback: &str, topic: &str) -> String {
+    format!(
+        "hub.mode={}&hub.callback={}&hub.topic={}&hub.secret=s3cret",
+        mode,
+        urlencode(callback),
+        urlencode(topic)
+    )
+}
+
+fn urlencode(value: &str) -> String {
+    value.replace(':', "%3A").replace('/', "%2F")
+}
+
+async fn wait_until(mut done: impl FnMut() -> bool) {
+    for _ in 0..200 {
+        if done() {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    panic!("Timed out waiting for the hub");
+}
+
+#[tokio::test]
+async fn test_verified_subscribers_receive_updates() {
+    let (callback, subscriber) = stand_in(false).await;
+    let state = alerts_state();
+
+    let status = hub_request(&state, form("subscribe", &callback, TOPIC)).await;
+    assert_eq!(status, StatusCode::ACCEPTED);
+    wait_until(|| state.websub.subscriptions().len() == 1).await;
+    let subscription = &state.websub.subscriptions()[0];
+    assert_eq!(subscription.topic, TOPIC);
+    // The lease is capped at the hub's longest
+    assert!(subscription.expires <= chrono::Utc::now() + chrono::Duration::seconds(3600));
+
+    add_item(
+        axum::extract::State(state.clone()),
+        axum::extract::Path("alerts".to_string()),
+        create_item("Outage".to_string(), None, None),
+        &AuditActor::api(None),
+    )
+    .unwrap()
+    .unwrap();
+    wait_until(|| subscriber.pushes.lock().unwrap().len() == 1).await;
+    {
+        let pushes = subscriber.pushes.lock().unwrap();
+        let (headers, body) = &pushes[0];
+        assert_eq!(headers["content-type"], "application/atom+xml");
+        assert_eq!(
+            headers["x-hub-signature"].to_str().unwrap(),
+            signature("s3cret", body.as_bytes())
+        );
+        assert!(
+            headers["link"]
+                .to_str()
+                .unwrap()
+                .contains("<http://feeds.example.com/hub>; rel=\"hub\"")
+        );
+        let feed: atom_syndication::Feed = body.parse().unwrap();
+        assert_eq!(feed.entries()[0].title().as_str(), "Outage");
+        assert!(
+            feed.links()
+                .iter()
+                .any(|l| l.rel() == "self" && l.href() == TOPIC)
+        );
+    }
+
+    let status = hub_request(&state, form("unsubscribe", &callback, TOPIC)).await;
+    assert_eq!(status, StatusCode::ACCEPTED);
+    wait_until(|| state.websub.subscriptions().is_empty()).await;
+}
+
+#[tokio::test]
+async fn test_invalid_or_unconfirmed_requests_are_rejected() {
+    let (callback, _) = stand_in(true).await;
+    let state = alerts_state();
+
+    for body in [
+        form("watch", &callback, TOPIC),
+        form("subscribe", "ftp://example.com", TOPIC),
+        form(
+            "subscribe",
+            &callback,
+            "http://feeds.example.com/feeds/missing.atom",
+        ),
+        form("subscribe", &callback, "http://feeds.example.com/other"),
+        // Only feeds served from this origin are topics
+        form(
+            "subscribe",
+            &callback,
+            "http://evil.example/feeds/alerts.atom",
+        ),
+    ] {
+        assert_eq!(hub_request(&state, body).await, StatusCode::BAD_REQUEST);
+    }
+
+    // The callback doesn't echo the challenge, so nothing is subscribed
+    let result = state
+        .websub
+        .verify(HubRequest {
+            mode: HubMode::Subscribe,
+            callback,
+            topic: TOPIC.to_string(),
+            lease_seconds: 60,
+            secret: None,
+        })
+        .await;
+    assert!(result.is_err());
+    assert!(state.websub.subscriptions().is_empty());
+}
+
+#[test]
+fn test_is_public_address() {
+    for address in [
+        "127.0.0.1",
+        "10.1.2.3",
+        "172.16.0.1",
+        "192.168.1.1",
+        "169.254.169.254",
+        "100.64.0.1",
+        "0.0.0.0",
+        "::1",
+        "fd00::1",
+        "fe80::1",
+        "::ffff:127.0.0.1",
+    ] {
+        assert!(
+            !is_public_address(address.parse::<IpAddr>().unwrap()),
+            "{}",
+            address
+        );
+    }
+    assert!(is_public_address("93.184.216.34".parse().unwrap()));
+    assert!(is_public_address("2606:2800:220:1::1".parse().unwrap()));
+}
+
+#[tokio::test]
+async fn test_private_callbacks_are_refused() {
+    let (callback, _) = stand_in(false).await;
+    let state = test_app_state(BTreeMap::new());
+
+    let result = state
+        .websub
+        .verify(HubRequest {
+            mode: HubMode::Subscribe,
+            callback,
+            topic: TOPIC.to_string(),
+            lease_seconds: 60,
+            secret: None,
+        })
+        .await;
+    assert!(result.unwrap_err().contains("private address"));
+    assert!(state.websub.subscriptions().is_empty());
+}
+
+#[test]
+fn test_pending_verifications_are_limited() {
+    let state = test_app_state(BTreeMap::new());
+
+    let permits: Vec<_> = std::iter::from_fn(|| state.websub.try_reserve_verification())
+        .take(100)
+        .collect();
+    assert!(permits.len() < 100);
+    assert!(state.websub.try_reserve_verification().is_none());
+    drop(permits);
+    assert!(state.websub.try_reserve_verification().is_some());
+}
+
+#[tokio::test]
+async fn test_subscriptions_follow_renamed_and_deleted_feeds() {
+    let (callback, _) = stand_in(false).await;
+    let state = alerts_state();
+    assert_eq!(
+        hub_request(&state, form("subscribe", &callback, TOPIC)).await,
+        StatusCode::ACCEPTED
+    );
+    wait_until(|| state.websub.subscriptions().len() == 1).await;
+
+    let settings = FeedSettings {
+        id: "outages".to_string(),
+        title: "Outages".to_string(),
+        link: "https://example.com".to_string(),
+        description: "System outages".to_string(),
+        max_item_age_seconds: None,
+        max_items: None,
+        max_served_items: None,
+    };
+    edit_feed(
+        axum::extract::State(state.clone()),
+        axum::extract::Path("alerts".to_string()),
+        settings,
+    )
+    .unwrap()
+    .unwrap();
+    assert_eq!(
+        state.websub.subscriptions()[0].topic,
+        "http://feeds.example.com/feeds/outages.atom"
+    );
+
+    delete_feed(
+        axum::extract::State(state.clone()),
+        axum::extract::Path("outages".to_string()),
+    )
+    .unwrap()
+    .unwrap();
+    assert!(state.websub.subscriptions().is_empty());
+}
+
+#[test]
+fn test_corrupt_subscriptions_file_is_an_error() {
+    let path =
+        std::env::temp_dir().join(format!("feed-crafter-websub-{}.json", uuid::Uuid::new_v4()));
+    std::fs::write(&path, "{not json").unwrap();
+
+    assert!(WebSubHub::new(path.clone(), 3600, false).is_err());
+    std::fs::remove_file(&path).unwrap();
+}